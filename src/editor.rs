@@ -1,39 +1,69 @@
-use std::{fmt::Display, panic, path::Path};
+use std::{
+    fmt::Display,
+    panic,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
 use clap::Parser;
-use crossterm::event::{Event, read};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, poll, read};
 
 use crate::{
     Cli,
     editor::{
         command::CommandBar,
+        completion::Completion,
+        config::{Config, RhsConfig, load_theme},
         control::{Control, State},
-        event::{Command, Direction},
+        event::{Command, Direction, Motion, MouseAction, Operator},
+        ex::ExCommand,
+        history::Histories,
+        keymap::{Action, Key, Keymap, MapMode, Mapping, parse_keys},
         message::MessageBar,
+        options::{OPTIONS, OptionId, SetAction, Value, parse_set_arg},
+        register::Registers,
         status::StatusBar,
+        timer::{Timer, Timers},
         ui::UiComponent,
-        view::View,
+        view::{MAX_SEARCH_COUNT, SearchDirection, SearchHit, Substitution, View},
+        wildmenu::WildMenu,
     },
-    terminal,
+    terminal::{self, Backend, CaretShape, CrosstermBackend},
 };
 
 pub mod annotated;
 mod command;
+mod completion;
+mod config;
 pub mod control;
 mod event;
+mod ex;
+mod history;
+mod keymap;
 mod message;
+mod options;
+mod register;
 mod status;
+mod timer;
 mod ui;
 mod view;
+mod wildmenu;
 
 const NAME: &str = env!("CARGO_PKG_NAME");
 const QUIT_TIMES: u8 = 2;
+/// Two clicks on the same cell within this time select the word under it.
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(500);
+const FILE_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+const MOUSE_SCROLL_LINES: usize = 3;
+const MAPPING_TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_MAPPING_DEPTH: usize = 100;
 
 #[derive(Default, PartialEq, Eq)]
 enum PromptType {
     Command,
     Search,
     Save,
+    Confirm,
     #[default]
     None,
 }
@@ -101,8 +131,18 @@ impl From<Option<&Path>> for FileInfo {
 pub struct DocumentStatus {
     total_lines: usize,
     current_line: usize,
+    /// Grapheme index of the cursor in its line.
+    current_column: usize,
     modified: bool,
+    readonly: bool,
     file_info: FileInfo,
+    path: Option<PathBuf>,
+    fileformat: String,
+    /// (current match, all matches) of the highlighted search
+    search_count: Option<(usize, usize)>,
+    /// (lines, graphemes) of the selection.
+    selection: Option<(usize, usize)>,
+    mode: &'static str,
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
@@ -120,8 +160,8 @@ impl From<(u16, u16)> for Size {
     }
 }
 
-#[derive(Default)]
-pub struct Editor {
+pub struct Editor<B: Backend = CrosstermBackend> {
+    backend: B,
     should_quit: bool,
     view: View,
     status: StatusBar,
@@ -132,6 +172,15 @@ pub struct Editor {
     size: Size,
     quit_time: u8,
     control: Control,
+    substitution: Option<Substitution>,
+    histories: Histories,
+    history_file: Option<PathBuf>,
+    registers: Registers,
+    wildmenu: WildMenu,
+    keymap: Keymap,
+    pending_keys: Vec<Key>,
+    last_click: Option<(Instant, u16, u16)>,
+    timers: Timers,
 }
 
 impl Editor {
@@ -142,12 +191,17 @@ impl Editor {
             let _ = terminal::terminate();
             current_hook(panic_info);
         }));
-        terminal::init()?;
         let args = Cli::parse();
-        let size: Size = terminal::size()?.into();
+        let mut editor = Editor::with_backend(CrosstermBackend::new()?)?;
 
-        let mut editor = Editor::default();
-        editor.resize(size);
+        editor.history_file = Histories::file();
+        if let Some(histories) = editor
+            .history_file
+            .as_deref()
+            .and_then(|path| Histories::load(path).ok())
+        {
+            editor.histories = histories;
+        }
 
         if editor.view.load(args.path).is_ok() {
             editor.message.update_message(String::from(
@@ -159,21 +213,77 @@ impl Editor {
                 .update_message(String::from("ERR: Could not open file"));
         };
 
+        // Only a config file given on the command line has to exist.
+        let config = match args.config {
+            Some(path) => Config::load(&path).map(Some),
+            None => Config::file()
+                .filter(|path| path.exists())
+                .map(|path| Config::load(&path))
+                .transpose(),
+        };
+        match config.and_then(|config| editor.apply_config(config.unwrap_or_default())) {
+            Ok(()) => {}
+            Err(err) => editor.message.update_message(format!("ERR: {err:#}")),
+        }
+
         editor.refresh_status();
 
         Ok(editor)
     }
+}
+
+impl<B: Backend> Editor<B> {
+    pub fn with_backend(backend: B) -> anyhow::Result<Self> {
+        let size: Size = backend.size()?.into();
+        let mut editor = Self {
+            backend,
+            should_quit: false,
+            view: View::default(),
+            status: StatusBar::default(),
+            message: MessageBar::default(),
+            command: CommandBar::default(),
+            prompt: PromptType::default(),
+            title: String::new(),
+            size: Size::default(),
+            quit_time: 0,
+            control: Control::default(),
+            substitution: None,
+            histories: Histories::default(),
+            history_file: None,
+            registers: Registers::default(),
+            wildmenu: WildMenu::default(),
+            keymap: Keymap::default(),
+            pending_keys: Vec::new(),
+            last_click: None,
+            timers: Timers::default(),
+        };
+        editor.resize(size);
+        editor
+            .timers
+            .repeat(Timer::FileCheck, FILE_CHECK_INTERVAL, Instant::now());
+
+        Ok(editor)
+    }
 
     pub fn run(&mut self) {
         loop {
-            self.refresh_screen();
             self.refresh_status();
+            self.refresh_screen();
             if self.should_quit {
                 break;
             }
 
-            match read() {
-                Ok(event) => self.evalute_event(event),
+            if let Some(at) = self.message.expires_at() {
+                self.timers.schedule_at(Timer::MessageExpiry, at);
+            }
+            // Without any timer there is nothing to do until the next event.
+            let event = match self.timers.timeout(Instant::now()) {
+                Some(timeout) => poll(timeout).and_then(|ready| ready.then(read).transpose()),
+                None => read().map(Some),
+            };
+            match event {
+                Ok(Some(event)) => self.evalute_event(event),
+                Ok(None) => {}
                 Err(_err) => {
                     #[cfg(debug_assertions)]
                     {
@@ -181,21 +291,220 @@ impl Editor {
                     }
                 }
             }
+
+            for timer in self.timers.expired(Instant::now()) {
+                self.handle_timer(timer);
+            }
+        }
+    }
+
+    fn handle_timer(&mut self, timer: Timer) {
+        match timer {
+            Timer::MessageExpiry => self.message.expire(),
+            Timer::FileCheck => {
+                self.status.refresh_branch();
+                if self.view.check_disk_change() {
+                    self.message.update_message(String::from(
+                        "WARNING! The file has been changed since reading it.",
+                    ));
+                }
+            }
+            Timer::MappingTimeout => self.resolve_keys(true),
+        }
+    }
+
+    fn apply_config(&mut self, config: Config) -> anyhow::Result<()> {
+        for (id, value) in config.options() {
+            self.set_value(id, value, false)?;
+        }
+        let keymap = config.keymap;
+        for (mode, mappings) in [
+            (MapMode::Normal, keymap.normal),
+            (MapMode::Visual, keymap.visual),
+            (MapMode::Insert, keymap.insert),
+        ] {
+            for mapping in mappings {
+                let action = match mapping.rhs {
+                    RhsConfig::Keys(keys) => Action::Keys(self.parse_keys(&keys)?),
+                    RhsConfig::Command(command) => Action::Command(command),
+                };
+                let mapping = Mapping {
+                    lhs: self.parse_keys(&mapping.lhs)?,
+                    action,
+                    remap: mapping.remap,
+                };
+                self.keymap.map(mode, mapping)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_keys(&self, notation: &str) -> anyhow::Result<Vec<Key>> {
+        parse_keys(notation, self.view.option(OptionId::Mapleader).as_str())
+    }
+
+    /// The mappings the next key is looked up in. None apply in prompts or while
+    /// a command waits for its motion.
+    fn map_mode(&self) -> Option<MapMode> {
+        if self.in_prompt() || self.control.is_pending() {
+            return None;
+        }
+        match self.control.state() {
+            State::Normal if self.view.has_selection() => Some(MapMode::Visual),
+            State::Normal => Some(MapMode::Normal),
+            State::Insert | State::Replace => Some(MapMode::Insert),
+            State::Command => None,
+        }
+    }
+
+    fn mode_name(&self) -> &'static str {
+        match self.control.state() {
+            State::Normal if self.view.has_selection() => "VISUAL",
+            State::Normal => "NORMAL",
+            State::Insert => "INSERT",
+            State::Replace => "REPLACE",
+            State::Command => "COMMAND",
+        }
+    }
+
+    fn caret_shape(&self) -> CaretShape {
+        if self.in_prompt() {
+            return CaretShape::Bar;
+        }
+        match self.control.state() {
+            State::Insert => CaretShape::Bar,
+            State::Replace => CaretShape::Underline,
+            State::Normal | State::Command => CaretShape::Block,
         }
     }
 
     fn evalute_event(&mut self, event: Event) {
+        if let Event::Key(key) = event {
+            if key.kind == KeyEventKind::Press
+                && (!self.pending_keys.is_empty() || self.map_mode().is_some())
+            {
+                self.pending_keys.push(Key::from(key));
+                self.resolve_keys(false);
+                return;
+            }
+        } else if !self.pending_keys.is_empty() {
+            self.resolve_keys(true);
+        }
+        self.handle_event(event);
+    }
+
+    /// Runs the mappings the pending keys start with and types the keys starting
+    /// none. Keys which may start a longer mapping wait for the next key, unless
+    /// `timeout` ran out for them.
+    fn resolve_keys(&mut self, mut timeout: bool) {
+        self.timers.cancel(Timer::MappingTimeout);
+        while let Some(&key) = self.pending_keys.first() {
+            let mapping = match self.map_mode() {
+                Some(mode) if !timeout && self.keymap.has_longer(mode, &self.pending_keys) => {
+                    self.timers
+                        .schedule_at(Timer::MappingTimeout, Instant::now() + MAPPING_TIMEOUT);
+                    return;
+                }
+                Some(mode) => self.keymap.longest(mode, &self.pending_keys).cloned(),
+                None => None,
+            };
+            timeout = false;
+            match mapping {
+                Some(mapping) => {
+                    self.pending_keys.drain(..mapping.lhs.len());
+                    self.run_mapping(mapping, 0);
+                }
+                None => {
+                    self.pending_keys.remove(0);
+                    self.handle_event(Event::Key(KeyEvent::from(key)));
+                }
+            }
+        }
+    }
+
+    /// Types the keys of `mapping` or runs its command. The keys of a recursive
+    /// mapping are mapped again, except for its own keys at their start.
+    fn run_mapping(&mut self, mapping: Mapping, depth: usize) {
+        let keys = match mapping.action {
+            Action::Command(command) => {
+                if let Err(err) = command
+                    .parse::<ExCommand>()
+                    .and_then(|command| self.execute(command))
+                {
+                    self.message.update_message(format!("ERR: {err}"));
+                }
+                return;
+            }
+            Action::Keys(keys) => keys,
+        };
+        if depth >= MAX_MAPPING_DEPTH {
+            self.message
+                .update_message(String::from("ERR: Recursive mapping"));
+            return;
+        }
+
+        let mut rest = keys.as_slice();
+        if !mapping.remap || rest.starts_with(&mapping.lhs) {
+            let typed = if mapping.remap {
+                mapping.lhs.len()
+            } else {
+                rest.len()
+            };
+            for key in &rest[..typed] {
+                self.handle_event(Event::Key(KeyEvent::from(*key)));
+            }
+            rest = &rest[typed..];
+        }
+        while let Some(&key) = rest.first() {
+            match self
+                .map_mode()
+                .and_then(|mode| self.keymap.longest(mode, rest))
+                .cloned()
+            {
+                Some(inner) => {
+                    rest = &rest[inner.lhs.len()..];
+                    self.run_mapping(inner, depth + 1);
+                }
+                None => {
+                    rest = &rest[1..];
+                    self.handle_event(Event::Key(KeyEvent::from(key)));
+                }
+            }
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) {
+        if let Event::Key(key) = event
+            && self.apply_to_selection(key)
+        {
+            return;
+        }
+        if let Event::Paste(text) = event {
+            self.paste(&text);
+            return;
+        }
         if let Ok(command) = self.control.evaluate(event) {
             if let Command::Resize(size) = command {
                 self.resize(size);
                 return;
             }
+            if let Command::Mouse { action, col, row } = command {
+                self.handle_mouse(action, col, row);
+                return;
+            }
+            if !matches!(
+                command,
+                Command::NextCompletion | Command::PreviousCompletion
+            ) {
+                self.hide_wildmenu();
+            }
 
             match self.prompt {
                 PromptType::Save => self.handle_event_during_save(command),
                 PromptType::Search => self.handle_event_during_search(command),
                 PromptType::None => self.handle_event_no_prompt(command),
                 PromptType::Command => self.handle_event_during_command(command),
+                PromptType::Confirm => self.handle_event_during_confirm(command),
             }
         }
     }
@@ -209,8 +518,14 @@ impl Editor {
 
         match command {
             Command::Move(direction) => self.view.move_point(direction),
+            Command::Insert(c) if self.control.state() == State::Replace => {
+                self.view.replace_char(c);
+            }
             Command::Insert(c) => self.view.insert_char(c),
             Command::Delete => self.view.delete(),
+            Command::Backspace if self.control.state() == State::Replace => {
+                self.view.replace_backspace();
+            }
             Command::Backspace => self.view.delete_backspace(),
             Command::StartOfLine => self.view.move_to_start_of_line(),
             Command::EndOfLine => self.view.move_to_end_of_line(),
@@ -218,63 +533,279 @@ impl Editor {
             Command::Save => self.handle_save(),
             Command::Search => self.set_prompt(PromptType::Search),
             Command::Dismiss => {}
-            Command::Resize(_) | Command::Quit => unreachable!(),
+            Command::Resize(_) | Command::Mouse { .. } | Command::Quit => unreachable!(),
             Command::Switch(State::Command) => self.set_prompt(PromptType::Command),
+            Command::Switch(State::Normal) => self.view.commit_change(),
             Command::Switch(_) => {}
-            Command::NextWord => self.view.move_to_next_word(),
-            Command::PreviousWord => self.view.move_to_previous_word(),
             Command::OpenLineBelow => self.view.open_new_line_below(),
             Command::OpenLineAbove => self.view.open_new_line_above(),
+            Command::MatchBracket => self.view.move_to_matching_bracket(),
+            Command::SearchWordForward => self.search_word(SearchDirection::Forward),
+            Command::SearchWordBackward => self.search_word(SearchDirection::Backward),
+            Command::SearchNext => self.repeat_search(false),
+            Command::SearchPrevious => self.repeat_search(true),
+            Command::Motion {
+                motion: Motion::Mark { name, linewise },
+                ..
+            } => {
+                if let Err(err) = self.view.jump_to_mark(name, linewise) {
+                    self.message.update_message(format!("ERR: {err}"));
+                }
+            }
+            Command::Motion { motion, count } => {
+                self.view.apply_motion(motion, count);
+            }
+            Command::Operator {
+                operator,
+                motion,
+                count,
+            } => {
+                if let Some(text) = self.view.apply_operator(operator, motion, count) {
+                    self.finish_operator(operator, text);
+                }
+            }
+            Command::Scroll {
+                forward,
+                half,
+                count,
+            } => {
+                self.view.scroll(forward, half, count);
+            }
+            Command::Reposition(screen_line) => self.view.reposition(screen_line),
+            Command::SetMark(name) => {
+                if let Err(err) = self.view.set_mark(name) {
+                    self.message.update_message(format!("ERR: {err}"));
+                }
+            }
+            Command::JumpOlder | Command::JumpNewer => {
+                let jumped = if command == Command::JumpOlder {
+                    self.view.jump_older()
+                } else {
+                    self.view.jump_newer()
+                };
+                if let Err(err) = jumped {
+                    self.message.update_message(format!("ERR: {err}"));
+                }
+            }
+            Command::Undo => {
+                if !self.view.undo() {
+                    self.message
+                        .update_message(String::from("Already at oldest change"));
+                }
+            }
+            Command::Redo => {
+                if !self.view.redo() {
+                    self.message
+                        .update_message(String::from("Already at newest change"));
+                }
+            }
+            Command::PreviousHistory
+            | Command::NextHistory
+            | Command::DeleteWordBackward
+            | Command::DeleteToStartOfLine
+            | Command::PasteRegister(_)
+            | Command::NextCompletion
+            | Command::PreviousCompletion => {}
+        }
+    }
+
+    /// Terminals may send line breaks as `\r`.
+    fn paste(&mut self, text: &str) {
+        self.view.clear_selection();
+        self.hide_wildmenu();
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+
+        match self.prompt {
+            PromptType::None => {
+                self.reset_quit_time();
+                self.view.paste(&text);
+                if self.control.state() == State::Normal {
+                    self.view.commit_change();
+                }
+            }
+            PromptType::Search => {
+                self.command.insert_str(&text);
+                let query = self.command.get_value();
+                self.view.search_forward(&query);
+            }
+            PromptType::Command | PromptType::Save => self.command.insert_str(&text),
+            PromptType::Confirm => {}
+        }
+    }
+
+    fn finish_operator(&mut self, operator: Operator, text: String) {
+        self.registers.set('"', text);
+        if operator == Operator::Change {
+            self.control.enter_insert();
+        } else {
+            self.view.commit_change();
+        }
+    }
+
+    /// With a mouse selection in normal mode `y`, `d`, `x` and `c` apply to it,
+    /// any other key drops it.
+    ///
+    /// return: whether the key was used
+    fn apply_to_selection(&mut self, key: KeyEvent) -> bool {
+        if !self.view.has_selection() || key.kind != KeyEventKind::Press {
+            return false;
+        }
+        let operator = match (key.code, key.modifiers) {
+            (KeyCode::Char('y'), KeyModifiers::NONE) => Some(Operator::Yank),
+            (KeyCode::Char('d' | 'x'), KeyModifiers::NONE) => Some(Operator::Delete),
+            (KeyCode::Char('c'), KeyModifiers::NONE) => Some(Operator::Change),
+            _ => None,
+        };
+
+        match operator {
+            Some(operator) if !self.in_prompt() && self.control.state() == State::Normal => {
+                if let Some(text) = self.view.apply_operator_to_selection(operator) {
+                    self.finish_operator(operator, text);
+                }
+                true
+            }
+            _ => {
+                self.view.clear_selection();
+                false
+            }
+        }
+    }
+
+    fn handle_mouse(&mut self, action: MouseAction, col: u16, row: u16) {
+        if !self.view.option(OptionId::Mouse).as_bool() {
+            return;
+        }
+        let view_height = self.size.height.saturating_sub(2);
+
+        match action {
+            MouseAction::Click if row == self.size.height.saturating_sub(1) => {
+                if matches!(
+                    self.prompt,
+                    PromptType::Command | PromptType::Search | PromptType::Save
+                ) {
+                    self.command.click(col);
+                }
+            }
+            MouseAction::Click if row >= view_height => {}
+            _ if self.in_prompt() => {}
+            MouseAction::ScrollUp | MouseAction::ScrollDown => self
+                .view
+                .scroll_lines(action == MouseAction::ScrollDown, MOUSE_SCROLL_LINES),
+            MouseAction::Click => {
+                let now = Instant::now();
+                let double_click = self.last_click.is_some_and(|(time, last_col, last_row)| {
+                    (last_col, last_row) == (col, row)
+                        && now.duration_since(time) <= DOUBLE_CLICK_TIME
+                });
+                if double_click {
+                    self.view.select_word(col, row);
+                    self.last_click = None;
+                } else {
+                    self.view.click(col, row);
+                    self.last_click = Some((now, col, row));
+                }
+            }
+            MouseAction::Drag => self.view.drag(col, row.min(view_height.saturating_sub(1))),
         }
     }
 
     fn handle_event_during_search(&mut self, command: Command) {
         match command {
-            Command::Insert(_) | Command::Backspace | Command::Delete => {
+            Command::Insert(_)
+            | Command::Backspace
+            | Command::Delete
+            | Command::DeleteWordBackward
+            | Command::DeleteToStartOfLine => {
                 self.command.handle_edit_command(command);
                 let query = self.command.get_value();
                 self.view.search_forward(&query);
             }
-            Command::Move(Direction::Up | Direction::Left) => self.view.search_prev(),
-            Command::Move(Direction::Down | Direction::Right) => self.view.search_next(),
+            Command::PasteRegister(name) => {
+                if self.paste_register(name) {
+                    let query = self.command.get_value();
+                    self.view.search_forward(&query);
+                }
+            }
+            Command::NextCompletion => {
+                self.command.handle_edit_command(Command::Insert('\t'));
+                let query = self.command.get_value();
+                self.view.search_forward(&query);
+            }
+            Command::PreviousCompletion => {}
+            Command::Move(Direction::Left | Direction::Right)
+            | Command::StartOfLine
+            | Command::EndOfLine => self.command.handle_edit_command(command),
+            Command::Move(Direction::Up) => self.view.search_prev(),
+            Command::Move(Direction::Down) => self.view.search_next(),
             Command::Dismiss | Command::Switch(State::Normal) => {
                 self.set_prompt(PromptType::None);
                 self.view.dismiss_search();
             }
+            Command::PreviousHistory | Command::NextHistory => {
+                if self.browse_history(command) {
+                    let query = self.command.get_value();
+                    self.view.search_forward(&query);
+                }
+            }
             Command::Enter => {
+                let invalid = self.view.has_invalid_search();
+                let query = self.command.get_value();
+                self.registers.set('/', query.clone());
+                self.histories.search.add(query.clone());
                 self.set_prompt(PromptType::None);
-                self.view.exit_search();
+                self.view.exit_search(query);
+                if invalid {
+                    self.message
+                        .update_message(String::from("ERR: Invalid regular expression"));
+                }
             }
-            Command::Quit
-            | Command::StartOfLine
-            | Command::EndOfLine
-            | Command::Save
-            | Command::Search => {}
+            Command::Quit | Command::Save | Command::Search => {}
             Command::Switch(_) => {}
-            Command::Resize(_) => unreachable!(),
-            Command::NextWord
-            | Command::PreviousWord
-            | Command::OpenLineBelow
-            | Command::OpenLineAbove => {}
+            Command::Resize(_) | Command::Mouse { .. } => unreachable!(),
+            Command::OpenLineBelow
+            | Command::OpenLineAbove
+            | Command::MatchBracket
+            | Command::Undo
+            | Command::Redo
+            | Command::SearchWordForward
+            | Command::SearchWordBackward
+            | Command::SearchNext
+            | Command::SearchPrevious
+            | Command::Motion { .. }
+            | Command::Operator { .. }
+            | Command::Scroll { .. }
+            | Command::Reposition(_)
+            | Command::SetMark(_)
+            | Command::JumpOlder
+            | Command::JumpNewer => {}
         }
     }
 
     fn handle_event_during_save(&mut self, command: Command) {
         match command {
-            Command::Resize(_) => unreachable!(),
-            Command::Move(_)
+            Command::Resize(_) | Command::Mouse { .. } => unreachable!(),
+            Command::Move(Direction::Up | Direction::Down)
             | Command::Quit
-            | Command::StartOfLine
-            | Command::EndOfLine
             | Command::Save
             | Command::Search => {}
-            Command::Insert(_) | Command::Backspace | Command::Delete => {
-                self.command.handle_edit_command(command)
+            Command::Insert(_)
+            | Command::Backspace
+            | Command::Delete
+            | Command::Move(Direction::Left | Direction::Right)
+            | Command::StartOfLine
+            | Command::EndOfLine
+            | Command::DeleteWordBackward
+            | Command::DeleteToStartOfLine => self.command.handle_edit_command(command),
+            Command::PasteRegister(name) => {
+                self.paste_register(name);
             }
+            Command::NextCompletion => self.complete(true),
+            Command::PreviousCompletion => self.complete(false),
             Command::Dismiss => {
                 self.set_prompt(PromptType::None);
                 self.message.update_message("Save aborted.".to_string());
             }
+            Command::PreviousHistory | Command::NextHistory => {}
             Command::Enter => {
                 let file = self.command.get_value();
                 self.save(Some(&file));
@@ -282,60 +813,272 @@ impl Editor {
             }
             // TODO
             Command::Switch(_) => {}
-            Command::NextWord
-            | Command::PreviousWord
-            | Command::OpenLineBelow
-            | Command::OpenLineAbove => {}
+            Command::OpenLineBelow
+            | Command::OpenLineAbove
+            | Command::MatchBracket
+            | Command::Undo
+            | Command::Redo
+            | Command::SearchWordForward
+            | Command::SearchWordBackward
+            | Command::SearchNext
+            | Command::SearchPrevious
+            | Command::Motion { .. }
+            | Command::Operator { .. }
+            | Command::Scroll { .. }
+            | Command::Reposition(_)
+            | Command::SetMark(_)
+            | Command::JumpOlder
+            | Command::JumpNewer => {}
         }
     }
 
     fn handle_event_during_command(&mut self, command: Command) {
         match command {
-            Command::Resize(_) => unreachable!(),
-            Command::Move(_)
+            Command::Resize(_) | Command::Mouse { .. } => unreachable!(),
+            Command::Move(Direction::Up | Direction::Down)
             | Command::Quit
-            | Command::StartOfLine
-            | Command::EndOfLine
             | Command::Save
             | Command::Search => {}
-            Command::Insert(_) | Command::Backspace | Command::Delete => {
-                self.command.handle_edit_command(command)
+            Command::Insert(_)
+            | Command::Backspace
+            | Command::Delete
+            | Command::Move(Direction::Left | Direction::Right)
+            | Command::StartOfLine
+            | Command::EndOfLine
+            | Command::DeleteWordBackward
+            | Command::DeleteToStartOfLine => self.command.handle_edit_command(command),
+            Command::PasteRegister(name) => {
+                self.paste_register(name);
             }
+            Command::NextCompletion => self.complete(true),
+            Command::PreviousCompletion => self.complete(false),
             Command::Dismiss => {
                 self.set_prompt(PromptType::None);
             }
+            Command::PreviousHistory | Command::NextHistory => {
+                self.browse_history(command);
+            }
             Command::Enter => {
                 let com = self.command.get_value();
-                if com == "q" {
-                    self.quit();
-                }
+                self.registers.set(':', com.clone());
+                self.histories.command.add(com.clone());
                 self.set_prompt(PromptType::None);
+                if let Err(err) = com
+                    .parse::<ExCommand>()
+                    .and_then(|command| self.execute(command))
+                {
+                    self.message.update_message(format!("ERR: {err}"));
+                }
             }
             // TODO
             Command::Switch(_) => {}
-            Command::NextWord
-            | Command::PreviousWord
-            | Command::OpenLineBelow
-            | Command::OpenLineAbove => {}
+            Command::OpenLineBelow
+            | Command::OpenLineAbove
+            | Command::MatchBracket
+            | Command::Undo
+            | Command::Redo
+            | Command::SearchWordForward
+            | Command::SearchWordBackward
+            | Command::SearchNext
+            | Command::SearchPrevious
+            | Command::Motion { .. }
+            | Command::Operator { .. }
+            | Command::Scroll { .. }
+            | Command::Reposition(_)
+            | Command::SetMark(_)
+            | Command::JumpOlder
+            | Command::JumpNewer => {}
+        }
+    }
+
+    fn search_word(&mut self, direction: SearchDirection) {
+        match self.view.search_word(direction) {
+            Ok((query, hit)) => {
+                self.registers.set('/', query.clone());
+                self.histories.search.add(query);
+                self.report_search_hit(hit);
+            }
+            Err(err) => self.message.update_message(format!("ERR: {err}")),
         }
     }
+
+    fn repeat_search(&mut self, reverse: bool) {
+        match self.view.repeat_search(reverse) {
+            Ok(hit) => self.report_search_hit(hit),
+            Err(err) => self.message.update_message(format!("ERR: {err}")),
+        }
+    }
+
+    fn report_search_hit(&mut self, hit: SearchHit) {
+        let message = match hit {
+            SearchHit::Match => return,
+            SearchHit::WrappedToTop => "search hit BOTTOM, continuing at TOP",
+            SearchHit::WrappedToBottom => "search hit TOP, continuing at BOTTOM",
+        };
+        self.message.update_message(String::from(message));
+    }
+
+    /// `%` is the current file name.
+    ///
+    /// return: whether the register had any contents
+    fn paste_register(&mut self, name: char) -> bool {
+        let text = match name {
+            '%' => self
+                .view
+                .file_path()
+                .map(|path| path.to_string_lossy().to_string()),
+            _ => self.registers.get(name).map(str::to_string),
+        };
+
+        if let Some(text) = text {
+            self.command.insert_str(&text);
+            return true;
+        }
+        false
+    }
+
+    fn complete(&mut self, forward: bool) {
+        if !self.wildmenu.is_active() {
+            let line = self.command.text_before_caret();
+            let completion = match self.prompt {
+                PromptType::Command => Completion::command(line, &self.buffer_names()),
+                PromptType::Save => Completion::path(line),
+                _ => return,
+            };
+            if completion.candidates.is_empty() {
+                return;
+            }
+            let original = line[completion.start..].to_string();
+            self.wildmenu.show(completion, original);
+        }
+
+        let start = self.wildmenu.start();
+        let text = self.wildmenu.select(forward).to_string();
+        self.command.complete(start, &text);
+        // A single candidate is inserted right away, the next Tab completes anew.
+        if !self.wildmenu.is_visible() {
+            self.wildmenu.hide();
+        }
+    }
+
+    fn hide_wildmenu(&mut self) {
+        if self.wildmenu.hide() {
+            self.view.set_render(true);
+        }
+    }
+
+    fn buffer_names(&self) -> Vec<String> {
+        self.view
+            .file_path()
+            .map(|path| path.to_string_lossy().to_string())
+            .into_iter()
+            .collect()
+    }
+
+    /// return: whether the text was replaced
+    fn browse_history(&mut self, command: Command) -> bool {
+        let history = match self.prompt {
+            PromptType::Search => &mut self.histories.search,
+            PromptType::Command => &mut self.histories.command,
+            _ => return false,
+        };
+        let entry = if command == Command::PreviousHistory {
+            history.previous(&self.command.get_value())
+        } else {
+            history.next()
+        };
+
+        if let Some(entry) = entry.map(str::to_string) {
+            self.command.set_value(entry);
+            return true;
+        }
+        false
+    }
+
+    fn handle_event_during_confirm(&mut self, command: Command) {
+        let Some(mut substitution) = self.substitution.take() else {
+            self.set_prompt(PromptType::None);
+            return;
+        };
+
+        match command {
+            Command::Insert('y') => {
+                self.view.substitute(&mut substitution);
+                if !self.view.next_substitution(&mut substitution) {
+                    self.finish_substitution(substitution);
+                    return;
+                }
+            }
+            Command::Insert('n') => {
+                self.view.skip_substitution(&mut substitution);
+                if !self.view.next_substitution(&mut substitution) {
+                    self.finish_substitution(substitution);
+                    return;
+                }
+            }
+            Command::Insert('a') => {
+                self.view.substitute_all(&mut substitution);
+                self.finish_substitution(substitution);
+                return;
+            }
+            Command::Insert('q') | Command::Dismiss | Command::Switch(State::Normal) => {
+                self.finish_substitution(substitution);
+                return;
+            }
+            _ => {}
+        }
+
+        self.substitution = Some(substitution);
+    }
+
+    fn finish_substitution(&mut self, substitution: Substitution) {
+        self.view.finish_substitution(&substitution);
+        self.set_prompt(PromptType::None);
+        self.report_substitution(&substitution, false);
+    }
+
+    fn report_substitution(&mut self, substitution: &Substitution, count_only: bool) {
+        let (count, lines) = (substitution.count(), substitution.changed_lines());
+        let noun = match (count_only, count == 1) {
+            (false, true) => "substitution",
+            (false, false) => "substitutions",
+            (true, true) => "match",
+            (true, false) => "matches",
+        };
+
+        self.message.update_message(format!(
+            "{count} {noun} on {lines} line{}",
+            if lines == 1 { "" } else { "s" }
+        ));
+    }
+
     fn refresh_screen(&mut self) {
         if self.size.height == 0 || self.size.width == 0 {
             return;
         }
 
-        let _ = terminal::hide_caret();
+        self.backend.set_caret_visible(false);
 
         if self.in_prompt() {
-            self.command.render(self.size.height.saturating_sub(1));
+            self.command
+                .render(&mut self.backend, self.size.height.saturating_sub(1));
         } else {
-            self.message.render(self.size.height.saturating_sub(1));
+            self.message
+                .render(&mut self.backend, self.size.height.saturating_sub(1));
         }
         if self.size.height > 1 {
-            self.status.render(self.size.height.saturating_sub(2));
+            self.status
+                .render(&mut self.backend, self.size.height.saturating_sub(2));
         }
         if self.size.height > 2 {
-            self.view.render(0);
+            if self.view.needs_render() {
+                self.wildmenu.set_render(true);
+            }
+            self.view.render(&mut self.backend, 0);
+        }
+        if self.size.height > 3 {
+            self.wildmenu
+                .render(&mut self.backend, self.size.height.saturating_sub(3));
         }
         let (col, row) = if self.in_prompt() {
             (
@@ -346,17 +1089,31 @@ impl Editor {
             self.view.cursor_pos()
         };
 
-        let _ = terminal::move_caret(col, row);
-        let _ = terminal::show_caret();
-        let _ = terminal::execute();
+        self.backend.move_caret(col, row);
+        self.backend.set_caret_visible(true);
+        let _ = self.backend.set_caret_shape(self.caret_shape());
+        let _ = self.backend.flush();
     }
 
     fn refresh_status(&mut self) {
-        let status = self.view.get_status();
+        let mut status = self.view.get_status();
+        status.mode = self.mode_name();
+        let show_mode = self.view.option(OptionId::Showmode).as_bool()
+            && match self.control.state() {
+                State::Normal => self.view.has_selection(),
+                State::Insert | State::Replace => true,
+                State::Command => false,
+            };
+        self.message.set_mode(show_mode.then_some(status.mode));
 
         let title = &status.file_info.file;
 
-        if title != &self.title && terminal::set_title(format!("{} - {NAME}", title)).is_ok() {
+        if title != &self.title
+            && self
+                .backend
+                .set_title(&format!("{} - {NAME}", title))
+                .is_ok()
+        {
             self.title = title.to_string()
         }
         self.status.update_status(status);
@@ -364,6 +1121,7 @@ impl Editor {
 
     fn resize(&mut self, size: Size) {
         self.size = size;
+        self.backend.resize(size.width, size.height);
         self.view.resize(Size {
             width: size.width,
             height: size.height.saturating_sub(2),
@@ -375,6 +1133,7 @@ impl Editor {
         self.message.resize(size);
         self.status.resize(size);
         self.command.resize(size);
+        self.wildmenu.resize(size);
     }
 
     fn handle_save(&mut self) {
@@ -391,13 +1150,181 @@ impl Editor {
         } else {
             self.view.save()
         };
-        if res.is_ok() {
-            self.message
-                .update_message(String::from("File saved successfully."));
+        match res {
+            Ok(()) => self
+                .message
+                .update_message(String::from("File saved successfully.")),
+            Err(err) => self
+                .message
+                .update_message(format!("Error while saving file: {err}")),
+        }
+    }
+
+    fn execute(&mut self, command: ExCommand) -> anyhow::Result<()> {
+        match command {
+            ExCommand::Quit => self.quit(),
+            ExCommand::Set(args) => self.set_options(&args, false)?,
+            ExCommand::SetLocal(args) => self.set_options(&args, true)?,
+            ExCommand::Substitute(substitute) => {
+                let pattern = substitute.pattern.clone();
+                let flags = substitute.flags;
+                let mut substitution = self.view.substitution(substitute)?;
+
+                if flags.count_only {
+                    self.view.count_substitutions(&mut substitution);
+                } else if flags.confirm {
+                    if self.view.next_substitution(&mut substitution) {
+                        self.substitution = Some(substitution);
+                        self.set_prompt(PromptType::Confirm);
+                        return Ok(());
+                    }
+                } else {
+                    self.view.substitute_all(&mut substitution);
+                    self.view.finish_substitution(&substitution);
+                }
+
+                if substitution.count() == 0 {
+                    anyhow::bail!("Pattern not found: {pattern}");
+                }
+                self.report_substitution(&substitution, flags.count_only);
+            }
+            ExCommand::NoHighlight => self.view.clear_search_highlight(),
+            ExCommand::Colorscheme(Some(name)) => {
+                self.set_value(OptionId::Theme, Value::String(name.into()), false)?;
+            }
+            ExCommand::Colorscheme(None) => {
+                let name = self.backend.theme().name().to_string();
+                self.message.update_message(name);
+            }
+            ExCommand::Map {
+                modes,
+                remap,
+                lhs: Some(lhs),
+                rhs: Some(rhs),
+            } => {
+                let lhs = self.parse_keys(&lhs)?;
+                let rhs = self.parse_keys(&rhs)?;
+                for mode in modes {
+                    self.keymap.map(
+                        *mode,
+                        Mapping {
+                            lhs: lhs.clone(),
+                            action: Action::Keys(rhs.clone()),
+                            remap,
+                        },
+                    )?;
+                }
+            }
+            ExCommand::Map { modes, lhs, .. } => {
+                let prefix = match lhs {
+                    Some(lhs) => self.parse_keys(&lhs)?,
+                    None => Vec::new(),
+                };
+                let listed = self.keymap.list(modes, &prefix);
+                if listed.is_empty() {
+                    anyhow::bail!("No mapping found");
+                }
+                self.message.update_message(listed.join("  "));
+            }
+            ExCommand::Unmap { modes, lhs } => {
+                let lhs = self.parse_keys(&lhs)?;
+                let mut found = false;
+                for mode in modes {
+                    found |= self.keymap.unmap(*mode, &lhs);
+                }
+                if !found {
+                    anyhow::bail!("No such mapping");
+                }
+            }
+            ExCommand::Write(file) => {
+                if file.is_none() && !self.view.has_file() {
+                    anyhow::bail!("No file name");
+                }
+                self.save(file.as_deref());
+            }
+            ExCommand::SaveAs(file) => self.save(Some(&file)),
+            ExCommand::Edit(file) => {
+                if self.view.get_status().modified {
+                    anyhow::bail!("No write since last change");
+                }
+                self.view.edit(PathBuf::from(file))?;
+            }
+            // With a single buffer there is nothing to switch to, `:b` reports it.
+            ExCommand::Buffer(name) => {
+                let names = self.buffer_names();
+                let buffer = match name {
+                    Some(name) => names
+                        .into_iter()
+                        .find(|buffer| buffer.starts_with(&name))
+                        .ok_or_else(|| anyhow::anyhow!("No matching buffer for {name}"))?,
+                    None => names
+                        .into_iter()
+                        .next()
+                        .unwrap_or_else(|| String::from("[No Name]")),
+                };
+                let lines = self.view.get_status().total_lines;
+                let plural = if lines == 1 { "" } else { "s" };
+                self.message
+                    .update_message(format!("\"{buffer}\" {lines} line{plural}"));
+            }
+        }
+        Ok(())
+    }
+
+    fn set_options(&mut self, args: &[String], local: bool) -> anyhow::Result<()> {
+        let shown = if args.is_empty() {
+            OPTIONS
+                .iter()
+                .filter(|info| *self.view.option(info.id) != info.default)
+                .map(|info| info.show(self.view.option(info.id)))
+                .collect()
         } else {
-            self.message
-                .update_message(String::from("Error while saving file."));
+            let mut shown = Vec::new();
+            for arg in args {
+                shown.extend(self.set_option(arg, local)?);
+            }
+            shown
+        };
+        if !shown.is_empty() {
+            self.message.update_message(shown.join("  "));
+        }
+        Ok(())
+    }
+
+    /// return: the option to show for `opt?`, or `opt` of an option which is not a boolean
+    fn set_option(&mut self, arg: &str, local: bool) -> anyhow::Result<Option<String>> {
+        let (info, action) = parse_set_arg(arg)?;
+        let value = match (action, self.view.option(info.id)) {
+            (SetAction::Show, value)
+            | (SetAction::Set, value @ (Value::Int(_) | Value::String(_))) => {
+                return Ok(Some(info.show(value)));
+            }
+            (SetAction::Set, _) => Value::Bool(true),
+            (SetAction::Unset, _) => Value::Bool(false),
+            (SetAction::Toggle, Value::Bool(value)) => Value::Bool(!value),
+            (SetAction::Toggle, _) => anyhow::bail!("Invalid argument: {arg}"),
+            (SetAction::Reset, _) => info.default.clone(),
+            (SetAction::Assign(value), _) => info.parse(value)?,
+        };
+        self.set_value(info.id, value, local)?;
+        Ok(None)
+    }
+
+    /// A theme which can't be loaded or an invalid status line leaves the option
+    /// as it was.
+    fn set_value(&mut self, id: OptionId, value: Value, local: bool) -> anyhow::Result<()> {
+        let value = id.info().check(value)?;
+        match id {
+            OptionId::Theme => self.backend.set_theme(load_theme(value.as_str())?),
+            OptionId::Statusline => self.status.set_format(value.as_str().parse()?),
+            _ => {}
+        }
+        self.view.set_option(id, value, local);
+        if id == OptionId::Mouse {
+            self.backend
+                .set_mouse_capture(self.view.option(id).as_bool())?;
         }
+        Ok(())
     }
 
     fn quit(&mut self) {
@@ -426,15 +1353,31 @@ impl Editor {
             PromptType::Search => {
                 self.view.enter_search();
                 self.command
-                    .set_prompt("Search (Esc to cancel): ".to_string());
+                    .set_prompt("Search (\\v = regex, Esc to cancel): ".to_string());
             }
             PromptType::Save => self.command.set_prompt("Save as: ".to_string()),
             PromptType::None => self.message.set_render(true),
             PromptType::Command => {
                 self.command.set_prompt("Command: ".to_string());
             }
+            PromptType::Confirm => {
+                let replacement = self
+                    .substitution
+                    .as_ref()
+                    .map(Substitution::replacement)
+                    .unwrap_or_default();
+                self.command
+                    .set_prompt(format!("replace with {replacement} (y/n/a/q)? "));
+            }
         }
 
+        if prompt == PromptType::None {
+            self.control.leave_prompt();
+        } else {
+            self.control.enter_prompt();
+        }
+        self.histories.search.reset();
+        self.histories.command.reset();
         self.command.clear();
         self.prompt = prompt;
     }
@@ -449,24 +1392,302 @@ impl DocumentStatus {
         if self.modified { "(modified)" } else { "" }
     }
 
-    pub fn line_count(&self) -> String {
-        format!("{} lines", self.total_lines)
-    }
-
-    pub fn position_indicator(&self) -> String {
-        format!(
-            "{}/{}",
-            self.current_line.saturating_add(1),
-            self.total_lines
-        )
+    /// `[3/17]`, counts above `MAX_SEARCH_COUNT` are shown as `>99`.
+    pub fn search_indicator(&self) -> String {
+        let Some((current, total)) = self.search_count else {
+            return String::new();
+        };
+        let count = |count: usize| {
+            if count > MAX_SEARCH_COUNT {
+                format!(">{MAX_SEARCH_COUNT}")
+            } else {
+                count.to_string()
+            }
+        };
+        format!("[{}/{}]", count(current), count(total))
     }
 }
 
-impl Drop for Editor {
+impl<B: Backend> Drop for Editor<B> {
     fn drop(&mut self) {
-        let _ = terminal::terminate();
+        let _ = self.backend.terminate();
+        if let Some(path) = self.history_file.as_deref() {
+            let _ = self.histories.save(path);
+        }
         if self.should_quit {
             println!("Goodbye");
         }
     }
 }
+
+#[cfg(test)]
+fn type_keys(editor: &mut Editor<terminal::TestBackend>, keys: &str) {
+    for c in keys.chars() {
+        let (code, modifiers) = match c {
+            '\x1b' => (KeyCode::Esc, KeyModifiers::NONE),
+            '\n' => (KeyCode::Enter, KeyModifiers::NONE),
+            '\t' => (KeyCode::Tab, KeyModifiers::NONE),
+            c if c.is_uppercase() => (KeyCode::Char(c), KeyModifiers::SHIFT),
+            c => (KeyCode::Char(c), KeyModifiers::NONE),
+        };
+        editor.evalute_event(Event::Key(KeyEvent::new(code, modifiers)));
+    }
+    editor.refresh_status();
+    editor.refresh_screen();
+}
+
+#[test]
+fn render_into_test_backend() {
+    let mut editor = Editor::with_backend(terminal::TestBackend::new(40, 6)).unwrap();
+    editor.refresh_screen();
+    assert!(editor.backend.row_text(1).contains("editor -- version"));
+
+    type_keys(&mut editor, "ione two\nthree\x1b");
+    let backend = &editor.backend;
+    assert_eq!(backend.row_text(0).trim_end(), "one two");
+    assert_eq!(backend.row_text(1).trim_end(), "three");
+    assert_eq!(backend.row_text(2).trim_end(), "~");
+    assert_eq!(backend.caret(), Some((5, 1)));
+    assert!(backend.style_at(0, 4).unwrap().reverse);
+
+    type_keys(&mut editor, ":set hls\n:set mouse\n/two\n");
+    let backend = &editor.backend;
+    assert!(backend.mouse_capture());
+    assert_eq!(backend.title(), format!("[No Name] - {NAME}"));
+    assert_eq!(backend.caret(), Some((4, 0)));
+    assert_eq!(backend.style_at(3, 0).unwrap().background, None);
+    assert!(backend.style_at(4, 0).unwrap().background.is_some());
+}
+
+#[test]
+fn lay_out_with_options() {
+    let mut editor = Editor::with_backend(terminal::TestBackend::new(12, 6)).unwrap();
+    type_keys(&mut editor, "i\tab\x1b:set ts=4 nu\n");
+    assert_eq!(editor.backend.row_text(0), "  1     ab  ");
+    assert_eq!(editor.backend.caret(), Some((10, 0)));
+
+    type_keys(&mut editor, ":set wrap\nAabcdefghij\x1b");
+    let backend = &editor.backend;
+    assert_eq!(backend.row_text(0), "  1     abab");
+    assert_eq!(backend.row_text(1), "    cdefghij");
+    assert_eq!(backend.row_text(2).trim_end(), "");
+    assert_eq!(backend.row_text(3).trim_end(), "~");
+    assert_eq!(backend.caret(), Some((4, 2)));
+
+    type_keys(&mut editor, ":set et nonu nowrap\no\tx\x1b");
+    assert_eq!(editor.view.get_status().total_lines, 2);
+    assert_eq!(editor.backend.row_text(1).trim_end(), "    x");
+
+    type_keys(&mut editor, ":set ts=0\n");
+    assert_eq!(editor.view.option(OptionId::Tabstop), &Value::Int(4));
+    type_keys(&mut editor, ":set theme=mono\n/ab\n");
+    assert!(editor.backend.style_at(6, 0).unwrap().reverse);
+}
+
+#[test]
+fn switch_colorscheme() {
+    let mut editor = Editor::with_backend(terminal::TestBackend::new(60, 6)).unwrap();
+    let message = |editor: &Editor<terminal::TestBackend>| editor.backend.row_text(5);
+    type_keys(&mut editor, "inote\x1b/no\n");
+    assert!(editor.backend.style_at(0, 0).unwrap().background.is_some());
+
+    type_keys(&mut editor, ":colo solarized\n");
+    assert!(message(&editor).contains("Invalid theme: solarized"));
+    type_keys(&mut editor, ":colo\n");
+    assert_eq!(message(&editor).trim_end(), "default");
+
+    type_keys(&mut editor, ":colorscheme mono\n:set theme?\n");
+    assert_eq!(message(&editor).trim_end(), "theme=mono");
+    let style = editor.backend.style_at(0, 0).unwrap();
+    assert!(style.reverse && style.background.is_none());
+    assert!(editor.backend.style_at(0, 4).unwrap().reverse);
+}
+
+#[test]
+fn apply_config_keymap() {
+    let mut editor = Editor::with_backend(terminal::TestBackend::new(20, 6)).unwrap();
+    let config =
+        Config::parse("[keymap.normal]\nQ = \"dw\"\n[keymap.insert]\n\"<Tab>\" = \"<lt>-\"")
+            .unwrap();
+    editor.apply_config(config).unwrap();
+    type_keys(&mut editor, "ione two\x1b0Q");
+    assert_eq!(editor.backend.row_text(0).trim_end(), "two");
+
+    type_keys(&mut editor, "A\t\x1b");
+    assert_eq!(editor.backend.row_text(0).trim_end(), "two<-");
+}
+
+#[test]
+fn reject_config_options_out_of_range() {
+    let mut editor = Editor::with_backend(terminal::TestBackend::new(20, 6)).unwrap();
+    let config = Config::parse("scrolloff = 100000").unwrap();
+    assert_eq!(
+        editor.apply_config(config).unwrap_err().to_string(),
+        "scrolloff must be between 0 and 999, got 100000"
+    );
+    assert_eq!(editor.view.option(OptionId::Scrolloff).as_int(), 0);
+}
+
+#[test]
+fn map_key_sequences() {
+    let mut editor = Editor::with_backend(terminal::TestBackend::new(40, 6)).unwrap();
+    let config = Config::parse(
+        "mapleader = \",\"\n[keymap.normal]\n\"<Leader>n\" = { command = \"set nu\" }",
+    )
+    .unwrap();
+    editor.apply_config(config).unwrap();
+    let line = |editor: &Editor<terminal::TestBackend>| editor.backend.row_text(0);
+
+    type_keys(&mut editor, ":inoremap jk <Esc>\nione jam twjk");
+    assert_eq!(line(&editor).trim_end(), "one jam tw");
+    type_keys(&mut editor, ":nnoremap <Leader>d dw\n0,d");
+    assert_eq!(line(&editor).trim_end(), "jam tw");
+
+    type_keys(&mut editor, ":nnoremap , A!<Esc>\n,");
+    assert_eq!(line(&editor).trim_end(), "jam tw");
+    editor.handle_timer(Timer::MappingTimeout);
+    editor.refresh_screen();
+    assert_eq!(line(&editor).trim_end(), "jam tw!");
+
+    type_keys(&mut editor, ":nmap Q 0,d\nQ");
+    assert_eq!(line(&editor).trim_end(), "tw!");
+    type_keys(&mut editor, ",n");
+    assert_eq!(line(&editor).trim_end(), "  1 tw!");
+
+    type_keys(&mut editor, ":nmap Q\n");
+    assert_eq!(editor.backend.row_text(5).trim_end(), "n Q 0,d");
+    type_keys(&mut editor, ":unmap Q\n:nmap Q\n");
+    assert!(editor.backend.row_text(5).contains("No mapping found"));
+
+    type_keys(&mut editor, ":nmap a b\n:nmap b a\na");
+    assert!(editor.backend.row_text(5).contains("Recursive mapping"));
+}
+
+#[test]
+fn report_the_buffer() {
+    let mut editor = Editor::with_backend(terminal::TestBackend::new(60, 6)).unwrap();
+    let message = |editor: &Editor<terminal::TestBackend>| editor.backend.row_text(5);
+
+    type_keys(&mut editor, ":b\n");
+    assert_eq!(message(&editor).trim_end(), "\"[No Name]\" 0 lines");
+    type_keys(&mut editor, ":b main\n");
+    assert!(message(&editor).contains("No matching buffer for main"));
+
+    let path = std::env::temp_dir().join(format!("oxide-buffer-{}.txt", std::process::id()));
+    std::fs::write(&path, "x\ny\n").unwrap();
+    type_keys(&mut editor, &format!(":e {}\n", path.display()));
+    type_keys(
+        &mut editor,
+        &format!(":b {}\n", std::env::temp_dir().display()),
+    );
+    assert_eq!(
+        message(&editor).trim_end(),
+        format!("\"{}\" 2 lines", path.display())
+    );
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn set_options_by_scope() {
+    let dir = std::env::temp_dir().join(format!("oxide-options-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.txt"), "a\tb\n").unwrap();
+    std::fs::write(dir.join("b.txt"), "x\n").unwrap();
+    let mut editor = Editor::with_backend(terminal::TestBackend::new(60, 6)).unwrap();
+    let message = |editor: &Editor<terminal::TestBackend>| editor.backend.row_text(5);
+
+    type_keys(&mut editor, &format!(":e {}/a.txt\n", dir.display()));
+    type_keys(&mut editor, ":setlocal ts=4 nu\n:set ts? wrap?\n");
+    assert_eq!(message(&editor).trim_end(), "tabstop=4  nowrap");
+    assert_eq!(editor.backend.row_text(0).trim_end(), "  1 a   b");
+
+    type_keys(
+        &mut editor,
+        &format!(":e {}/b.txt\n:set ts\n", dir.display()),
+    );
+    assert_eq!(message(&editor).trim_end(), "tabstop=8");
+    type_keys(&mut editor, ":set ts=2 nu!\n:set\n");
+    assert_eq!(message(&editor).trim_end(), "tabstop=2");
+    assert_eq!(editor.backend.row_text(0).trim_end(), "x");
+    type_keys(&mut editor, ":set ts& number&\n");
+    assert_eq!(editor.view.option(OptionId::Tabstop), &Value::Int(8));
+
+    type_keys(&mut editor, ":set ro\n:w\n");
+    assert!(message(&editor).contains("'readonly' option is set"));
+    type_keys(&mut editor, ":set ts=x\n");
+    assert!(message(&editor).contains("Number required after =: tabstop=x"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn customise_status_line() {
+    let mut editor = Editor::with_backend(terminal::TestBackend::new(50, 6)).unwrap();
+    let status = |editor: &mut Editor<terminal::TestBackend>| {
+        editor.refresh_status();
+        editor.refresh_screen();
+        editor.backend.row_text(4)
+    };
+    type_keys(&mut editor, "ione\ntwo\x1b");
+    assert!(status(&mut editor).starts_with("[No Name] | Text - 2 lines (modified)"));
+
+    type_keys(&mut editor, ":set stl=%f%=%{mode}|%l:%c\n");
+    assert_eq!(
+        status(&mut editor),
+        format!("[No Name]{}NORMAL|2:4", " ".repeat(31))
+    );
+    type_keys(&mut editor, ":set mouse stl=%{mode}|%{selection}\n");
+    assert_eq!(status(&mut editor).trim_end(), "NORMAL|");
+    editor.handle_mouse(MouseAction::Click, 0, 0);
+    editor.handle_mouse(MouseAction::Drag, 1, 0);
+    assert_eq!(status(&mut editor).trim_end(), "VISUAL|2 chars");
+    editor.handle_mouse(MouseAction::Drag, 1, 1);
+    assert_eq!(status(&mut editor).trim_end(), "VISUAL|2 lines");
+
+    type_keys(&mut editor, "\x1b:set stl=%l%q\n");
+    assert!(
+        editor
+            .backend
+            .row_text(5)
+            .contains("Unknown statusline item: %q")
+    );
+    assert_eq!(status(&mut editor).trim_end(), "NORMAL|");
+}
+
+#[test]
+fn show_mode_and_caret_shape() {
+    let mut editor = Editor::with_backend(terminal::TestBackend::new(40, 6)).unwrap();
+    let message = |editor: &Editor<terminal::TestBackend>| editor.backend.row_text(5);
+    let backspace = |editor: &mut Editor<terminal::TestBackend>, times| {
+        for _ in 0..times {
+            editor.evalute_event(Event::Key(KeyEvent::new(
+                KeyCode::Backspace,
+                KeyModifiers::NONE,
+            )));
+        }
+        type_keys(editor, "");
+    };
+    type_keys(&mut editor, ":colo\n");
+    assert_eq!(message(&editor).trim_end(), "default");
+    type_keys(&mut editor, "ione");
+    assert_eq!(message(&editor).trim_end(), "-- INSERT --");
+    assert_eq!(editor.backend.caret_shape(), CaretShape::Bar);
+    type_keys(&mut editor, "\x1b");
+    assert_eq!(message(&editor).trim_end(), "");
+    assert_eq!(editor.backend.caret_shape(), CaretShape::Block);
+
+    type_keys(&mut editor, "0Rxy");
+    assert_eq!(message(&editor).trim_end(), "-- REPLACE --");
+    assert_eq!(editor.backend.caret_shape(), CaretShape::Underline);
+    assert_eq!(editor.backend.row_text(0).trim_end(), "xye");
+    backspace(&mut editor, 1);
+    type_keys(&mut editor, "zzz");
+    assert_eq!(editor.backend.row_text(0).trim_end(), "xzzz");
+    backspace(&mut editor, 3);
+    assert_eq!(editor.backend.row_text(0).trim_end(), "xne");
+
+    type_keys(&mut editor, "\x1b:");
+    assert_eq!(editor.backend.caret_shape(), CaretShape::Bar);
+    type_keys(&mut editor, "set noshowmode\ni");
+    assert_eq!(message(&editor).trim_end(), "");
+    assert_eq!(editor.backend.caret_shape(), CaretShape::Bar);
+}
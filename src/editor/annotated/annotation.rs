@@ -24,4 +24,7 @@ pub enum AnnotationType {
     Char,
     Lifetime,
     String,
+    MatchingBracket,
+    WildMenuSelection,
+    Selection,
 }
@@ -1,12 +1,24 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
 use crate::{
-    editor::{event::Command, ui::UiComponent, view::line::Line},
-    terminal,
+    editor::{
+        event::{Command, Direction},
+        ui::UiComponent,
+        view::line::Line,
+    },
+    terminal::Backend,
 };
 
+/// Tabs pasted into the command line are drawn this wide.
+const TAB_WIDTH: usize = 8;
+
 #[derive(Default)]
 pub struct CommandBar {
     prompt: String,
     value: Line,
+    /// Grapheme index of the caret in `value`.
+    caret: usize,
     width: u16,
     render: bool,
 }
@@ -14,19 +26,120 @@ pub struct CommandBar {
 impl CommandBar {
     pub fn handle_edit_command(&mut self, command: Command) {
         match command {
-            Command::Insert(c) => self.value.append_char(c),
-            Command::Backspace | Command::Delete => self.value.delete_last(),
+            Command::Insert(c) => self.insert_str(&c.to_string()),
+            Command::Backspace if self.caret > 0 => {
+                self.caret -= 1;
+                self.value.delete(self.caret);
+            }
+            Command::Delete => self.value.delete(self.caret),
+            Command::Move(Direction::Left) => self.caret = self.caret.saturating_sub(1),
+            Command::Move(Direction::Right) => {
+                self.caret =
+                    std::cmp::min(self.caret.saturating_add(1), self.value.grapheme_count())
+            }
+            Command::StartOfLine => self.caret = 0,
+            Command::EndOfLine => self.caret = self.value.grapheme_count(),
+            Command::DeleteWordBackward => self.delete_before_caret(self.previous_word_start()),
+            Command::DeleteToStartOfLine => self.delete_before_caret(0),
             _ => {}
         }
         self.set_render(true);
     }
 
+    /// Line breaks are joined with spaces.
+    pub fn insert_str(&mut self, text: &str) {
+        let text = text.trim_end_matches('\n').replace('\n', " ");
+        let old_len = self.value.grapheme_count();
+        let byte_idx = self.caret_byte_idx();
+
+        self.value.replace(byte_idx..byte_idx, &text);
+        self.caret = self
+            .caret
+            .saturating_add(self.value.grapheme_count().saturating_sub(old_len));
+        self.set_render(true);
+    }
+
+    pub fn text_before_caret(&self) -> &str {
+        &self.value[..self.caret_byte_idx()]
+    }
+
+    /// Replace the text from byte `start` up to the caret with `text`.
+    pub fn complete(&mut self, start: usize, text: &str) {
+        let end = self.caret_byte_idx();
+        if start > end {
+            return;
+        }
+        self.delete_before_caret(self.value[..start].graphemes(true).count());
+        self.insert_str(text);
+    }
+
+    fn caret_byte_idx(&self) -> usize {
+        self.value
+            .grapheme_index_to_byte_idx(self.caret)
+            .unwrap_or(self.value.len())
+    }
+
+    fn delete_before_caret(&mut self, from: usize) {
+        let start = self
+            .value
+            .grapheme_index_to_byte_idx(from)
+            .unwrap_or(self.value.len());
+        let end = self.caret_byte_idx();
+
+        self.value.replace(start..end, "");
+        self.caret = from;
+    }
+
+    /// Start of the word before the caret, skipping whitespace. A word is a run of
+    /// alphanumeric characters and `_`, or a run of other non-blank characters.
+    fn previous_word_start(&self) -> usize {
+        let graphemes = self.value.graphemes(true).collect::<Vec<_>>();
+        let is_blank = |grapheme: &str| grapheme.trim().is_empty();
+        let is_keyword = |grapheme: &str| {
+            grapheme
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_')
+        };
+
+        let mut idx = std::cmp::min(self.caret, graphemes.len());
+        while idx > 0 && is_blank(graphemes[idx - 1]) {
+            idx -= 1;
+        }
+        if let Some(last) = idx.checked_sub(1).map(|last| graphemes[last]) {
+            let keyword = is_keyword(last);
+            while idx > 0
+                && !is_blank(graphemes[idx - 1])
+                && is_keyword(graphemes[idx - 1]) == keyword
+            {
+                idx -= 1;
+            }
+        }
+        idx
+    }
+
+    fn scroll_offset(&self) -> usize {
+        let area = (self.width as usize).saturating_sub(self.prompt.width());
+        let caret_col = self.value.width_until(self.caret, TAB_WIDTH);
+
+        caret_col.saturating_add(1).saturating_sub(area)
+    }
+
+    pub fn click(&mut self, col: u16) {
+        let value_col = (col as usize)
+            .saturating_sub(self.prompt.width())
+            .saturating_add(self.scroll_offset());
+        self.caret = self.value.grapheme_index_at_width(value_col, TAB_WIDTH);
+        self.set_render(true);
+    }
+
     pub fn caret_pos_col(&self) -> usize {
-        let max_width = self
+        let col = self
             .prompt
-            .len()
-            .saturating_add(self.value.grapheme_count());
-        std::cmp::min(self.width as usize, max_width)
+            .width()
+            .saturating_add(self.value.width_until(self.caret, TAB_WIDTH))
+            .saturating_sub(self.scroll_offset());
+        std::cmp::min(self.width as usize, col)
     }
 
     pub fn set_prompt(&mut self, prompt: String) {
@@ -34,12 +147,19 @@ impl CommandBar {
         self.set_render(true);
     }
 
+    pub fn set_value(&mut self, value: String) {
+        self.value = Line::from(value);
+        self.caret = self.value.grapheme_count();
+        self.set_render(true);
+    }
+
     pub fn get_value(&self) -> String {
         self.value.to_string()
     }
 
     pub fn clear(&mut self) {
         self.value.clear();
+        self.caret = 0;
         self.set_render(true);
     }
 }
@@ -57,19 +177,57 @@ impl UiComponent for CommandBar {
         self.width = width
     }
 
-    fn draw(&mut self, y: u16) -> anyhow::Result<()> {
-        // TODO: use `width` instanceof `len`?
-        let area = (self.width as usize).saturating_sub(self.prompt.len());
+    fn draw(&mut self, backend: &mut dyn Backend, y: u16) -> anyhow::Result<()> {
+        let area = (self.width as usize).saturating_sub(self.prompt.width());
 
-        let value_end = self.value.width();
-
-        let value_start = value_end.saturating_sub(area);
+        let value_start = self.scroll_offset();
+        let value_end = value_start.saturating_add(area);
         let message = format!(
             "{}{}",
             self.prompt,
-            self.value.get_visable_graphemes(value_start..value_end)
+            self.value
+                .get_visable_graphemes(value_start..value_end, TAB_WIDTH)
         );
 
-        terminal::print_at(0, y, true, message)
+        backend.print_at(0, y, true, &message)
     }
 }
+
+#[test]
+fn edit_in_the_middle() {
+    let mut bar = CommandBar {
+        width: 80,
+        ..Default::default()
+    };
+    let type_str = |bar: &mut CommandBar, text: &str| {
+        text.chars()
+            .for_each(|c| bar.handle_edit_command(Command::Insert(c)))
+    };
+
+    type_str(&mut bar, "s/foo/bar/");
+    bar.handle_edit_command(Command::Move(Direction::Left));
+    type_str(&mut bar, "baz");
+    assert_eq!(bar.get_value(), "s/foo/barbaz/");
+
+    bar.handle_edit_command(Command::DeleteWordBackward);
+    assert_eq!(bar.get_value(), "s/foo//");
+    bar.handle_edit_command(Command::DeleteWordBackward);
+    assert_eq!(bar.get_value(), "s/foo/");
+
+    bar.handle_edit_command(Command::StartOfLine);
+    bar.handle_edit_command(Command::Delete);
+    type_str(&mut bar, "%s");
+    assert_eq!(bar.get_value(), "%s/foo/");
+
+    bar.handle_edit_command(Command::Move(Direction::Right));
+    bar.insert_str("(重要)\n");
+    assert_eq!(bar.get_value(), "%s/(重要)foo/");
+
+    bar.set_prompt(String::from("Command: "));
+    assert_eq!(bar.caret_pos_col(), 9 + 9);
+
+    bar.handle_edit_command(Command::DeleteToStartOfLine);
+    assert_eq!(bar.get_value(), "foo/");
+    bar.handle_edit_command(Command::Backspace);
+    assert_eq!(bar.get_value(), "foo/");
+}
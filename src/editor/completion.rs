@@ -0,0 +1,195 @@
+use std::path::Path;
+
+use crate::editor::{
+    config, ex,
+    options::{Kind, OPTIONS, OptionId, OptionInfo},
+};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Completion {
+    /// Byte index where the completed word starts, candidates replace the text after it.
+    pub start: usize,
+    pub candidates: Vec<String>,
+}
+
+impl Completion {
+    /// `buffers` are the names offered to `:buffer`.
+    pub fn command(line: &str, buffers: &[String]) -> Self {
+        let (name, args) = ex::split_command(line);
+        let name_start = line.len().saturating_sub(name.len() + args.len());
+        if args.is_empty() {
+            return Self {
+                start: name_start,
+                candidates: ex::command_names(name),
+            };
+        }
+
+        let word_start = args
+            .rfind(char::is_whitespace)
+            .map_or(0, |idx| idx.saturating_add(1));
+        let word = &args[word_start..];
+        let start = line.len().saturating_sub(args.len()) + word_start;
+
+        if let Some("set" | "setlocal") = ex::command_name(name)
+            && let Some(idx) = word.find(['=', ':'])
+        {
+            return Self {
+                start: start + idx + 1,
+                candidates: option_values(&word[..idx], &word[idx + 1..]),
+            };
+        }
+        let candidates = match ex::command_name(name) {
+            Some("edit" | "write" | "saveas") => paths(word),
+            Some("buffer") => buffers
+                .iter()
+                .filter(|buffer| buffer.starts_with(word))
+                .cloned()
+                .collect(),
+            Some("colorscheme") => config::theme_names()
+                .into_iter()
+                .filter(|name| name.starts_with(word))
+                .collect(),
+            Some("set" | "setlocal") => OPTIONS
+                .iter()
+                .flat_map(|info| match info.kind {
+                    Kind::Bool => vec![info.name.to_string(), format!("no{}", info.name)],
+                    _ => vec![format!("{}=", info.name)],
+                })
+                .filter(|option| option.starts_with(word))
+                .collect(),
+            _ => Vec::new(),
+        };
+        Self { start, candidates }
+    }
+
+    pub fn path(partial: &str) -> Self {
+        Self {
+            start: 0,
+            candidates: paths(partial),
+        }
+    }
+}
+
+/// The values of option `name` starting with `prefix`, for options with a known set.
+fn option_values(name: &str, prefix: &str) -> Vec<String> {
+    let values = match OptionInfo::find(name) {
+        Some(info) if info.id == OptionId::Theme => config::theme_names(),
+        Some(OptionInfo {
+            kind: Kind::Enum(names),
+            ..
+        }) => names.iter().map(|name| name.to_string()).collect(),
+        _ => Vec::new(),
+    };
+    values
+        .into_iter()
+        .filter(|value| value.starts_with(prefix))
+        .collect()
+}
+
+/// Entries of the directory in `partial` starting with its file name part, sorted.
+/// Directories end with `/`, hidden entries are only offered for a leading `.`.
+fn paths(partial: &str) -> Vec<String> {
+    let (dir, prefix) = match partial.rfind('/') {
+        Some(idx) => partial.split_at(idx.saturating_add(1)),
+        None => ("", partial),
+    };
+    let Ok(entries) = std::fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+
+    let mut paths = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let separator = if Path::new(dir).join(&name).is_dir() {
+                "/"
+            } else {
+                ""
+            };
+            Some(format!("{dir}{name}{separator}"))
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn complete_command_line() {
+    let buffers = vec![String::from("main.rs")];
+
+    let completion = Completion::command("s", &buffers);
+    assert_eq!(completion.start, 0);
+    assert_eq!(
+        completion.candidates,
+        ["saveas", "set", "setlocal", "substitute"]
+    );
+
+    let completion = Completion::command("%su", &buffers);
+    assert_eq!(completion.start, 1);
+    assert_eq!(completion.candidates, ["substitute"]);
+
+    let completion = Completion::command("set ic nos", &buffers);
+    assert_eq!(completion.start, 7);
+    assert_eq!(completion.candidates, ["noshowmode", "nosmartcase"]);
+
+    let completion = Completion::command("setl t", &buffers);
+    assert_eq!(completion.candidates, ["tabstop=", "theme="]);
+
+    let completion = Completion::command("colo mo", &buffers);
+    assert_eq!(completion.candidates, ["mono"]);
+
+    let completion = Completion::command("set ff=", &buffers);
+    assert_eq!(completion.start, 7);
+    assert_eq!(completion.candidates, ["unix", "dos"]);
+    let completion = Completion::command("setl nu fileformat:d", &buffers);
+    assert_eq!(completion.start, 19);
+    assert_eq!(completion.candidates, ["dos"]);
+    let completion = Completion::command("set theme=m", &buffers);
+    assert_eq!(completion.start, 10);
+    assert_eq!(completion.candidates, ["mono"]);
+    assert!(
+        Completion::command("set ts=", &buffers)
+            .candidates
+            .is_empty()
+    );
+
+    let completion = Completion::command("b m", &buffers);
+    assert_eq!(completion.start, 2);
+    assert_eq!(completion.candidates, ["main.rs"]);
+
+    assert!(Completion::command("q ", &buffers).candidates.is_empty());
+}
+
+#[test]
+fn complete_paths() {
+    let dir = std::env::temp_dir().join(format!("oxide-completion-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    for file in ["notes.txt", "new.rs", ".hidden"] {
+        std::fs::write(dir.join(file), "").unwrap();
+    }
+    let dir = format!("{}/", dir.display());
+
+    let completion = Completion::path(&format!("{dir}n"));
+    assert_eq!(
+        completion.candidates,
+        [format!("{dir}new.rs"), format!("{dir}notes.txt")]
+    );
+    assert_eq!(
+        Completion::path(&format!("{dir}s")).candidates,
+        [format!("{dir}src/")]
+    );
+    assert_eq!(
+        Completion::path(&format!("{dir}.")).candidates,
+        [format!("{dir}.hidden")]
+    );
+
+    let line = format!("w {dir}no");
+    let completion = Completion::command(&line, &[]);
+    assert_eq!(completion.start, 2);
+    assert_eq!(completion.candidates, [format!("{dir}notes.txt")]);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
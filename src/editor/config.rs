@@ -0,0 +1,479 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use crossterm::style::Color;
+use serde::{Deserialize, Deserializer, de::Error};
+
+use crate::{
+    editor::{
+        keymap,
+        options::{OptionId, Value},
+        status::StatusFormat,
+    },
+    terminal::{Element, Style, Theme, parse_color},
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    #[serde(deserialize_with = "deserialize_tabstop")]
+    pub tabstop: usize,
+    pub expandtab: bool,
+    pub number: bool,
+    pub wrap: bool,
+    pub scrolloff: usize,
+    pub ignorecase: bool,
+    pub smartcase: bool,
+    pub hlsearch: bool,
+    pub mouse: bool,
+    pub showmode: bool,
+    #[serde(deserialize_with = "deserialize_theme")]
+    pub theme: String,
+    #[serde(deserialize_with = "deserialize_leader")]
+    pub mapleader: String,
+    #[serde(deserialize_with = "deserialize_statusline")]
+    pub statusline: String,
+    pub keymap: KeymapConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let default = |id: OptionId| &id.info().default;
+        Self {
+            tabstop: default(OptionId::Tabstop).as_int(),
+            expandtab: default(OptionId::Expandtab).as_bool(),
+            number: default(OptionId::Number).as_bool(),
+            wrap: default(OptionId::Wrap).as_bool(),
+            scrolloff: default(OptionId::Scrolloff).as_int(),
+            ignorecase: default(OptionId::Ignorecase).as_bool(),
+            smartcase: default(OptionId::Smartcase).as_bool(),
+            hlsearch: default(OptionId::Hlsearch).as_bool(),
+            mouse: default(OptionId::Mouse).as_bool(),
+            showmode: default(OptionId::Showmode).as_bool(),
+            theme: default(OptionId::Theme).to_string(),
+            mapleader: default_leader(),
+            statusline: default(OptionId::Statusline).to_string(),
+            keymap: KeymapConfig::default(),
+        }
+    }
+}
+
+/// `[keymap.normal]`, `[keymap.visual]` and `[keymap.insert]`: keys mapped to the
+/// keys they type, `"keys"` or `{ keys = "keys", remap = true }`, or to an ex
+/// command, `{ command = "write" }`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeymapConfig {
+    #[serde(deserialize_with = "deserialize_mappings")]
+    pub normal: Vec<MappingConfig>,
+    #[serde(deserialize_with = "deserialize_mappings")]
+    pub visual: Vec<MappingConfig>,
+    #[serde(deserialize_with = "deserialize_mappings")]
+    pub insert: Vec<MappingConfig>,
+}
+
+/// A mapping as written in the config. The keys are parsed once `mapleader` is
+/// known, which may be set after the mappings.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MappingConfig {
+    pub lhs: String,
+    pub rhs: RhsConfig,
+    pub remap: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RhsConfig {
+    Keys(String),
+    Command(String),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MappingValue {
+    Keys(String),
+    Table(MappingTable),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MappingTable {
+    keys: Option<String>,
+    command: Option<String>,
+    #[serde(default)]
+    remap: bool,
+}
+
+/// `themes/<name>.toml` in the config directory. Elements left out keep the style
+/// of the default theme.
+#[derive(Deserialize)]
+#[serde(transparent)]
+struct ThemeFile {
+    #[serde(deserialize_with = "deserialize_styles")]
+    styles: Vec<(Element, Style)>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StyleConfig {
+    #[serde(default, deserialize_with = "deserialize_color")]
+    fg: Option<Color>,
+    #[serde(default, deserialize_with = "deserialize_color")]
+    bg: Option<Color>,
+    #[serde(default)]
+    bold: bool,
+    #[serde(default)]
+    italic: bool,
+    #[serde(default)]
+    underline: bool,
+    #[serde(default)]
+    reverse: bool,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/oxide`, falling back to `~/.config/oxide`.
+    pub fn dir() -> Option<PathBuf> {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .map(|dir| dir.join(env!("CARGO_PKG_NAME")))
+    }
+
+    pub fn file() -> Option<PathBuf> {
+        Self::dir().map(|dir| dir.join("config.toml"))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("read config file {}", path.display()))?;
+        Self::parse(&contents).map_err(|message| anyhow::anyhow!("{}:{message}", path.display()))
+    }
+
+    /// return: the config, or the error prefixed with the line and column it was found at
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        toml::from_str(contents).map_err(|error| describe_error(contents, &error))
+    }
+
+    pub fn options(&self) -> Vec<(OptionId, Value)> {
+        vec![
+            (OptionId::Tabstop, Value::Int(self.tabstop)),
+            (OptionId::Expandtab, Value::Bool(self.expandtab)),
+            (OptionId::Number, Value::Bool(self.number)),
+            (OptionId::Wrap, Value::Bool(self.wrap)),
+            (OptionId::Scrolloff, Value::Int(self.scrolloff)),
+            (OptionId::Ignorecase, Value::Bool(self.ignorecase)),
+            (OptionId::Smartcase, Value::Bool(self.smartcase)),
+            (OptionId::Hlsearch, Value::Bool(self.hlsearch)),
+            (OptionId::Mouse, Value::Bool(self.mouse)),
+            (OptionId::Showmode, Value::Bool(self.showmode)),
+            (
+                OptionId::Theme,
+                Value::String(Cow::Owned(self.theme.clone())),
+            ),
+            (
+                OptionId::Mapleader,
+                Value::String(Cow::Owned(self.mapleader.clone())),
+            ),
+            (
+                OptionId::Statusline,
+                Value::String(Cow::Owned(self.statusline.clone())),
+            ),
+        ]
+    }
+}
+
+fn default_leader() -> String {
+    OptionId::Mapleader.info().default.to_string()
+}
+
+/// A built-in theme, or the theme file `name.toml` in the `themes` directory of
+/// the config.
+pub fn load_theme(name: &str) -> anyhow::Result<Theme> {
+    if let Some(theme) = Theme::builtin(name) {
+        return Ok(theme);
+    }
+    let Some(path) = theme_dir()
+        .map(|dir| dir.join(format!("{name}.toml")))
+        .filter(|path| path.exists())
+    else {
+        anyhow::bail!(
+            "Invalid theme: {name}, expected one of {} or a theme file",
+            Theme::BUILTIN.join(", ")
+        );
+    };
+    let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("read theme file {}", path.display()))?;
+    parse_theme(name, &contents).map_err(|message| anyhow::anyhow!("{}:{message}", path.display()))
+}
+
+pub fn theme_names() -> Vec<String> {
+    let mut names = Theme::BUILTIN
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    if let Some(entries) = theme_dir().and_then(|dir| std::fs::read_dir(dir).ok()) {
+        names.extend(entries.filter_map(|entry| {
+            let path = entry.ok()?.path();
+            (path.extension()? == "toml").then_some(path.file_stem()?.to_str()?.to_string())
+        }));
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn theme_dir() -> Option<PathBuf> {
+    Config::dir().map(|dir| dir.join("themes"))
+}
+
+fn parse_theme(name: &str, contents: &str) -> Result<Theme, String> {
+    let file: ThemeFile =
+        toml::from_str(contents).map_err(|error| describe_error(contents, &error))?;
+    let mut theme = Theme::named(name);
+    for (element, style) in file.styles {
+        theme.set_style(element, style);
+    }
+    Ok(theme)
+}
+
+fn describe_error(contents: &str, error: &toml::de::Error) -> String {
+    let (line, col) = error
+        .span()
+        .map_or((1, 1), |span| line_col(contents, span.start));
+    format!("{line}:{col}: {}", error.message())
+}
+
+/// The 1-based line and column of the byte at `offset`.
+fn line_col(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+fn deserialize_tabstop<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    let tabstop = Value::Int(usize::deserialize(deserializer)?);
+    OptionId::Tabstop
+        .info()
+        .check(tabstop)
+        .map(|tabstop| tabstop.as_int())
+        .map_err(D::Error::custom)
+}
+
+fn deserialize_theme<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    load_theme(&name).map_err(|err| D::Error::custom(format!("{err:#}")))?;
+    Ok(name)
+}
+
+fn deserialize_styles<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<(Element, Style)>, D::Error> {
+    BTreeMap::<String, StyleConfig>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, style)| {
+            let element = Element::find(&name).ok_or_else(|| {
+                D::Error::custom(format!(
+                    "unknown element `{name}`, expected one of {}",
+                    Element::NAMES.join(", ")
+                ))
+            })?;
+            let style = Style {
+                foreground: style.fg,
+                background: style.bg,
+                reverse: style.reverse,
+                bold: style.bold,
+                italic: style.italic,
+                underline: style.underline,
+            };
+            Ok((element, style))
+        })
+        .collect()
+}
+
+fn deserialize_color<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Color>, D::Error> {
+    parse_color(&String::deserialize(deserializer)?)
+        .map(Some)
+        .map_err(D::Error::custom)
+}
+
+fn deserialize_leader<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let leader = String::deserialize(deserializer)?;
+    keymap::parse_keys(&leader, "").map_err(D::Error::custom)?;
+    Ok(leader)
+}
+
+fn deserialize_statusline<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let statusline = String::deserialize(deserializer)?;
+    statusline
+        .parse::<StatusFormat>()
+        .map_err(|err| D::Error::custom(format!("{err:#}")))?;
+    Ok(statusline)
+}
+
+/// Checks the key notation with the default leader, which is as valid as any.
+fn deserialize_mappings<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<MappingConfig>, D::Error> {
+    let leader = default_leader();
+    let check_keys = |keys: &str| {
+        keymap::parse_keys(keys, &leader)
+            .map(|keys| keys.is_empty())
+            .map_err(D::Error::custom)
+    };
+    BTreeMap::<String, MappingValue>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(lhs, value)| {
+            if check_keys(&lhs)? {
+                return Err(D::Error::custom("mapping of no keys"));
+            }
+            let (rhs, remap) = match value {
+                MappingValue::Keys(keys) => (RhsConfig::Keys(keys), false),
+                MappingValue::Table(MappingTable {
+                    keys: Some(keys),
+                    command: None,
+                    remap,
+                }) => (RhsConfig::Keys(keys), remap),
+                MappingValue::Table(MappingTable {
+                    keys: None,
+                    command: Some(command),
+                    ..
+                }) => (RhsConfig::Command(command), false),
+                MappingValue::Table(_) => {
+                    return Err(D::Error::custom(format!(
+                        "mapping `{lhs}` needs either `keys` or `command`"
+                    )));
+                }
+            };
+            if let RhsConfig::Keys(keys) = &rhs {
+                check_keys(keys)?;
+            }
+            Ok(MappingConfig { lhs, rhs, remap })
+        })
+        .collect()
+}
+
+#[test]
+fn parse_config() {
+    let config = Config::parse(
+        r#"
+tabstop = 4
+expandtab = true
+number = true
+theme = "mono"
+mapleader = "<Space>"
+statusline = "%f%=%l:%c"
+
+[keymap.normal]
+"<C-s>" = ":w<CR>"
+"<Leader>q" = { command = "quit" }
+
+[keymap.visual]
+"<Leader>y" = { keys = "y", remap = true }
+"#,
+    )
+    .unwrap();
+    assert_eq!(config.tabstop, 4);
+    assert!(config.expandtab && config.number && !config.wrap);
+    assert!(config.hlsearch);
+    assert_eq!(config.theme, "mono");
+    assert_eq!(config.statusline, "%f%=%l:%c");
+    assert!(
+        config
+            .options()
+            .contains(&(OptionId::Number, Value::Bool(true)))
+    );
+    assert!(
+        config
+            .options()
+            .contains(&(OptionId::Mapleader, Value::String(Cow::Borrowed("<Space>"))))
+    );
+    assert_eq!(
+        config.keymap.normal,
+        [
+            MappingConfig {
+                lhs: String::from("<C-s>"),
+                rhs: RhsConfig::Keys(String::from(":w<CR>")),
+                remap: false,
+            },
+            MappingConfig {
+                lhs: String::from("<Leader>q"),
+                rhs: RhsConfig::Command(String::from("quit")),
+                remap: false,
+            },
+        ]
+    );
+    assert!(config.keymap.visual[0].remap);
+    assert!(config.keymap.insert.is_empty());
+
+    assert!(Config::parse("").is_ok());
+}
+
+#[test]
+fn parse_theme_file() {
+    let theme = parse_theme(
+        "dusk",
+        r##"
+[keyword]
+fg = "#6495ed"
+bold = true
+
+[status_bar]
+fg = "black"
+bg = "250"
+"##,
+    )
+    .unwrap();
+    assert_eq!(theme.name(), "dusk");
+    assert_eq!(
+        theme.style(Element::Keyword),
+        Style {
+            foreground: Some(Color::Rgb {
+                r: 100,
+                g: 149,
+                b: 237
+            }),
+            bold: true,
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        theme.style(Element::StatusBar).background,
+        Some(Color::AnsiValue(250))
+    );
+    assert_eq!(
+        theme.style(Element::Comment),
+        Theme::default().style(Element::Comment)
+    );
+
+    let error = |contents| parse_theme("dusk", contents).unwrap_err();
+    assert!(error("[keywords]\nbold = true").starts_with("1:1: unknown element `keywords`"));
+    assert!(error("[keyword]\nfg = \"teal\"").starts_with("2:6: Invalid color: teal"));
+    assert!(error("[keyword]\nblink = true").starts_with("2:1: unknown field `blink`"));
+}
+
+#[test]
+fn report_where_the_config_is_invalid() {
+    let error = |contents| Config::parse(contents).unwrap_err();
+
+    assert!(error("number = true\ntabsop = 4").starts_with("2:1: unknown field `tabsop`"));
+    assert_eq!(
+        error("tabstop = 0"),
+        "1:11: tabstop must be between 1 and 32, got 0"
+    );
+    assert!(error("wrap = 1").starts_with("1:8: invalid type"));
+    assert!(error("theme = \"solarized\"").starts_with("1:9: Invalid theme: solarized"));
+    assert!(error("statusline = \"%f %q\"").starts_with("1:14: Unknown statusline item: %q"));
+    assert!(error("[keymap.command]").starts_with("1:9: unknown field `command`"));
+    assert!(error("[keymap.normal]\njk = \"<Foo>\"").contains("Unknown key: <Foo>"));
+    assert!(
+        error("[keymap.normal]\nQ = { keys = \"q\", command = \"q\" }")
+            .contains("mapping `Q` needs either `keys` or `command`")
+    );
+}
@@ -1,10 +1,25 @@
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 
-use crate::editor::event::{Command, Direction};
+use crate::editor::event::{CharSearch, Command, Direction, Motion, Operator, ScreenLine};
 
 #[derive(Debug, Default)]
 pub struct Control {
     mode: State,
+    /// The mode to return to when the prompt is closed.
+    previous: State,
+    pending: Option<Pending>,
+    count: Option<usize>,
+    operator: Option<(Operator, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pending {
+    Register,
+    Find { forward: bool, till: bool },
+    G,
+    Z,
+    SetMark,
+    Mark { linewise: bool },
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
@@ -12,6 +27,7 @@ pub enum State {
     #[default]
     Normal,
     Insert,
+    Replace,
     Command,
 }
 
@@ -20,15 +36,211 @@ impl Control {
         if let Event::Key(e) = event {
             return match self.mode {
                 State::Normal => self.normal_command(e),
-                State::Insert => self.insert_command(e),
-                _ => todo!(),
+                State::Insert | State::Replace => self.insert_command(e),
+                State::Command => self.prompt_command(e),
             };
         }
 
         Command::try_from(event)
     }
 
+    /// Route keys to the prompt until `leave_prompt` is called.
+    pub fn enter_prompt(&mut self) {
+        if self.mode != State::Command {
+            self.previous = self.mode;
+            self.mode = State::Command;
+        }
+    }
+
+    pub fn leave_prompt(&mut self) {
+        self.pending = None;
+        if self.mode == State::Command {
+            self.mode = self.previous;
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.mode
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some() || self.operator.is_some()
+    }
+
+    pub fn enter_insert(&mut self) {
+        self.mode = State::Insert;
+    }
+
+    fn motion(&mut self, motion: Motion) -> Command {
+        let count = self.count.take().unwrap_or(1);
+        match self.operator.take() {
+            Some((operator, operator_count)) => Command::Operator {
+                operator,
+                motion,
+                count: operator_count.saturating_mul(count),
+            },
+            None => Command::Motion { motion, count },
+        }
+    }
+
     fn normal_command(&mut self, event: KeyEvent) -> anyhow::Result<Command> {
+        if event.kind != KeyEventKind::Press {
+            anyhow::bail!("not yet implement");
+        }
+        match (self.pending.take(), event.code) {
+            (Some(Pending::Find { forward, till }), KeyCode::Char(target)) => {
+                return Ok(self.motion(Motion::FindChar(CharSearch {
+                    target,
+                    forward,
+                    till,
+                })));
+            }
+            (Some(Pending::G), KeyCode::Char(c @ ('e' | 'E'))) => {
+                return Ok(self.motion(Motion::WordEnd {
+                    forward: false,
+                    big: c == 'E',
+                }));
+            }
+            (Some(Pending::G), KeyCode::Char('g')) => {
+                let line = self.count.take().unwrap_or(1);
+                return Ok(self.motion(Motion::Line(line)));
+            }
+            (Some(Pending::Z), KeyCode::Char(c @ ('t' | 'z' | 'b'))) if self.operator.is_none() => {
+                self.count = None;
+                return Ok(Command::Reposition(match c {
+                    't' => ScreenLine::Top,
+                    'z' => ScreenLine::Middle,
+                    _ => ScreenLine::Bottom,
+                }));
+            }
+            (Some(Pending::SetMark), KeyCode::Char(name)) if self.operator.is_none() => {
+                self.count = None;
+                return Ok(Command::SetMark(name));
+            }
+            (Some(Pending::Mark { linewise }), KeyCode::Char(name)) => {
+                return Ok(self.motion(Motion::Mark { name, linewise }));
+            }
+            (Some(_), _) => {
+                self.count = None;
+                self.operator = None;
+                anyhow::bail!("not a command");
+            }
+            (None, _) => {}
+        }
+
+        match (event.code, event.modifiers) {
+            (KeyCode::Char(digit @ '0'..='9'), KeyModifiers::NONE)
+                if digit != '0' || self.count.is_some() =>
+            {
+                let digit = digit.to_digit(10).unwrap_or_default() as usize;
+                self.count = Some(
+                    self.count
+                        .unwrap_or_default()
+                        .saturating_mul(10)
+                        .saturating_add(digit),
+                );
+                anyhow::bail!("waiting for a command");
+            }
+            (KeyCode::Char(c @ ('f' | 't')), KeyModifiers::NONE)
+            | (KeyCode::Char(c @ ('F' | 'T')), KeyModifiers::SHIFT) => {
+                self.pending = Some(Pending::Find {
+                    forward: c.is_lowercase(),
+                    till: c.eq_ignore_ascii_case(&'t'),
+                });
+                anyhow::bail!("waiting for a character");
+            }
+            (KeyCode::Char('g'), KeyModifiers::NONE) => {
+                self.pending = Some(Pending::G);
+                anyhow::bail!("waiting for a command");
+            }
+            (KeyCode::Char(c @ ('w' | 'b' | 'e')), KeyModifiers::NONE)
+            | (KeyCode::Char(c @ ('W' | 'B' | 'E')), KeyModifiers::SHIFT) => {
+                let forward = !c.eq_ignore_ascii_case(&'b');
+                let big = c.is_uppercase();
+                let motion = if c.eq_ignore_ascii_case(&'e') {
+                    Motion::WordEnd { forward, big }
+                } else {
+                    Motion::WordStart { forward, big }
+                };
+                return Ok(self.motion(motion));
+            }
+            (KeyCode::Char('m'), KeyModifiers::NONE) => {
+                self.pending = Some(Pending::SetMark);
+                anyhow::bail!("waiting for a mark");
+            }
+            (KeyCode::Char(c @ ('\'' | '`')), _) => {
+                self.pending = Some(Pending::Mark {
+                    linewise: c == '\'',
+                });
+                anyhow::bail!("waiting for a mark");
+            }
+            (KeyCode::Char('z'), KeyModifiers::NONE) => {
+                self.pending = Some(Pending::Z);
+                anyhow::bail!("waiting for a command");
+            }
+            (KeyCode::Char('G'), KeyModifiers::SHIFT) => {
+                let line = self.count.take().unwrap_or(usize::MAX);
+                return Ok(self.motion(Motion::Line(line)));
+            }
+            (
+                KeyCode::Char(c @ ('{' | '}' | '(' | ')')),
+                KeyModifiers::NONE | KeyModifiers::SHIFT,
+            ) => {
+                let forward = matches!(c, '}' | ')');
+                let motion = if matches!(c, '{' | '}') {
+                    Motion::Paragraph { forward }
+                } else {
+                    Motion::Sentence { forward }
+                };
+                return Ok(self.motion(motion));
+            }
+            (KeyCode::Char(c @ ('H' | 'M' | 'L')), KeyModifiers::SHIFT) => {
+                let screen_line = match c {
+                    'H' => ScreenLine::Top,
+                    'M' => ScreenLine::Middle,
+                    _ => ScreenLine::Bottom,
+                };
+                return Ok(self.motion(Motion::ScreenLine(screen_line)));
+            }
+            (KeyCode::Char(c @ ('d' | 'u' | 'f' | 'b')), KeyModifiers::CONTROL)
+                if self.operator.is_none() =>
+            {
+                return Ok(Command::Scroll {
+                    forward: matches!(c, 'd' | 'f'),
+                    half: matches!(c, 'd' | 'u'),
+                    count: self.count.take().unwrap_or(1),
+                });
+            }
+            (KeyCode::PageDown | KeyCode::PageUp, _) if self.operator.is_none() => {
+                return Ok(Command::Scroll {
+                    forward: event.code == KeyCode::PageDown,
+                    half: false,
+                    count: self.count.take().unwrap_or(1),
+                });
+            }
+            (KeyCode::Char(';'), KeyModifiers::NONE) => return Ok(self.motion(Motion::RepeatFind)),
+            (KeyCode::Char(','), KeyModifiers::NONE) => {
+                return Ok(self.motion(Motion::RepeatFindReverse));
+            }
+            (KeyCode::Char(c @ ('d' | 'c' | 'y')), KeyModifiers::NONE)
+                if self.operator.is_none() =>
+            {
+                let operator = match c {
+                    'd' => Operator::Delete,
+                    'c' => Operator::Change,
+                    _ => Operator::Yank,
+                };
+                self.operator = Some((operator, self.count.take().unwrap_or(1)));
+                anyhow::bail!("waiting for a motion");
+            }
+            _ => {}
+        }
+
+        self.count = None;
+        if self.operator.take().is_some() {
+            anyhow::bail!("not a motion");
+        }
+
         match event {
             KeyEvent {
                 code,
@@ -48,18 +260,31 @@ impl Control {
                     self.mode = State::Insert;
                     Ok(Command::EndOfLine)
                 }
+                (KeyCode::Char('r') | KeyCode::Char('R'), KeyModifiers::SHIFT) => {
+                    self.mode = State::Replace;
+                    Ok(Command::Switch(State::Replace))
+                }
                 (KeyCode::Char('k'), KeyModifiers::NONE) => Ok(Command::Move(Direction::Up)),
                 (KeyCode::Char('j'), KeyModifiers::NONE) => Ok(Command::Move(Direction::Down)),
                 (KeyCode::Char('h'), KeyModifiers::NONE) => Ok(Command::Move(Direction::Left)),
                 (KeyCode::Char('l'), KeyModifiers::NONE) => Ok(Command::Move(Direction::Right)),
-                (KeyCode::Char('w') | KeyCode::Char('e'), KeyModifiers::NONE) => {
-                    Ok(Command::NextWord)
-                }
-                (KeyCode::Char('b'), KeyModifiers::NONE) => Ok(Command::PreviousWord),
                 (KeyCode::Char('0'), KeyModifiers::NONE) => Ok(Command::StartOfLine),
                 (KeyCode::Char('$'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
                     Ok(Command::EndOfLine)
                 }
+                (KeyCode::Char('n'), KeyModifiers::NONE) => Ok(Command::SearchNext),
+                (KeyCode::Char('n') | KeyCode::Char('N'), KeyModifiers::SHIFT) => {
+                    Ok(Command::SearchPrevious)
+                }
+                (KeyCode::Char('*'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                    Ok(Command::SearchWordForward)
+                }
+                (KeyCode::Char('#'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                    Ok(Command::SearchWordBackward)
+                }
+                (KeyCode::Char('%'), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                    Ok(Command::MatchBracket)
+                }
                 (KeyCode::Char('o'), KeyModifiers::NONE) => {
                     self.mode = State::Insert;
                     Ok(Command::OpenLineBelow)
@@ -68,14 +293,17 @@ impl Control {
                     self.mode = State::Insert;
                     Ok(Command::OpenLineAbove)
                 }
-                (KeyCode::Char('s'), KeyModifiers::NONE) => {
-                    self.mode = State::Insert;
+                (KeyCode::Char('s') | KeyCode::Char('/'), KeyModifiers::NONE) => {
                     Ok(Command::Search)
                 }
-                (KeyCode::Char(':'), _) => {
-                    self.mode = State::Insert;
-                    Ok(Command::Switch(State::Command))
+                (KeyCode::Char('u'), KeyModifiers::NONE) => Ok(Command::Undo),
+                (KeyCode::Char('r'), KeyModifiers::CONTROL) => Ok(Command::Redo),
+                (KeyCode::Char('o'), KeyModifiers::CONTROL) => Ok(Command::JumpOlder),
+                // Terminals send `Ctrl-i` as Tab.
+                (KeyCode::Char('i'), KeyModifiers::CONTROL) | (KeyCode::Tab, _) => {
+                    Ok(Command::JumpNewer)
                 }
+                (KeyCode::Char(':'), _) => Ok(Command::Switch(State::Command)),
                 _ => anyhow::bail!("not yet implement"),
             },
             _ => anyhow::bail!("not yet implement"),
@@ -110,10 +338,162 @@ impl Control {
                 (KeyCode::Right, _) => Ok(Command::Move(Direction::Right)),
                 (KeyCode::Home, _) => Ok(Command::StartOfLine),
                 (KeyCode::End, _) => Ok(Command::EndOfLine),
+                (KeyCode::PageDown | KeyCode::PageUp, _) => Ok(Command::Scroll {
+                    forward: code == KeyCode::PageDown,
+                    half: false,
+                    count: 1,
+                }),
                 // (KeyCode::Esc, KeyModifiers::NONE) => Ok(Command::Dismiss),
                 _ => anyhow::bail!("not yet implement"),
             },
             _ => anyhow::bail!("not yet implement"),
         }
     }
+
+    fn prompt_command(&mut self, event: KeyEvent) -> anyhow::Result<Command> {
+        if event.kind == KeyEventKind::Press
+            && let Some(Pending::Register) = self.pending.take()
+        {
+            return match event.code {
+                KeyCode::Char(name) => Ok(Command::PasteRegister(name)),
+                _ => anyhow::bail!("not a register"),
+            };
+        }
+
+        match event {
+            KeyEvent {
+                code,
+                modifiers,
+                kind: KeyEventKind::Press,
+                ..
+            } => match (code, modifiers) {
+                (KeyCode::Esc, KeyModifiers::NONE) => Ok(Command::Dismiss),
+                (KeyCode::Char('w'), KeyModifiers::CONTROL) => Ok(Command::DeleteWordBackward),
+                (KeyCode::Char('u'), KeyModifiers::CONTROL) => Ok(Command::DeleteToStartOfLine),
+                (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
+                    self.pending = Some(Pending::Register);
+                    anyhow::bail!("waiting for a register name")
+                }
+                (KeyCode::Char('p'), KeyModifiers::CONTROL) => Ok(Command::PreviousHistory),
+                (KeyCode::Char('n'), KeyModifiers::CONTROL) => Ok(Command::NextHistory),
+                (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                    Ok(Command::Insert(c))
+                }
+                (KeyCode::Tab, _) => Ok(Command::NextCompletion),
+                (KeyCode::BackTab, _) => Ok(Command::PreviousCompletion),
+                (KeyCode::Enter, _) => Ok(Command::Enter),
+                (KeyCode::Backspace, _) => Ok(Command::Backspace),
+                (KeyCode::Delete, _) => Ok(Command::Delete),
+                (KeyCode::Up, _) => Ok(Command::Move(Direction::Up)),
+                (KeyCode::Down, _) => Ok(Command::Move(Direction::Down)),
+                (KeyCode::Left, _) => Ok(Command::Move(Direction::Left)),
+                (KeyCode::Right, _) => Ok(Command::Move(Direction::Right)),
+                (KeyCode::Home, _) => Ok(Command::StartOfLine),
+                (KeyCode::End, _) => Ok(Command::EndOfLine),
+                _ => anyhow::bail!("not yet implement"),
+            },
+            _ => anyhow::bail!("not yet implement"),
+        }
+    }
+}
+
+#[test]
+fn counts_and_operators() {
+    let mut control = Control::default();
+    let mut press = |c: char| {
+        let modifiers = if c.is_uppercase() {
+            KeyModifiers::SHIFT
+        } else {
+            KeyModifiers::NONE
+        };
+        control.evaluate(Event::Key(KeyEvent::new(KeyCode::Char(c), modifiers)))
+    };
+
+    assert!(press('2').is_err());
+    assert!(press('d').is_err());
+    assert!(press('3').is_err());
+    assert!(press('t').is_err());
+    assert_eq!(
+        press(',').unwrap(),
+        Command::Operator {
+            operator: Operator::Delete,
+            motion: Motion::FindChar(CharSearch {
+                target: ',',
+                forward: true,
+                till: true,
+            }),
+            count: 6,
+        }
+    );
+
+    assert!(press('1').is_err());
+    assert!(press('0').is_err());
+    assert!(press('F').is_err());
+    assert_eq!(
+        press('😀').unwrap(),
+        Command::Motion {
+            motion: Motion::FindChar(CharSearch {
+                target: '😀',
+                forward: false,
+                till: false,
+            }),
+            count: 10,
+        }
+    );
+
+    // An operator without a motion is cancelled.
+    assert!(press('y').is_err());
+    assert!(press('u').is_err());
+    assert_eq!(press('u').unwrap(), Command::Undo);
+    assert_eq!(press('0').unwrap(), Command::StartOfLine);
+}
+
+#[test]
+fn line_and_scroll_commands() {
+    let mut control = Control::default();
+    let mut press = |code: KeyCode, modifiers: KeyModifiers| {
+        control.evaluate(Event::Key(KeyEvent::new(code, modifiers)))
+    };
+
+    assert!(press(KeyCode::Char('g'), KeyModifiers::NONE).is_err());
+    assert_eq!(
+        press(KeyCode::Char('g'), KeyModifiers::NONE).unwrap(),
+        Command::Motion {
+            motion: Motion::Line(1),
+            count: 1,
+        }
+    );
+    assert_eq!(
+        press(KeyCode::Char('G'), KeyModifiers::SHIFT).unwrap(),
+        Command::Motion {
+            motion: Motion::Line(usize::MAX),
+            count: 1,
+        }
+    );
+
+    assert!(press(KeyCode::Char('d'), KeyModifiers::NONE).is_err());
+    assert!(press(KeyCode::Char('4'), KeyModifiers::NONE).is_err());
+    assert_eq!(
+        press(KeyCode::Char('G'), KeyModifiers::SHIFT).unwrap(),
+        Command::Operator {
+            operator: Operator::Delete,
+            motion: Motion::Line(4),
+            count: 1,
+        }
+    );
+
+    assert!(press(KeyCode::Char('3'), KeyModifiers::NONE).is_err());
+    assert_eq!(
+        press(KeyCode::Char('d'), KeyModifiers::CONTROL).unwrap(),
+        Command::Scroll {
+            forward: true,
+            half: true,
+            count: 3,
+        }
+    );
+    assert!(press(KeyCode::Char('z'), KeyModifiers::NONE).is_err());
+    assert_eq!(
+        press(KeyCode::Char('t'), KeyModifiers::NONE).unwrap(),
+        Command::Reposition(ScreenLine::Top)
+    );
 }
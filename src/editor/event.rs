@@ -1,5 +1,7 @@
 use anyhow::{Ok, anyhow};
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
 
 use crate::editor::{Size, control::State};
 
@@ -11,6 +13,77 @@ pub enum Direction {
     Right,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CharSearch {
+    pub target: char,
+    pub forward: bool,
+    pub till: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    FindChar(CharSearch),
+    RepeatFind,
+    RepeatFindReverse,
+    WordStart {
+        forward: bool,
+        big: bool,
+    },
+    WordEnd {
+        forward: bool,
+        big: bool,
+    },
+    Paragraph {
+        forward: bool,
+    },
+    Sentence {
+        forward: bool,
+    },
+    ScreenLine(ScreenLine),
+    /// `gg` / `G`: a 1-based line number, clamped to the last line.
+    Line(usize),
+    /// `'{name}` to the first non-blank of the mark's line, or `` `{name} `` to the mark.
+    Mark {
+        name: char,
+        linewise: bool,
+    },
+}
+
+impl Motion {
+    pub fn is_jump(self) -> bool {
+        matches!(
+            self,
+            Self::Paragraph { .. }
+                | Self::Sentence { .. }
+                | Self::ScreenLine(_)
+                | Self::Line(_)
+                | Self::Mark { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenLine {
+    Top,
+    Middle,
+    Bottom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseAction {
+    Click,
+    Drag,
+    ScrollUp,
+    ScrollDown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Command {
     Move(Direction),
@@ -21,8 +94,6 @@ pub enum Command {
     Backspace,
     StartOfLine,
     EndOfLine,
-    NextWord,
-    PreviousWord,
     Enter,
     Save,
     Search,
@@ -30,6 +101,44 @@ pub enum Command {
     Switch(State),
     OpenLineBelow,
     OpenLineAbove,
+    MatchBracket,
+    Undo,
+    Redo,
+    PreviousHistory,
+    NextHistory,
+    DeleteWordBackward,
+    DeleteToStartOfLine,
+    PasteRegister(char),
+    NextCompletion,
+    PreviousCompletion,
+    SearchWordForward,
+    SearchWordBackward,
+    SearchNext,
+    SearchPrevious,
+    Motion {
+        motion: Motion,
+        count: usize,
+    },
+    Operator {
+        operator: Operator,
+        motion: Motion,
+        count: usize,
+    },
+    /// `Ctrl-d` / `Ctrl-u` scroll half a window, `Ctrl-f` / `Ctrl-b` a whole one.
+    Scroll {
+        forward: bool,
+        half: bool,
+        count: usize,
+    },
+    Reposition(ScreenLine),
+    SetMark(char),
+    JumpOlder,
+    JumpNewer,
+    Mouse {
+        action: MouseAction,
+        col: u16,
+        row: u16,
+    },
 }
 
 impl TryFrom<Event> for Command {
@@ -62,8 +171,24 @@ impl TryFrom<Event> for Command {
                 (KeyCode::Esc, KeyModifiers::NONE) => Ok(Self::Dismiss),
                 _ => Err(anyhow!("Not yet implement")),
             },
-            Event::Mouse(_mouse_event) => Err(anyhow!("Not yet implement")),
-            Event::Paste(_) => Err(anyhow!("Not yet implement")),
+            Event::Mouse(MouseEvent {
+                kind, column, row, ..
+            }) => {
+                let action = match kind {
+                    MouseEventKind::Down(MouseButton::Left) => MouseAction::Click,
+                    MouseEventKind::Drag(MouseButton::Left) => MouseAction::Drag,
+                    MouseEventKind::ScrollUp => MouseAction::ScrollUp,
+                    MouseEventKind::ScrollDown => MouseAction::ScrollDown,
+                    _ => return Err(anyhow!("Not yet implement")),
+                };
+                Ok(Self::Mouse {
+                    action,
+                    col: column,
+                    row,
+                })
+            }
+            // Handled by the editor, the text does not fit into a `Command`.
+            Event::Paste(_) => Err(anyhow!("Paste is not a command")),
             Event::Resize(width, height) => Ok(Self::Resize(Size { width, height })),
             _ => Err(anyhow!("Not yet implement")),
         }
@@ -0,0 +1,459 @@
+use std::{ops::Range, str::FromStr};
+
+use crate::editor::keymap::MapMode;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExCommand {
+    Quit,
+    Set(Vec<String>),
+    SetLocal(Vec<String>),
+    Substitute(Substitute),
+    Write(Option<String>),
+    SaveAs(String),
+    Edit(String),
+    /// `:b [name]`
+    Buffer(Option<String>),
+    /// `:colo [name]`, showing the current theme without a name.
+    Colorscheme(Option<String>),
+    NoHighlight,
+    /// `:map`, `:nmap`, `:noremap` and the like. Without `rhs` the mappings
+    /// starting with `lhs` are listed.
+    Map {
+        modes: &'static [MapMode],
+        remap: bool,
+        lhs: Option<String>,
+        rhs: Option<String>,
+    },
+    Unmap {
+        modes: &'static [MapMode],
+        lhs: String,
+    },
+}
+
+/// Command names with their shortest accepted abbreviation. Checked in order, so
+/// longer abbreviations sharing a prefix come first.
+const COMMANDS: &[(&str, &str)] = &[
+    ("b", "buffer"),
+    ("colo", "colorscheme"),
+    ("e", "edit"),
+    ("im", "imap"),
+    ("ino", "inoremap"),
+    ("iu", "iunmap"),
+    ("map", "map"),
+    ("nm", "nmap"),
+    ("nn", "nnoremap"),
+    ("noh", "nohlsearch"),
+    ("no", "noremap"),
+    ("nun", "nunmap"),
+    ("q", "quit"),
+    ("sav", "saveas"),
+    ("setl", "setlocal"),
+    ("se", "set"),
+    ("s", "substitute"),
+    ("unm", "unmap"),
+    ("vm", "vmap"),
+    ("vn", "vnoremap"),
+    ("vu", "vunmap"),
+    ("w", "write"),
+];
+
+fn map_command(name: &str) -> Option<(&'static [MapMode], bool)> {
+    const NORMAL_VISUAL: &[MapMode] = &[MapMode::Normal, MapMode::Visual];
+    Some(match name {
+        "map" | "unmap" => (NORMAL_VISUAL, true),
+        "noremap" => (NORMAL_VISUAL, false),
+        "nmap" | "nunmap" => (&[MapMode::Normal], true),
+        "nnoremap" => (&[MapMode::Normal], false),
+        "vmap" | "vunmap" => (&[MapMode::Visual], true),
+        "vnoremap" => (&[MapMode::Visual], false),
+        "imap" | "iunmap" => (&[MapMode::Insert], true),
+        "inoremap" => (&[MapMode::Insert], false),
+        _ => return None,
+    })
+}
+
+pub fn command_name(name: &str) -> Option<&'static str> {
+    COMMANDS
+        .iter()
+        .find(|(abbreviation, full)| name.starts_with(abbreviation) && full.starts_with(name))
+        .map(|(_, full)| *full)
+}
+
+pub fn command_names(prefix: &str) -> Vec<String> {
+    let mut names = COMMANDS
+        .iter()
+        .map(|(_, full)| full.to_string())
+        .filter(|full| full.starts_with(prefix))
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+pub fn split_command(s: &str) -> (&str, &str) {
+    let (_, rest) = parse_range(s.trim_start());
+    let name_end = rest
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(rest.len());
+    rest.split_at(name_end)
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Substitute {
+    pub range: LineRange,
+    pub pattern: String,
+    pub replacement: String,
+    pub flags: SubstituteFlags,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SubstituteFlags {
+    pub global: bool,
+    pub confirm: bool,
+    /// `i` / `I`: override the `ignorecase` option.
+    pub ignore_case: Option<bool>,
+    pub count_only: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Address {
+    Current,
+    Last,
+    /// 1-based line number, as typed.
+    Line(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineAddress {
+    base: Address,
+    offset: isize,
+}
+
+impl LineAddress {
+    const CURRENT: Self = Self {
+        base: Address::Current,
+        offset: 0,
+    };
+
+    /// return: 0-based line index
+    fn resolve(&self, current: usize, len: usize) -> anyhow::Result<usize> {
+        let line = match self.base {
+            Address::Current => current,
+            Address::Last => len.saturating_sub(1),
+            Address::Line(line) => line.saturating_sub(1),
+        };
+        line.checked_add_signed(self.offset)
+            .filter(|line| *line < len.max(1))
+            .ok_or_else(|| anyhow::anyhow!("Invalid range"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    start: LineAddress,
+    end: LineAddress,
+}
+
+impl Default for LineRange {
+    fn default() -> Self {
+        Self {
+            start: LineAddress::CURRENT,
+            end: LineAddress::CURRENT,
+        }
+    }
+}
+
+impl LineRange {
+    const WHOLE: Self = Self {
+        start: LineAddress {
+            base: Address::Line(1),
+            offset: 0,
+        },
+        end: LineAddress {
+            base: Address::Last,
+            offset: 0,
+        },
+    };
+
+    /// return: 0-based line indices
+    pub fn resolve(&self, current: usize, len: usize) -> anyhow::Result<Range<usize>> {
+        let start = self.start.resolve(current, len)?;
+        let end = self.end.resolve(current, len)?;
+        if start > end {
+            anyhow::bail!("Backwards range given");
+        }
+        Ok(start..end.saturating_add(1))
+    }
+}
+
+fn parse_number(s: &str) -> (Option<usize>, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    (s[..end].parse().ok(), &s[end..])
+}
+
+fn parse_address(s: &str) -> (Option<LineAddress>, &str) {
+    let (base, mut rest) = match s.chars().next() {
+        Some('.') => (Some(Address::Current), &s[1..]),
+        Some('$') => (Some(Address::Last), &s[1..]),
+        _ => match parse_number(s) {
+            (Some(line), rest) => (Some(Address::Line(line)), rest),
+            (None, rest) => (None, rest),
+        },
+    };
+
+    let mut offset = None::<isize>;
+    while let Some(sign) = rest.chars().next().filter(|c| matches!(c, '+' | '-')) {
+        let (count, remainder) = parse_number(&rest[1..]);
+        let count = count.unwrap_or(1) as isize;
+        offset = Some(offset.unwrap_or_default() + if sign == '+' { count } else { -count });
+        rest = remainder;
+    }
+
+    if base.is_none() && offset.is_none() {
+        return (None, s);
+    }
+    (
+        Some(LineAddress {
+            base: base.unwrap_or(Address::Current),
+            offset: offset.unwrap_or_default(),
+        }),
+        rest,
+    )
+}
+
+fn parse_range(s: &str) -> (Option<LineRange>, &str) {
+    if let Some(rest) = s.strip_prefix('%') {
+        return (Some(LineRange::WHOLE), rest);
+    }
+
+    let (start, rest) = parse_address(s);
+    if let Some(rest) = rest.strip_prefix(',') {
+        let (end, rest) = parse_address(rest);
+        return (
+            Some(LineRange {
+                start: start.unwrap_or(LineAddress::CURRENT),
+                end: end.unwrap_or(LineAddress::CURRENT),
+            }),
+            rest,
+        );
+    }
+
+    (start.map(|start| LineRange { start, end: start }), rest)
+}
+
+/// Splits `s` at the first `delimiter` not escaped with a backslash, dropping the
+/// backslash of escaped delimiters.
+///
+/// return: (field, remainder after the delimiter)
+fn split_field(s: &str, delimiter: char) -> (String, Option<&str>) {
+    let mut field = String::new();
+    let mut chars = s.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        if c == delimiter {
+            return (field, Some(&s[idx.saturating_add(c.len_utf8())..]));
+        }
+        if c == '\\' {
+            match chars.next() {
+                Some((_, next)) if next == delimiter => field.push(next),
+                Some((_, next)) => {
+                    field.push(c);
+                    field.push(next);
+                }
+                None => field.push(c),
+            }
+        } else {
+            field.push(c);
+        }
+    }
+    (field, None)
+}
+
+fn parse_substitute(range: LineRange, args: &str) -> anyhow::Result<Substitute> {
+    let Some(delimiter) = args.chars().next() else {
+        anyhow::bail!("Missing pattern");
+    };
+    if delimiter.is_alphanumeric() || delimiter.is_whitespace() || matches!(delimiter, '\\' | '"') {
+        anyhow::bail!("Invalid delimiter: {delimiter}");
+    }
+
+    let (pattern, rest) = split_field(&args[delimiter.len_utf8()..], delimiter);
+    let (replacement, rest) =
+        rest.map_or((String::new(), None), |rest| split_field(rest, delimiter));
+
+    let mut flags = SubstituteFlags::default();
+    for flag in rest.unwrap_or_default().trim().chars() {
+        match flag {
+            'g' => flags.global = !flags.global,
+            'c' => flags.confirm = true,
+            'i' => flags.ignore_case = Some(true),
+            'I' => flags.ignore_case = Some(false),
+            'n' => flags.count_only = true,
+            _ => anyhow::bail!("Trailing characters: {flag}"),
+        }
+    }
+
+    Ok(Substitute {
+        range,
+        pattern,
+        replacement,
+        flags,
+    })
+}
+
+fn parse_map(name: &str, args: &str) -> anyhow::Result<ExCommand> {
+    let Some((modes, remap)) = map_command(name) else {
+        anyhow::bail!("Not a mapping command: {name}");
+    };
+    let args = args.trim();
+    let (lhs, rhs) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_string());
+    if name.ends_with("unmap") {
+        let lhs = non_empty(lhs).ok_or_else(|| anyhow::anyhow!("Argument required"))?;
+        return Ok(ExCommand::Unmap { modes, lhs });
+    }
+    Ok(ExCommand::Map {
+        modes,
+        remap,
+        lhs: non_empty(lhs),
+        rhs: non_empty(rhs.trim_start()),
+    })
+}
+
+impl FromStr for ExCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (range, _) = parse_range(s);
+        let (name, args) = split_command(s);
+        let argument = || {
+            let argument = args.trim();
+            if argument.is_empty() {
+                anyhow::bail!("Argument required");
+            }
+            Ok(argument.to_string())
+        };
+
+        match (command_name(name), range) {
+            (Some("substitute"), range) => {
+                parse_substitute(range.unwrap_or_default(), args).map(Self::Substitute)
+            }
+            (Some(_), Some(_)) => anyhow::bail!("No range allowed"),
+            (Some("quit"), None) if args.trim().is_empty() => Ok(Self::Quit),
+            (Some("nohlsearch"), None) if args.trim().is_empty() => Ok(Self::NoHighlight),
+            (Some("set"), None) => Ok(Self::Set(
+                args.split_whitespace().map(String::from).collect(),
+            )),
+            (Some("setlocal"), None) => Ok(Self::SetLocal(
+                args.split_whitespace().map(String::from).collect(),
+            )),
+            (Some("write"), None) => Ok(Self::Write(argument().ok())),
+            (Some("saveas"), None) => argument().map(Self::SaveAs),
+            (Some("edit"), None) => argument().map(Self::Edit),
+            (Some("buffer"), None) => Ok(Self::Buffer(argument().ok())),
+            (Some("colorscheme"), None) => Ok(Self::Colorscheme(argument().ok())),
+            (Some(name), None) if map_command(name).is_some() => parse_map(name, args),
+            _ => anyhow::bail!("Not an editor command: {s}"),
+        }
+    }
+}
+
+#[test]
+fn parse_ex_command() {
+    assert_eq!("q".parse::<ExCommand>().unwrap(), ExCommand::Quit);
+    assert_eq!(
+        " set ic  noscs ".parse::<ExCommand>().unwrap(),
+        ExCommand::Set(vec![String::from("ic"), String::from("noscs")])
+    );
+    assert!("frobnicate".parse::<ExCommand>().is_err());
+    assert!("1,2q".parse::<ExCommand>().is_err());
+    assert_eq!(
+        "b main.rs".parse::<ExCommand>().unwrap(),
+        ExCommand::Buffer(Some(String::from("main.rs")))
+    );
+    assert_eq!("w".parse::<ExCommand>().unwrap(), ExCommand::Write(None));
+    assert_eq!(
+        "sav  notes.txt ".parse::<ExCommand>().unwrap(),
+        ExCommand::SaveAs(String::from("notes.txt"))
+    );
+    assert!("edit".parse::<ExCommand>().is_err());
+    assert_eq!("noh".parse::<ExCommand>().unwrap(), ExCommand::NoHighlight);
+    assert_eq!(
+        "colo mono".parse::<ExCommand>().unwrap(),
+        ExCommand::Colorscheme(Some(String::from("mono")))
+    );
+    assert_eq!(
+        "setl nu".parse::<ExCommand>().unwrap(),
+        ExCommand::SetLocal(vec![String::from("nu")])
+    );
+    assert_eq!(
+        "nn <Leader>w  :w<CR> ".parse::<ExCommand>().unwrap(),
+        ExCommand::Map {
+            modes: &[MapMode::Normal],
+            remap: false,
+            lhs: Some(String::from("<Leader>w")),
+            rhs: Some(String::from(":w<CR>")),
+        }
+    );
+    assert_eq!(
+        "map".parse::<ExCommand>().unwrap(),
+        ExCommand::Map {
+            modes: &[MapMode::Normal, MapMode::Visual],
+            remap: true,
+            lhs: None,
+            rhs: None,
+        }
+    );
+    assert_eq!(
+        "no".parse::<ExCommand>().unwrap(),
+        "noremap".parse().unwrap()
+    );
+    assert_eq!(
+        "iu jk".parse::<ExCommand>().unwrap(),
+        ExCommand::Unmap {
+            modes: &[MapMode::Insert],
+            lhs: String::from("jk"),
+        }
+    );
+    assert!("unmap".parse::<ExCommand>().is_err());
+}
+
+#[test]
+fn parse_substitute_command() {
+    assert_eq!(
+        "%s/a\\/b/[&]/gc".parse::<ExCommand>().unwrap(),
+        ExCommand::Substitute(Substitute {
+            range: LineRange::WHOLE,
+            pattern: String::from("a/b"),
+            replacement: String::from("[&]"),
+            flags: SubstituteFlags {
+                global: true,
+                confirm: true,
+                ..Default::default()
+            },
+        })
+    );
+
+    let ExCommand::Substitute(substitute) = "s#\\v(\\w+)#\\1#".parse::<ExCommand>().unwrap() else {
+        panic!("expected a substitution");
+    };
+    assert_eq!(substitute.range, LineRange::default());
+    assert_eq!(substitute.pattern, "\\v(\\w+)");
+    assert_eq!(substitute.replacement, "\\1");
+
+    assert!("s/a/b/x".parse::<ExCommand>().is_err());
+    assert!("sa".parse::<ExCommand>().is_err());
+}
+
+#[test]
+fn resolve_range() {
+    let range = |s: &str| parse_range(s).0.unwrap_or_default().resolve(4, 10);
+
+    assert_eq!(range("").unwrap(), 4..5);
+    assert_eq!(range("%").unwrap(), 0..10);
+    assert_eq!(range("2,$").unwrap(), 1..10);
+    assert_eq!(range(".,+2").unwrap(), 4..7);
+    assert_eq!(range("-1,.").unwrap(), 3..5);
+    assert_eq!(range("3").unwrap(), 2..3);
+    assert!(range("5,2").is_err());
+    assert!(range("11").is_err());
+}
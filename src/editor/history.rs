@@ -0,0 +1,169 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+const HISTORY_SIZE: usize = 200;
+const SEARCH_PREFIX: char = '/';
+const COMMAND_PREFIX: char = ':';
+
+/// Previously entered lines of a prompt, oldest first.
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<String>,
+    /// The entry shown while browsing, `None` when editing a new line.
+    position: Option<usize>,
+    /// The text typed before browsing started, only entries starting with it are shown.
+    prefix: String,
+}
+
+impl History {
+    pub fn add(&mut self, entry: String) {
+        self.reset();
+        if entry.trim().is_empty() {
+            return;
+        }
+
+        self.entries.retain(|existing| existing != &entry);
+        self.entries.push(entry);
+        if self.entries.len() > HISTORY_SIZE {
+            self.entries.remove(0);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.position = None;
+        self.prefix.clear();
+    }
+
+    /// `current` is the text in the prompt, remembered when browsing starts.
+    pub fn previous(&mut self, current: &str) -> Option<&str> {
+        if self.position.is_none() {
+            self.prefix = current.to_string();
+        }
+        let end = self.position.unwrap_or(self.entries.len());
+
+        let position = self.entries[..end]
+            .iter()
+            .rposition(|entry| entry.starts_with(&self.prefix))?;
+        self.position = Some(position);
+        Some(&self.entries[position])
+    }
+
+    /// Step to a newer entry, returning to the typed text after the newest one.
+    pub fn next(&mut self) -> Option<&str> {
+        let start = self.position?.saturating_add(1);
+
+        match self.entries[start..]
+            .iter()
+            .position(|entry| entry.starts_with(&self.prefix))
+        {
+            Some(offset) => {
+                let position = start.saturating_add(offset);
+                self.position = Some(position);
+                Some(&self.entries[position])
+            }
+            None => {
+                self.position = None;
+                Some(&self.prefix)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Histories {
+    pub search: History,
+    pub command: History,
+}
+
+impl Histories {
+    /// `$XDG_STATE_HOME/oxide/history`, falling back to `~/.local/state/oxide/history`.
+    pub fn file() -> Option<PathBuf> {
+        std::env::var_os("XDG_STATE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state"))
+            })
+            .map(|dir| dir.join(env!("CARGO_PKG_NAME")).join("history"))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path).context("read history file")?;
+        Ok(Self::parse(&contents))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).context("create state directory")?;
+        }
+        let mut file = std::fs::File::create(path).context("create history file")?;
+        write!(file, "{}", self.serialize()).context("write to history file")
+    }
+
+    fn parse(contents: &str) -> Self {
+        let mut histories = Self::default();
+        for line in contents.lines() {
+            let mut chars = line.chars();
+            match chars.next() {
+                Some(SEARCH_PREFIX) => histories.search.add(chars.as_str().to_string()),
+                Some(COMMAND_PREFIX) => histories.command.add(chars.as_str().to_string()),
+                _ => {}
+            }
+        }
+        histories
+    }
+
+    fn serialize(&self) -> String {
+        let search = self
+            .search
+            .entries
+            .iter()
+            .map(|entry| format!("{SEARCH_PREFIX}{entry}\n"));
+        let command = self
+            .command
+            .entries
+            .iter()
+            .map(|entry| format!("{COMMAND_PREFIX}{entry}\n"));
+
+        search.chain(command).collect()
+    }
+}
+
+#[test]
+fn browse_history() {
+    let mut history = History::default();
+    for entry in ["set ic", "s/a/b/", "set scs", "s/a/b/", ""] {
+        history.add(entry.to_string());
+    }
+
+    assert_eq!(history.previous(""), Some("s/a/b/"));
+    assert_eq!(history.previous(""), Some("set scs"));
+    assert_eq!(history.previous(""), Some("set ic"));
+    assert_eq!(history.previous(""), None);
+    assert_eq!(history.next(), Some("set scs"));
+    assert_eq!(history.next(), Some("s/a/b/"));
+    assert_eq!(history.next(), Some(""));
+    assert_eq!(history.next(), None);
+
+    // Only entries starting with the typed text are offered.
+    assert_eq!(history.previous("se"), Some("set scs"));
+    assert_eq!(history.previous("ignored while browsing"), Some("set ic"));
+    assert_eq!(history.next(), Some("set scs"));
+    assert_eq!(history.next(), Some("se"));
+}
+
+#[test]
+fn persist_histories() {
+    let mut histories = Histories::default();
+    histories.search.add(String::from("\\vfoo(bar)?"));
+    histories.command.add(String::from("%s/a/b/g"));
+    histories.command.add(String::from("q"));
+
+    let parsed = Histories::parse(&histories.serialize());
+    assert_eq!(parsed.search.entries, vec!["\\vfoo(bar)?"]);
+    assert_eq!(parsed.command.entries, vec!["%s/a/b/g", "q"]);
+}
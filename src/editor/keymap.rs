@@ -0,0 +1,337 @@
+use std::fmt::Display;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// A key as mappings match it, with shift folded into the character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        let mut modifiers = event.modifiers;
+        if matches!(event.code, KeyCode::Char(_) | KeyCode::BackTab) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self {
+            code: event.code,
+            modifiers,
+        }
+    }
+}
+
+impl From<Key> for KeyEvent {
+    fn from(key: Key) -> Self {
+        let mut modifiers = key.modifiers;
+        if matches!(key.code, KeyCode::Char(c) if c.is_uppercase()) || key.code == KeyCode::BackTab
+        {
+            modifiers.insert(KeyModifiers::SHIFT);
+        }
+        KeyEvent::new(key.code, modifiers)
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut modifiers = String::new();
+        for (modifier, prefix) in [
+            (KeyModifiers::CONTROL, "C-"),
+            (KeyModifiers::ALT, "A-"),
+            (KeyModifiers::SHIFT, "S-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                modifiers.push_str(prefix);
+            }
+        }
+        let name = match self.code {
+            KeyCode::Char(' ') => String::from("Space"),
+            KeyCode::Char('<') => String::from("lt"),
+            KeyCode::Char(c) if modifiers.is_empty() => return write!(f, "{c}"),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Enter => String::from("CR"),
+            KeyCode::Esc => String::from("Esc"),
+            KeyCode::Tab => String::from("Tab"),
+            KeyCode::BackTab => String::from("S-Tab"),
+            KeyCode::Backspace => String::from("BS"),
+            KeyCode::Delete => String::from("Del"),
+            KeyCode::F(n) => format!("F{n}"),
+            code => format!("{code:?}"),
+        };
+        write!(f, "<{modifiers}{name}>")
+    }
+}
+
+pub fn notation(keys: &[Key]) -> String {
+    keys.iter().map(Key::to_string).collect()
+}
+
+/// Parses keys in Vim's notation, e.g. `dd`, `<C-s>` or `:w<CR>`. `<Leader>` stands
+/// for the keys of `leader`.
+pub fn parse_keys(notation: &str, leader: &str) -> anyhow::Result<Vec<Key>> {
+    let mut keys = Vec::new();
+    let mut rest = notation;
+    while let Some(c) = rest.chars().next() {
+        if c == '<'
+            && let Some(end) = rest.find('>')
+            && end > 1
+        {
+            if rest[1..end].eq_ignore_ascii_case("leader") {
+                keys.extend(parse_keys(leader, "")?);
+            } else {
+                keys.push(parse_special(&rest[1..end])?);
+            }
+            rest = &rest[end + 1..];
+        } else {
+            keys.push(Key {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE,
+            });
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    Ok(keys)
+}
+
+fn parse_special(name: &str) -> anyhow::Result<Key> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name;
+    while let Some((modifier, key)) = rest.split_once('-')
+        && !key.is_empty()
+    {
+        modifiers |= match modifier.to_ascii_uppercase().as_str() {
+            "C" => KeyModifiers::CONTROL,
+            "S" => KeyModifiers::SHIFT,
+            "A" | "M" => KeyModifiers::ALT,
+            _ => anyhow::bail!("Unknown key: <{name}>"),
+        };
+        rest = key;
+    }
+
+    let mut chars = rest.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) if modifiers.contains(KeyModifiers::CONTROL) => {
+            KeyCode::Char(c.to_ascii_lowercase())
+        }
+        (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+            KeyCode::Char(c.to_ascii_uppercase())
+        }
+        (Some(c), None) if modifiers != KeyModifiers::NONE => KeyCode::Char(c),
+        _ => match rest.to_ascii_lowercase().as_str() {
+            "cr" | "enter" | "return" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "bs" => KeyCode::Backspace,
+            "del" => KeyCode::Delete,
+            "space" => KeyCode::Char(' '),
+            "lt" => KeyCode::Char('<'),
+            "bar" => KeyCode::Char('|'),
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            function => match function.strip_prefix('f').map(str::parse) {
+                Some(Ok(n @ 1..=12)) => KeyCode::F(n),
+                _ => anyhow::bail!("Unknown key: <{name}>"),
+            },
+        },
+    };
+    Ok(Key::from(KeyEvent::new(code, modifiers)))
+}
+
+/// The modes mappings are defined for. Visual mode is normal mode with a selection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapMode {
+    Normal,
+    Visual,
+    Insert,
+}
+
+impl MapMode {
+    fn letter(self) -> char {
+        match self {
+            Self::Normal => 'n',
+            Self::Visual => 'v',
+            Self::Insert => 'i',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Keys(Vec<Key>),
+    Command(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    pub lhs: Vec<Key>,
+    pub action: Action,
+    /// Whether the typed keys are mapped again, as with `:map` but not `:noremap`.
+    pub remap: bool,
+}
+
+impl Display for Mapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let remap = if self.remap { "" } else { "* " };
+        match &self.action {
+            Action::Keys(keys) => write!(f, "{} {remap}{}", notation(&self.lhs), notation(keys)),
+            Action::Command(command) => {
+                write!(f, "{} {remap}:{command}<CR>", notation(&self.lhs))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Keymap {
+    normal: Vec<Mapping>,
+    visual: Vec<Mapping>,
+    insert: Vec<Mapping>,
+}
+
+impl Keymap {
+    /// Adds `mapping`, replacing the one with the same keys.
+    pub fn map(&mut self, mode: MapMode, mapping: Mapping) -> anyhow::Result<()> {
+        if mapping.lhs.is_empty() {
+            anyhow::bail!("Mapping of no keys");
+        }
+        let mappings = self.mappings_mut(mode);
+        match mappings.iter_mut().find(|other| other.lhs == mapping.lhs) {
+            Some(other) => *other = mapping,
+            None => mappings.push(mapping),
+        }
+        Ok(())
+    }
+
+    /// return: whether there was a mapping of `lhs`
+    pub fn unmap(&mut self, mode: MapMode, lhs: &[Key]) -> bool {
+        let mappings = self.mappings_mut(mode);
+        let len = mappings.len();
+        mappings.retain(|mapping| mapping.lhs != lhs);
+        mappings.len() != len
+    }
+
+    pub fn longest(&self, mode: MapMode, keys: &[Key]) -> Option<&Mapping> {
+        self.mappings(mode)
+            .iter()
+            .filter(|mapping| keys.starts_with(&mapping.lhs))
+            .max_by_key(|mapping| mapping.lhs.len())
+    }
+
+    pub fn has_longer(&self, mode: MapMode, keys: &[Key]) -> bool {
+        self.mappings(mode)
+            .iter()
+            .any(|mapping| mapping.lhs.len() > keys.len() && mapping.lhs.starts_with(keys))
+    }
+
+    pub fn list(&self, modes: &[MapMode], prefix: &[Key]) -> Vec<String> {
+        let mut listed = modes
+            .iter()
+            .flat_map(|mode| {
+                self.mappings(*mode)
+                    .iter()
+                    .filter(|mapping| mapping.lhs.starts_with(prefix))
+                    .map(|mapping| format!("{} {mapping}", mode.letter()))
+            })
+            .collect::<Vec<_>>();
+        listed.sort();
+        listed
+    }
+
+    fn mappings(&self, mode: MapMode) -> &[Mapping] {
+        match mode {
+            MapMode::Normal => &self.normal,
+            MapMode::Visual => &self.visual,
+            MapMode::Insert => &self.insert,
+        }
+    }
+
+    fn mappings_mut(&mut self, mode: MapMode) -> &mut Vec<Mapping> {
+        match mode {
+            MapMode::Normal => &mut self.normal,
+            MapMode::Visual => &mut self.visual,
+            MapMode::Insert => &mut self.insert,
+        }
+    }
+}
+
+#[test]
+fn parse_key_notation() {
+    let key = |code, modifiers| Key { code, modifiers };
+    assert_eq!(
+        parse_keys(":w<CR>", "").unwrap(),
+        [
+            key(KeyCode::Char(':'), KeyModifiers::NONE),
+            key(KeyCode::Char('w'), KeyModifiers::NONE),
+            key(KeyCode::Enter, KeyModifiers::NONE),
+        ]
+    );
+    assert_eq!(
+        parse_keys("<C-S><lt><S-Tab><A-x>", "").unwrap(),
+        [
+            key(KeyCode::Char('s'), KeyModifiers::CONTROL),
+            key(KeyCode::Char('<'), KeyModifiers::NONE),
+            key(KeyCode::BackTab, KeyModifiers::NONE),
+            key(KeyCode::Char('x'), KeyModifiers::ALT),
+        ]
+    );
+    assert_eq!(parse_keys("a<>", "").unwrap().len(), 3);
+    assert_eq!(
+        KeyEvent::from(parse_keys("G", "").unwrap()[0]),
+        KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT)
+    );
+    assert!(parse_keys("<Foo>", "").is_err());
+    assert!(parse_keys("<X-a>", "").is_err());
+    assert_eq!(
+        parse_keys("<Leader>w", "<Space>").unwrap(),
+        parse_keys(" w", "").unwrap()
+    );
+    assert_eq!(
+        notation(&parse_keys("<C-s><lt>a<S-Tab><Space><CR>", "").unwrap()),
+        "<C-s><lt>a<S-Tab><Space><CR>"
+    );
+}
+
+#[test]
+fn look_up_mappings() {
+    let keys = |notation| parse_keys(notation, "").unwrap();
+    let mapping = |lhs, rhs| Mapping {
+        lhs: keys(lhs),
+        action: Action::Keys(keys(rhs)),
+        remap: false,
+    };
+    let mut keymap = Keymap::default();
+    keymap.map(MapMode::Normal, mapping("g", "x")).unwrap();
+    keymap.map(MapMode::Normal, mapping("gb", "dw")).unwrap();
+    keymap.map(MapMode::Normal, mapping("gb", "db")).unwrap();
+    keymap.map(MapMode::Insert, mapping("jk", "<Esc>")).unwrap();
+    assert!(keymap.map(MapMode::Normal, mapping("", "x")).is_err());
+
+    assert!(keymap.has_longer(MapMode::Normal, &keys("g")));
+    assert!(!keymap.has_longer(MapMode::Normal, &keys("gb")));
+    assert_eq!(
+        keymap.longest(MapMode::Normal, &keys("gbw")),
+        Some(&mapping("gb", "db"))
+    );
+    assert_eq!(
+        keymap.longest(MapMode::Normal, &keys("gx")),
+        Some(&mapping("g", "x"))
+    );
+    assert_eq!(keymap.longest(MapMode::Visual, &keys("g")), None);
+    assert_eq!(
+        keymap.list(&[MapMode::Normal, MapMode::Insert], &[]),
+        ["i jk * <Esc>", "n g * x", "n gb * db"]
+    );
+    assert_eq!(keymap.list(&[MapMode::Normal], &keys("gb")), ["n gb * db"]);
+
+    assert!(keymap.unmap(MapMode::Normal, &keys("g")));
+    assert!(!keymap.unmap(MapMode::Normal, &keys("g")));
+    assert!(keymap.has_longer(MapMode::Normal, &keys("g")));
+}
@@ -1,12 +1,13 @@
 use std::time::{Duration, Instant};
 
-use crate::{editor::ui::UiComponent, terminal};
+use crate::{editor::ui::UiComponent, terminal::Backend};
 
 const MESSAGE_DURATION: Duration = Duration::from_secs(5);
 
 #[derive(Default)]
 pub struct MessageBar {
     message: Message,
+    mode: Option<&'static str>,
     render: bool,
     clear_after_expiry: bool,
 }
@@ -27,7 +28,7 @@ impl Default for Message {
 
 impl Message {
     fn is_expired(&self) -> bool {
-        self.instant.elapsed() > MESSAGE_DURATION
+        self.instant.elapsed() >= MESSAGE_DURATION
     }
 }
 
@@ -40,6 +41,28 @@ impl MessageBar {
         self.clear_after_expiry = false;
         self.set_render(true);
     }
+
+    /// Switching modes clears the message like it does in Vim.
+    pub fn set_mode(&mut self, mode: Option<&'static str>) {
+        if self.mode != mode {
+            if mode.is_some() {
+                self.message.text.clear();
+            }
+            self.mode = mode;
+            self.set_render(true);
+        }
+    }
+
+    /// When the shown message has to be cleared, `None` once it was.
+    pub fn expires_at(&self) -> Option<Instant> {
+        (!self.clear_after_expiry).then(|| self.message.instant + MESSAGE_DURATION)
+    }
+
+    /// Clears the expired message on the next render, even when a prompt hides the bar now.
+    pub fn expire(&mut self) {
+        self.clear_after_expiry = true;
+        self.set_render(true);
+    }
 }
 
 impl UiComponent for MessageBar {
@@ -53,7 +76,7 @@ impl UiComponent for MessageBar {
 
     fn set_size(&mut self, _width: u16, _height: u16) {}
 
-    fn draw(&mut self, y: u16) -> anyhow::Result<()> {
+    fn draw(&mut self, backend: &mut dyn Backend, y: u16) -> anyhow::Result<()> {
         if self.message.is_expired() {
             self.clear_after_expiry = true;
         }
@@ -63,6 +86,11 @@ impl UiComponent for MessageBar {
             &self.message.text
         };
 
-        terminal::print_at(0, y, true, message)
+        match self.mode {
+            Some(mode) if message.is_empty() => {
+                backend.print_at(0, y, true, &format!("-- {mode} --"))
+            }
+            _ => backend.print_at(0, y, true, message),
+        }
     }
 }
@@ -0,0 +1,394 @@
+use std::{borrow::Cow, fmt::Display};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    Global,
+    /// Every buffer has its own value, new buffers start with the global one.
+    Buffer,
+    /// Every window has its own value, new windows start with the global one.
+    Window,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Bool,
+    Int { min: usize, max: usize },
+    Enum(&'static [&'static str]),
+    String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Bool(bool),
+    Int(usize),
+    String(Cow<'static, str>),
+}
+
+/// Every option, in the order of `OPTIONS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionId {
+    Expandtab,
+    Fileformat,
+    Hlsearch,
+    Ignorecase,
+    Mapleader,
+    Mouse,
+    Number,
+    Readonly,
+    Scrolloff,
+    Showmode,
+    Smartcase,
+    Statusline,
+    Tabstop,
+    Theme,
+    Wrap,
+}
+
+pub struct OptionInfo {
+    pub id: OptionId,
+    pub name: &'static str,
+    /// The abbreviation, the same as `name` if there is none.
+    pub short: &'static str,
+    pub kind: Kind,
+    pub scope: Scope,
+    pub default: Value,
+}
+
+/// The options known to `:set`, sorted by name.
+pub const OPTIONS: &[OptionInfo] = &[
+    OptionInfo {
+        id: OptionId::Expandtab,
+        name: "expandtab",
+        short: "et",
+        kind: Kind::Bool,
+        scope: Scope::Buffer,
+        default: Value::Bool(false),
+    },
+    OptionInfo {
+        id: OptionId::Fileformat,
+        name: "fileformat",
+        short: "ff",
+        kind: Kind::Enum(&["unix", "dos"]),
+        scope: Scope::Buffer,
+        default: Value::String(Cow::Borrowed("unix")),
+    },
+    OptionInfo {
+        id: OptionId::Hlsearch,
+        name: "hlsearch",
+        short: "hls",
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        default: Value::Bool(true),
+    },
+    OptionInfo {
+        id: OptionId::Ignorecase,
+        name: "ignorecase",
+        short: "ic",
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        default: Value::Bool(false),
+    },
+    OptionInfo {
+        id: OptionId::Mapleader,
+        name: "mapleader",
+        short: "mapleader",
+        kind: Kind::String,
+        scope: Scope::Global,
+        default: Value::String(Cow::Borrowed("\\")),
+    },
+    OptionInfo {
+        id: OptionId::Mouse,
+        name: "mouse",
+        short: "mouse",
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        default: Value::Bool(false),
+    },
+    OptionInfo {
+        id: OptionId::Number,
+        name: "number",
+        short: "nu",
+        kind: Kind::Bool,
+        scope: Scope::Window,
+        default: Value::Bool(false),
+    },
+    OptionInfo {
+        id: OptionId::Readonly,
+        name: "readonly",
+        short: "ro",
+        kind: Kind::Bool,
+        scope: Scope::Buffer,
+        default: Value::Bool(false),
+    },
+    OptionInfo {
+        id: OptionId::Scrolloff,
+        name: "scrolloff",
+        short: "so",
+        kind: Kind::Int { min: 0, max: 999 },
+        scope: Scope::Window,
+        default: Value::Int(0),
+    },
+    OptionInfo {
+        id: OptionId::Showmode,
+        name: "showmode",
+        short: "smd",
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        default: Value::Bool(true),
+    },
+    OptionInfo {
+        id: OptionId::Smartcase,
+        name: "smartcase",
+        short: "scs",
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        default: Value::Bool(false),
+    },
+    OptionInfo {
+        id: OptionId::Statusline,
+        name: "statusline",
+        short: "stl",
+        kind: Kind::String,
+        scope: Scope::Global,
+        default: Value::String(Cow::Borrowed("%t | %y - %L lines %m%=%{search} %l/%L")),
+    },
+    OptionInfo {
+        id: OptionId::Tabstop,
+        name: "tabstop",
+        short: "ts",
+        kind: Kind::Int { min: 1, max: 32 },
+        scope: Scope::Buffer,
+        default: Value::Int(8),
+    },
+    OptionInfo {
+        id: OptionId::Theme,
+        name: "theme",
+        short: "theme",
+        kind: Kind::String,
+        scope: Scope::Global,
+        default: Value::String(Cow::Borrowed("default")),
+    },
+    OptionInfo {
+        id: OptionId::Wrap,
+        name: "wrap",
+        short: "wrap",
+        kind: Kind::Bool,
+        scope: Scope::Window,
+        default: Value::Bool(false),
+    },
+];
+
+impl OptionId {
+    pub fn info(self) -> &'static OptionInfo {
+        &OPTIONS[self as usize]
+    }
+}
+
+impl OptionInfo {
+    pub fn find(name: &str) -> Option<&'static Self> {
+        OPTIONS
+            .iter()
+            .find(|info| info.name == name || info.short == name)
+    }
+
+    pub fn parse(&self, value: &str) -> anyhow::Result<Value> {
+        let value =
+            match self.kind {
+                Kind::Bool => anyhow::bail!("Invalid argument: {}={value}", self.name),
+                Kind::Int { .. } => Value::Int(value.parse().map_err(|_| {
+                    anyhow::anyhow!("Number required after =: {}={value}", self.name)
+                })?),
+                Kind::Enum(_) | Kind::String => Value::String(Cow::Owned(value.to_string())),
+            };
+        self.check(value)
+    }
+
+    pub fn check(&self, value: Value) -> anyhow::Result<Value> {
+        match (self.kind, &value) {
+            (Kind::Bool, Value::Bool(_)) => {}
+            (Kind::Int { min, max }, Value::Int(n)) => {
+                if !(min..=max).contains(n) {
+                    anyhow::bail!("{} must be between {min} and {max}, got {n}", self.name);
+                }
+            }
+            (Kind::Enum(names), Value::String(name)) => {
+                if !names.contains(&name.as_ref()) {
+                    anyhow::bail!(
+                        "Invalid {}: {name}, expected one of {}",
+                        self.name,
+                        names.join(", ")
+                    );
+                }
+            }
+            (Kind::String, Value::String(_)) => {}
+            _ => anyhow::bail!("Invalid value for {}: {value:?}", self.name),
+        }
+        Ok(value)
+    }
+
+    /// The option as `:set opt?` shows it: `name=value`, or `name` / `noname`.
+    pub fn show(&self, value: &Value) -> String {
+        match value {
+            Value::Bool(true) => self.name.to_string(),
+            Value::Bool(false) => format!("no{}", self.name),
+            value => format!("{}={value}", self.name),
+        }
+    }
+}
+
+impl Value {
+    pub fn as_bool(&self) -> bool {
+        matches!(self, Self::Bool(true))
+    }
+
+    pub fn as_int(&self) -> usize {
+        match self {
+            Self::Int(value) => *value,
+            _ => 0,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::String(value) => value,
+            _ => "",
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bool(value) => write!(f, "{value}"),
+            Self::Int(value) => write!(f, "{value}"),
+            Self::String(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// A value for every option. The global values are kept in one of these, and
+/// every buffer and window keeps its own of which only the options of its scope
+/// are read.
+#[derive(Debug, Clone)]
+pub struct Options {
+    values: Vec<Value>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            values: OPTIONS.iter().map(|info| info.default.clone()).collect(),
+        }
+    }
+}
+
+impl Options {
+    pub fn get(&self, id: OptionId) -> &Value {
+        &self.values[id as usize]
+    }
+
+    /// `value` has to be of the option's kind, see `OptionInfo::check`.
+    pub fn set(&mut self, id: OptionId, value: Value) {
+        self.values[id as usize] = value;
+    }
+
+    pub fn bool(&self, id: OptionId) -> bool {
+        self.get(id).as_bool()
+    }
+
+    pub fn int(&self, id: OptionId) -> usize {
+        self.get(id).as_int()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SetAction<'a> {
+    /// `opt`: switch a boolean option on, show any other option.
+    Set,
+    Unset,
+    Toggle,
+    Show,
+    Reset,
+    Assign(&'a str),
+}
+
+pub fn parse_set_arg(arg: &str) -> anyhow::Result<(&'static OptionInfo, SetAction<'_>)> {
+    let find = |name: &str| {
+        OptionInfo::find(name).ok_or_else(|| anyhow::anyhow!("Unknown option: {name}"))
+    };
+    if let Some(idx) = arg.find(['=', ':']) {
+        return Ok((find(&arg[..idx])?, SetAction::Assign(&arg[idx + 1..])));
+    }
+    if let Some(name) = arg.strip_suffix('?') {
+        return Ok((find(name)?, SetAction::Show));
+    }
+    if let Some(name) = arg.strip_suffix('&') {
+        return Ok((find(name)?, SetAction::Reset));
+    }
+    if let Some(name) = arg.strip_suffix('!') {
+        return Ok((find(name)?, SetAction::Toggle));
+    }
+    if let Some(info) = OptionInfo::find(arg) {
+        return Ok((info, SetAction::Set));
+    }
+    let (name, action) = match (arg.strip_prefix("no"), arg.strip_prefix("inv")) {
+        (Some(name), _) => (name, SetAction::Unset),
+        (_, Some(name)) => (name, SetAction::Toggle),
+        _ => (arg, SetAction::Set),
+    };
+    let info = find(name)?;
+    if info.kind != Kind::Bool {
+        anyhow::bail!("Invalid argument: {arg}");
+    }
+    Ok((info, action))
+}
+
+#[test]
+fn options_are_in_id_order() {
+    assert!(
+        OPTIONS
+            .iter()
+            .enumerate()
+            .all(|(idx, info)| info.id as usize == idx)
+    );
+    assert!(OPTIONS.windows(2).all(|pair| pair[0].name < pair[1].name));
+    assert!(
+        OPTIONS
+            .iter()
+            .all(|info| info.check(info.default.clone()).is_ok())
+    );
+}
+
+#[test]
+fn parse_set_arguments() {
+    let parse = |arg| parse_set_arg(arg).map(|(info, action)| (info.id, action));
+
+    assert_eq!(parse("ic").unwrap(), (OptionId::Ignorecase, SetAction::Set));
+    assert_eq!(parse("number").unwrap(), (OptionId::Number, SetAction::Set));
+    assert_eq!(parse("nonu").unwrap(), (OptionId::Number, SetAction::Unset));
+    assert_eq!(
+        parse("invwrap").unwrap(),
+        (OptionId::Wrap, SetAction::Toggle)
+    );
+    assert_eq!(parse("wrap!").unwrap(), (OptionId::Wrap, SetAction::Toggle));
+    assert_eq!(parse("ts?").unwrap(), (OptionId::Tabstop, SetAction::Show));
+    assert_eq!(
+        parse("so&").unwrap(),
+        (OptionId::Scrolloff, SetAction::Reset)
+    );
+    assert_eq!(
+        parse("ts=4").unwrap(),
+        (OptionId::Tabstop, SetAction::Assign("4"))
+    );
+    assert!(parse("tabsop").is_err());
+    assert!(parse("nots").is_err());
+
+    let tabstop = OptionId::Tabstop.info();
+    assert_eq!(tabstop.parse("4").unwrap(), Value::Int(4));
+    assert!(tabstop.parse("0").is_err());
+    assert!(tabstop.parse("four").is_err());
+    assert!(OptionId::Fileformat.info().parse("dos").is_ok());
+    assert!(OptionId::Fileformat.info().parse("mac").is_err());
+    assert_eq!(tabstop.show(&Value::Int(4)), "tabstop=4");
+    assert_eq!(OptionId::Wrap.info().show(&Value::Bool(false)), "nowrap");
+}
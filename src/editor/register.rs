@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+pub struct Registers {
+    registers: HashMap<char, String>,
+}
+
+impl Registers {
+    pub fn get(&self, name: char) -> Option<&str> {
+        self.registers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Writing to an uppercase register appends to its lowercase one.
+    pub fn set(&mut self, name: char, text: String) {
+        if name.is_ascii_uppercase() {
+            self.registers
+                .entry(name.to_ascii_lowercase())
+                .or_default()
+                .push_str(&text);
+        } else {
+            self.registers.insert(name, text);
+        }
+    }
+}
+
+#[test]
+fn append_to_register() {
+    let mut registers = Registers::default();
+    registers.set('a', String::from("foo"));
+    registers.set('A', String::from("bar"));
+    registers.set('"', String::from("baz"));
+
+    assert_eq!(registers.get('a'), Some("foobar"));
+    assert_eq!(registers.get('A'), Some("foobar"));
+    assert_eq!(registers.get('"'), Some("baz"));
+    assert_eq!(registers.get('b'), None);
+}
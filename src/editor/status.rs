@@ -1,13 +1,273 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
 use crate::{
-    editor::{DocumentStatus, ui::UiComponent},
-    terminal,
+    editor::{DocumentStatus, options::OptionId, ui::UiComponent},
+    terminal::{Backend, Element},
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Mode,
+    /// `%f`: the path as given, relative to the working directory if below it.
+    Path,
+    AbsolutePath,
+    FileName,
+    Modified,
+    Readonly,
+    FileType,
+    Encoding,
+    FileFormat,
+    Line,
+    Column,
+    Lines,
+    Percent,
+    SearchCount,
+    Branch,
+    Selection,
+}
+
+const NAMED_FIELDS: &[(&str, Field)] = &[
+    ("branch", Field::Branch),
+    ("encoding", Field::Encoding),
+    ("fileformat", Field::FileFormat),
+    ("mode", Field::Mode),
+    ("search", Field::SearchCount),
+    ("selection", Field::Selection),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Item {
+    Text(String),
+    Field(Field),
+    /// `%=`: the items after it are aligned to the right.
+    Align,
+    /// `%<`: where the line is cut when it is too wide.
+    Truncate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusFormat {
+    items: Vec<Item>,
+}
+
+impl Default for StatusFormat {
+    fn default() -> Self {
+        OptionId::Statusline
+            .info()
+            .default
+            .as_str()
+            .parse()
+            .unwrap_or(Self { items: Vec::new() })
+    }
+}
+
+impl FromStr for StatusFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut items = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text.push(c);
+                continue;
+            }
+            let item = match chars.next() {
+                Some('%') => {
+                    text.push('%');
+                    continue;
+                }
+                Some('=') => Item::Align,
+                Some('<') => Item::Truncate,
+                Some('f') => Item::Field(Field::Path),
+                Some('F') => Item::Field(Field::AbsolutePath),
+                Some('t') => Item::Field(Field::FileName),
+                Some('m') => Item::Field(Field::Modified),
+                Some('r') => Item::Field(Field::Readonly),
+                Some('y') => Item::Field(Field::FileType),
+                Some('l') => Item::Field(Field::Line),
+                Some('c') => Item::Field(Field::Column),
+                Some('L') => Item::Field(Field::Lines),
+                Some('p') => Item::Field(Field::Percent),
+                Some('{') => {
+                    let rest = chars.as_str();
+                    let Some((name, after)) = rest.split_once('}') else {
+                        anyhow::bail!("Missing }} after %{{{rest}");
+                    };
+                    let Some((_, field)) = NAMED_FIELDS.iter().find(|(other, _)| *other == name)
+                    else {
+                        anyhow::bail!("Unknown statusline item: %{{{name}}}");
+                    };
+                    chars = after.chars();
+                    Item::Field(*field)
+                }
+                Some(other) => anyhow::bail!("Unknown statusline item: %{other}"),
+                None => anyhow::bail!("Missing statusline item after %"),
+            };
+            if !text.is_empty() {
+                items.push(Item::Text(std::mem::take(&mut text)));
+            }
+            items.push(item);
+        }
+        if !text.is_empty() {
+            items.push(Item::Text(text));
+        }
+        Ok(Self { items })
+    }
+}
+
+impl StatusFormat {
+    /// The status line for `width` columns. When it is too wide the text after
+    /// `%<`, or after the start without one, is cut and marked with `<`.
+    fn render(&self, status: &DocumentStatus, branch: Option<&str>, width: usize) -> String {
+        let mut left = String::new();
+        let mut right = String::new();
+        let mut aligned = false;
+        let mut truncate_at = None;
+        for item in &self.items {
+            let side = if aligned { &mut right } else { &mut left };
+            match item {
+                Item::Text(text) => side.push_str(text),
+                Item::Field(field) => side.push_str(&field_text(*field, status, branch)),
+                Item::Align => aligned = true,
+                Item::Truncate => truncate_at = Some((aligned, side.len())),
+            }
+        }
+
+        let padding = width.saturating_sub(left.width() + right.width());
+        let truncate_at = match truncate_at {
+            Some((true, idx)) => left.len() + padding + idx,
+            Some((false, idx)) => idx,
+            None => 0,
+        };
+        let line = format!("{left}{}{right}", " ".repeat(padding));
+        if line.width() <= width {
+            return line;
+        }
+        truncate(&line, truncate_at, width)
+    }
+}
+
+fn field_text(field: Field, status: &DocumentStatus, branch: Option<&str>) -> String {
+    let path = status.path.as_deref();
+    match field {
+        Field::Mode => status.mode.to_string(),
+        Field::Path => path.map_or_else(
+            || status.file_info.file.clone(),
+            |path| relative_path(path).display().to_string(),
+        ),
+        Field::AbsolutePath => path.map_or_else(
+            || status.file_info.file.clone(),
+            |path| {
+                std::path::absolute(path)
+                    .unwrap_or_else(|_| path.to_path_buf())
+                    .display()
+                    .to_string()
+            },
+        ),
+        Field::FileName => status.file_info.file.clone(),
+        Field::Modified => status.modified_indicator().to_string(),
+        Field::Readonly => String::from(if status.readonly { "[RO]" } else { "" }),
+        Field::FileType => status.file_info.file_ty.to_string(),
+        // Files are only read if they are valid UTF-8.
+        Field::Encoding => String::from("utf-8"),
+        Field::FileFormat => status.fileformat.clone(),
+        Field::Line => status.current_line.saturating_add(1).to_string(),
+        Field::Column => status.current_column.saturating_add(1).to_string(),
+        Field::Lines => status.total_lines.to_string(),
+        Field::Percent => {
+            let percent = status.current_line.saturating_add(1) * 100 / status.total_lines.max(1);
+            format!("{}%", percent.min(100))
+        }
+        Field::SearchCount => status.search_indicator(),
+        Field::Branch => branch.unwrap_or_default().to_string(),
+        Field::Selection => match status.selection {
+            Some((1, graphemes)) => format!("{graphemes} chars"),
+            Some((lines, _)) => format!("{lines} lines"),
+            None => String::new(),
+        },
+    }
+}
+
+/// `path` relative to the working directory, unchanged if it is not below it.
+fn relative_path(path: &Path) -> PathBuf {
+    std::env::current_dir()
+        .ok()
+        .and_then(|dir| path.strip_prefix(dir).ok())
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
+/// Cuts graphemes after the byte index `at` of `line` until it fits into
+/// `width` columns with a `<` in their place, then cuts the end if it is
+/// still too wide.
+fn truncate(line: &str, at: usize, width: usize) -> String {
+    let mut excess = (line.width() + 1).saturating_sub(width);
+    let (kept, rest) = line.split_at(at.min(line.len()));
+    // Without enough text after `at` the line is cut from the start.
+    let (kept, rest) = if rest.width() < excess {
+        ("", line)
+    } else {
+        (kept, rest)
+    };
+    let mut rest = rest.graphemes(true);
+    while excess > 0 {
+        let Some(grapheme) = rest.next() else {
+            break;
+        };
+        excess = excess.saturating_sub(grapheme.width());
+    }
+    let line = format!("{kept}<{}", rest.as_str());
+    let mut used = 0;
+    line.graphemes(true)
+        .take_while(|grapheme| {
+            used += grapheme.width();
+            used <= width
+        })
+        .collect()
+}
+
+/// The branch checked out in the git repository `dir` is in, or the start of
+/// the commit hash when none is.
+fn git_branch(dir: &Path) -> Option<String> {
+    let git = dir
+        .ancestors()
+        .map(|dir| dir.join(".git"))
+        .find(|git| git.exists())?;
+    // Worktrees and submodules have a file pointing at the git directory.
+    let git = if git.is_file() {
+        let contents = std::fs::read_to_string(&git).ok()?;
+        let target = PathBuf::from(contents.strip_prefix("gitdir:")?.trim());
+        git.parent()?.join(target)
+    } else {
+        git
+    };
+    let head = std::fs::read_to_string(git.join("HEAD")).ok()?;
+    let head = head.trim();
+    match head.strip_prefix("ref: ") {
+        Some(reference) => Some(
+            reference
+                .strip_prefix("refs/heads/")
+                .unwrap_or(reference)
+                .to_string(),
+        ),
+        None => Some(head.chars().take(7).collect()),
+    }
+}
+
 #[derive(Default)]
 pub struct StatusBar {
     status: DocumentStatus,
+    format: StatusFormat,
+    /// The git branch of the file, looked up when another file is shown.
+    branch: Option<String>,
     render: bool,
     width: u16,
 }
@@ -15,10 +275,34 @@ pub struct StatusBar {
 impl StatusBar {
     pub fn update_status(&mut self, status: DocumentStatus) {
         if self.status != status {
+            if self.status.path != status.path {
+                self.branch = Self::find_branch(status.path.as_deref());
+            }
             self.status = status;
             self.render = true;
         }
     }
+
+    pub fn set_format(&mut self, format: StatusFormat) {
+        self.format = format;
+        self.render = true;
+    }
+
+    pub fn refresh_branch(&mut self) {
+        let branch = Self::find_branch(self.status.path.as_deref());
+        if branch != self.branch {
+            self.branch = branch;
+            self.render = true;
+        }
+    }
+
+    fn find_branch(path: Option<&Path>) -> Option<String> {
+        let dir = match path {
+            Some(path) => std::path::absolute(path).ok()?.parent()?.to_path_buf(),
+            None => std::env::current_dir().ok()?,
+        };
+        git_branch(&dir)
+    }
 }
 
 impl UiComponent for StatusBar {
@@ -34,20 +318,67 @@ impl UiComponent for StatusBar {
         self.width = width;
     }
 
-    fn draw(&mut self, y: u16) -> anyhow::Result<()> {
-        let modified_indicator = self.status.modified_indicator();
-        let line_count = self.status.line_count();
+    fn draw(&mut self, backend: &mut dyn Backend, y: u16) -> anyhow::Result<()> {
+        let status = self
+            .format
+            .render(&self.status, self.branch.as_deref(), self.width as usize);
+        backend.print_styled_at(0, y, true, &status, Element::StatusBar)
+    }
+}
 
-        let beginning = format!(
-            "{} - {line_count} {modified_indicator}",
-            self.status.file_info
-        );
-        let position_indicator = self.status.position_indicator();
-        let reminder_len = (self.width as usize).saturating_sub(beginning.width());
+#[test]
+fn parse_status_format() {
+    let format = "%f%m %%%=%{mode} %l:%c".parse::<StatusFormat>().unwrap();
+    assert_eq!(
+        format.items,
+        [
+            Item::Field(Field::Path),
+            Item::Field(Field::Modified),
+            Item::Text(String::from(" %")),
+            Item::Align,
+            Item::Field(Field::Mode),
+            Item::Text(String::from(" ")),
+            Item::Field(Field::Line),
+            Item::Text(String::from(":")),
+            Item::Field(Field::Column),
+        ]
+    );
+    assert!("%x".parse::<StatusFormat>().is_err());
+    assert!("%{colour}".parse::<StatusFormat>().is_err());
+    assert!("%{mode".parse::<StatusFormat>().is_err());
+    assert!("50%".parse::<StatusFormat>().is_err());
+}
 
-        let status = format!("{beginning}{position_indicator:>reminder_len$}");
+#[test]
+fn render_status_line() {
+    let status = DocumentStatus {
+        total_lines: 40,
+        current_line: 9,
+        current_column: 4,
+        modified: true,
+        path: Some(PathBuf::from("/tmp/notes/todo.txt")),
+        fileformat: String::from("unix"),
+        selection: Some((1, 3)),
+        mode: "INSERT",
+        file_info: Path::new("/tmp/notes/todo.txt").into(),
+        ..Default::default()
+    };
+    let render = |format: &str, width| {
+        format
+            .parse::<StatusFormat>()
+            .unwrap()
+            .render(&status, Some("main"), width)
+    };
 
-        // status.truncate(self.width as usize);
-        terminal::print_inverted_at(0, y, true, status)
-    }
+    assert_eq!(
+        render("%t%m%=%l:%c %p", 32),
+        "todo.txt(modified)      10:5 25%"
+    );
+    assert_eq!(
+        render("%{mode} %{branch} %{selection} %{fileformat}", 40).trim_end(),
+        "INSERT main 3 chars unix"
+    );
+    assert_eq!(render("%F%=%L", 16), "<otes/todo.txt40");
+    assert_eq!(render("%L %<%F", 16), "40 <tes/todo.txt");
+    assert_eq!(render("%=%F", 10), "</todo.txt");
 }
@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timer {
+    MessageExpiry,
+    FileCheck,
+    MappingTimeout,
+}
+
+struct Deadline {
+    timer: Timer,
+    at: Instant,
+    /// Rescheduled after firing when set.
+    every: Option<Duration>,
+}
+
+#[derive(Default)]
+pub struct Timers {
+    deadlines: Vec<Deadline>,
+}
+
+impl Timers {
+    /// Fires `timer` once at `at`, replacing an earlier schedule of it.
+    pub fn schedule_at(&mut self, timer: Timer, at: Instant) {
+        self.cancel(timer);
+        self.deadlines.push(Deadline {
+            timer,
+            at,
+            every: None,
+        });
+    }
+
+    /// Fires `timer` every `every`, starting `every` from `now`.
+    pub fn repeat(&mut self, timer: Timer, every: Duration, now: Instant) {
+        self.cancel(timer);
+        self.deadlines.push(Deadline {
+            timer,
+            at: now + every,
+            every: Some(every),
+        });
+    }
+
+    pub fn cancel(&mut self, timer: Timer) {
+        self.deadlines.retain(|deadline| deadline.timer != timer);
+    }
+
+    pub fn timeout(&self, now: Instant) -> Option<Duration> {
+        self.deadlines
+            .iter()
+            .map(|deadline| deadline.at.saturating_duration_since(now))
+            .min()
+    }
+
+    /// Takes the timers due at `now`, repeating ones are scheduled again.
+    pub fn expired(&mut self, now: Instant) -> Vec<Timer> {
+        let mut expired = Vec::new();
+        self.deadlines.retain_mut(|deadline| {
+            if deadline.at > now {
+                return true;
+            }
+            expired.push(deadline.timer);
+            match deadline.every {
+                Some(every) => {
+                    deadline.at = now + every;
+                    true
+                }
+                None => false,
+            }
+        });
+        expired
+    }
+}
+
+#[test]
+fn fire_due_timers() {
+    let start = Instant::now();
+    let second = Duration::from_secs(1);
+    let mut timers = Timers::default();
+    assert_eq!(timers.timeout(start), None);
+
+    timers.repeat(Timer::FileCheck, 2 * second, start);
+    timers.schedule_at(Timer::MessageExpiry, start + 5 * second);
+    assert_eq!(timers.timeout(start), Some(2 * second));
+    assert!(timers.expired(start + second).is_empty());
+
+    assert_eq!(timers.expired(start + 2 * second), [Timer::FileCheck]);
+    assert_eq!(timers.timeout(start + 2 * second), Some(2 * second));
+    assert_eq!(
+        timers.expired(start + 6 * second),
+        [Timer::FileCheck, Timer::MessageExpiry]
+    );
+    assert_eq!(timers.timeout(start + 6 * second), Some(2 * second));
+
+    timers.cancel(Timer::FileCheck);
+    assert_eq!(timers.timeout(start), None);
+}
@@ -1,4 +1,4 @@
-use crate::editor::Size;
+use crate::{editor::Size, terminal::Backend};
 
 pub trait UiComponent {
     fn set_render(&mut self, render: bool);
@@ -12,14 +12,14 @@ pub trait UiComponent {
         self.set_render(true);
     }
 
-    fn draw(&mut self, y: u16) -> anyhow::Result<()>;
+    fn draw(&mut self, backend: &mut dyn Backend, y: u16) -> anyhow::Result<()>;
 
-    fn render(&mut self, y: u16) {
+    fn render(&mut self, backend: &mut dyn Backend, y: u16) {
         if !self.needs_render() {
             return;
         }
 
-        match self.draw(y) {
+        match self.draw(backend, y) {
             Ok(()) => self.set_render(false),
             Err(_err) => {
                 #[cfg(debug_assertions)]
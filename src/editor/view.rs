@@ -1,45 +1,141 @@
-use std::{fmt::Display, path::PathBuf};
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
     editor::{
-        DocumentStatus, FileInfo, Size,
-        event::Direction,
+        DocumentStatus, FileInfo, FileType, Size,
+        event::{CharSearch, Direction, Motion, Operator, ScreenLine},
+        ex::Substitute,
+        options::{OptionId, Options, Scope, Value},
         ui::UiComponent,
         view::{
             buffer::Buffer,
             cursor::{Cursor, Location},
             highlighter::Highlighter,
-            line::Line,
+            line::CharClass,
+            marks::{CONTEXT_MARK, Jump, JumpList},
+            pattern::{Pattern, REGEX_PREFIX, SearchOptions},
         },
     },
-    terminal::{self, Position},
+    terminal::{Backend, Element, Position},
 };
 
 mod buffer;
 mod cursor;
 pub mod highlighter;
 pub mod line;
+mod marks;
+pub mod pattern;
 
 const NAME: &str = env!("CARGO_PKG_NAME");
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Match counts above this are shown as `>99`.
+pub const MAX_SEARCH_COUNT: usize = 99;
+const NUMBER_MIN_DIGITS: usize = 3;
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum SearchDirection {
     #[default]
     Forward,
     Backward,
 }
 
+impl SearchDirection {
+    fn reverse(self) -> Self {
+        match self {
+            Self::Forward => Self::Backward,
+            Self::Backward => Self::Forward,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchHit {
+    Match,
+    WrappedToTop,
+    WrappedToBottom,
+}
+
+/// How an operator treats the text between the cursor and a motion's target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MotionKind {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+#[derive(Debug, Clone)]
+struct ActiveSearch {
+    query: String,
+    pattern: Pattern,
+    direction: SearchDirection,
+    /// Cleared by `:noh` until the next search.
+    highlight: bool,
+    count: Cell<Option<SearchCount>>,
+}
+
+/// A match count, valid while the buffer edits and the cursor stay the same.
+#[derive(Debug, Clone, Copy)]
+struct SearchCount {
+    edits: usize,
+    location: Location,
+    count: (usize, usize),
+}
+
 #[derive(Debug, Clone)]
 struct SearchInfo {
     previous_pos: Cursor,
     previous_offset: Position,
-    query: Line,
+    query: Result<Pattern, regex::Error>,
 }
 
 impl SearchInfo {
-    fn query(&self) -> &str {
-        &self.query[..]
+    fn query(&self) -> Option<&Pattern> {
+        self.query.as_ref().ok()
+    }
+}
+
+pub struct Substitution {
+    pattern: Pattern,
+    replacement: String,
+    lines: Range<usize>,
+    global: bool,
+    /// Where the next match is searched from: (line_index, byte_idx)
+    next: (usize, usize),
+    /// Whether `next` is the end of a non-empty match.
+    after_match: bool,
+    /// The match waiting to be replaced: (location, bytes)
+    candidate: Option<(Location, Range<usize>)>,
+    count: usize,
+    changed_lines: usize,
+    last_changed_line: Option<usize>,
+}
+
+impl Substitution {
+    pub fn replacement(&self) -> &str {
+        &self.replacement
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn changed_lines(&self) -> usize {
+        self.changed_lines
+    }
+
+    fn record(&mut self, line_index: usize) {
+        self.count = self.count.saturating_add(1);
+        if self.last_changed_line != Some(line_index) {
+            self.changed_lines = self.changed_lines.saturating_add(1);
+            self.last_changed_line = Some(line_index);
+        }
     }
 }
 
@@ -51,6 +147,18 @@ pub struct View {
     offset: Position,
     size: Size,
     search_info: Option<SearchInfo>,
+    active_search: Option<ActiveSearch>,
+    global: Options,
+    window: Options,
+    last_find: Option<CharSearch>,
+    jumps: JumpList,
+    /// File marks of files other than the buffer's, moved into it when the file is edited.
+    file_marks: HashMap<char, (PathBuf, Location)>,
+    /// The end of a mouse selection which stays put, the cursor is the other end.
+    selection: Option<Location>,
+    /// Where replace mode typed each grapheme and the one it overwrote, `None`
+    /// past the end of the line, for backspace to put back.
+    replaced: Vec<(Location, Option<String>)>,
 }
 
 impl View {
@@ -58,6 +166,10 @@ impl View {
         self.buffer.has_file()
     }
 
+    pub fn file_path(&self) -> Option<&Path> {
+        self.buffer.file()
+    }
+
     pub fn load(&mut self, path: Option<PathBuf>) -> anyhow::Result<()> {
         if let Some(path) = path {
             self.buffer.load(path)?;
@@ -66,42 +178,164 @@ impl View {
         Ok(())
     }
 
+    pub fn edit(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        let from = self.cursor.location();
+        let previous = self.buffer.file().map(Path::to_path_buf);
+        self.open(path)?;
+        self.jumps.push(Jump {
+            path: previous,
+            location: from,
+        });
+        Ok(())
+    }
+
+    /// Loads `path` into a new buffer, handing the file marks over between the buffers.
+    fn open(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        let mut buffer = Buffer::default();
+        *buffer.options_mut() = self.global.clone();
+        buffer.load(path.clone())?;
+
+        let previous = std::mem::replace(&mut self.buffer, buffer);
+        // The edits of the new buffer are counted from zero again.
+        if let Some(active_search) = &self.active_search {
+            active_search.count.set(None);
+        }
+        if let Some(previous_path) = previous.file() {
+            for (name, location) in previous.marks().file_marks() {
+                self.file_marks
+                    .insert(name, (previous_path.to_path_buf(), location));
+            }
+        }
+        let marks = self.buffer.marks_mut();
+        self.file_marks.retain(|name, (file, location)| {
+            if *file == path {
+                marks.set(*name, *location);
+            }
+            *file != path
+        });
+
+        self.cursor = Cursor::default();
+        self.offset = Position::default();
+        self.search_info = None;
+        self.set_render(true);
+        Ok(())
+    }
+
     pub fn get_status(&self) -> DocumentStatus {
+        let options = self.buffer.options();
         DocumentStatus {
             total_lines: self.buffer.len(),
             current_line: self.cursor.location().line_index,
+            current_column: self.cursor.location().grapheme_index,
             modified: self.buffer.dirty(),
+            readonly: options.bool(OptionId::Readonly),
             file_info: FileInfo::from(self.buffer.file()),
+            path: self.buffer.file().map(Path::to_path_buf),
+            fileformat: options.get(OptionId::Fileformat).to_string(),
+            search_count: self.search_count(),
+            selection: self.selection_size(),
+            ..Default::default()
         }
     }
 
+    fn file_type(&self) -> FileType {
+        FileInfo::from(self.buffer.file()).file_ty
+    }
+
     /// (width, height)
     pub fn size(&self) -> (u16, u16) {
         (self.size.width, self.size.height)
     }
 
+    /// Columns taken by the line numbers, 0 without `number`.
+    fn gutter_width(&self) -> usize {
+        if !self.window.bool(OptionId::Number) {
+            return 0;
+        }
+        let digits = self.buffer.len().max(1).ilog10() as usize + 1;
+        digits.max(NUMBER_MIN_DIGITS).saturating_add(1)
+    }
+
+    fn text_width(&self) -> usize {
+        (self.size.width as usize)
+            .saturating_sub(self.gutter_width())
+            .max(1)
+    }
+
+    fn line_rows(&self, line_index: usize) -> usize {
+        if !self.window.bool(OptionId::Wrap) {
+            return 1;
+        }
+        self.buffer
+            .get(line_index)
+            .map_or(1, |line| line.width(self.tabstop()) / self.text_width() + 1)
+    }
+
+    /// The first line of a view which ends with `last` on its bottom row.
+    fn top_line_for(&self, last: usize) -> usize {
+        let height = self.size.height as usize;
+        let mut rows = 0_usize;
+        let mut top = last;
+        for line_index in (0..=last).rev() {
+            rows = rows.saturating_add(self.line_rows(line_index));
+            if rows > height {
+                break;
+            }
+            top = line_index;
+        }
+        top
+    }
+
+    /// The last line shown, even if only partly.
+    fn bottom_line(&self) -> usize {
+        let height = self.size.height as usize;
+        let last_line = self.buffer.len().saturating_sub(1);
+        let mut rows = 0_usize;
+        let mut line_index = self.offset.row;
+        loop {
+            rows = rows.saturating_add(self.line_rows(line_index));
+            if rows >= height || line_index >= last_line {
+                return line_index;
+            }
+            line_index = line_index.saturating_add(1);
+        }
+    }
+
     fn scroll_vertically(&mut self, to: usize) {
-        let (_, height) = self.size();
-        let offset_changed = if to < self.offset.row {
-            self.offset.row = to;
-            true
-        } else if to >= self.offset.row.saturating_add(height as usize) {
-            self.offset.row = to.saturating_sub(height as usize).saturating_add(1);
-            true
+        let height = self.size.height as usize;
+        let margin = self
+            .window
+            .int(OptionId::Scrolloff)
+            .min(height.saturating_sub(1) / 2);
+        let above = to.saturating_sub(margin);
+        let below = to
+            .saturating_add(margin)
+            .min(self.buffer.len().saturating_sub(1))
+            .max(to);
+
+        let previous = self.offset.row;
+        if above < self.offset.row {
+            self.offset.row = above;
         } else {
-            false
-        };
+            self.offset.row = self.offset.row.max(self.top_line_for(below).min(to));
+        }
 
-        self.set_render(self.render | offset_changed);
+        self.set_render(self.render | (previous != self.offset.row));
     }
 
     fn scroll_horizontally(&mut self, to: usize) {
-        let (width, _) = self.size();
+        if self.window.bool(OptionId::Wrap) {
+            let offset_changed = self.offset.col != 0;
+            self.offset.col = 0;
+            self.set_render(self.render | offset_changed);
+            return;
+        }
+        let width = self.text_width();
         let offset_changed = if to < self.offset.col {
             self.offset.col = to;
             true
-        } else if to >= self.offset.col.saturating_add(width as usize) {
-            self.offset.col = to.saturating_sub(width as usize).saturating_add(1);
+        } else if to >= self.offset.col.saturating_add(width) {
+            self.offset.col = to.saturating_sub(width).saturating_add(1);
             true
         } else {
             false
@@ -118,20 +352,92 @@ impl View {
     }
 
     fn center_text_location(&mut self) {
-        let Size { width, height } = self.size;
-        let Position { col, row } = self.caret_position();
-
-        let horizontal = width.div_ceil(2);
-        let vertical = height.div_ceil(2);
+        let Position { col, .. } = self.caret_position();
+        let horizontal = self.size.width.div_ceil(2);
 
         self.offset.col = col.saturating_sub(horizontal as usize);
-        self.offset.row = row.saturating_sub(vertical as usize);
+        self.reposition(ScreenLine::Middle);
+    }
+
+    pub fn reposition(&mut self, screen_line: ScreenLine) {
+        let height = self.size.height as usize;
+        let Position { row, .. } = self.caret_position();
+
+        self.offset.row = match screen_line {
+            ScreenLine::Top => row,
+            ScreenLine::Middle => row.saturating_sub(height.saturating_sub(1) / 2),
+            ScreenLine::Bottom => row.saturating_sub(height.saturating_sub(1)),
+        };
         self.set_render(true);
     }
 
+    /// return: whether the view or the cursor moved
+    pub fn scroll(&mut self, forward: bool, half: bool, count: usize) -> bool {
+        let height = self.size.height as usize;
+        let last_line = self.buffer.len().saturating_sub(1);
+        let page = if half {
+            height / 2
+        } else {
+            height.saturating_sub(2)
+        };
+        let amount = page.max(1).saturating_mul(count);
+
+        let previous = (self.offset.row, self.cursor.location().line_index);
+        let line_index = self.cursor.location().line_index;
+        if forward {
+            let max_offset = if half {
+                self.buffer.len().saturating_sub(height)
+            } else {
+                last_line
+            };
+            self.offset.row = self.offset.row.saturating_add(amount).min(max_offset);
+            self.cursor.location_mut().line_index = if half {
+                line_index.saturating_add(amount).min(last_line)
+            } else {
+                line_index.max(self.offset.row)
+            };
+        } else {
+            self.offset.row = self.offset.row.saturating_sub(amount);
+            let bottom = self.offset.row.saturating_add(height.saturating_sub(1));
+            self.cursor.location_mut().line_index = if half {
+                line_index.saturating_sub(amount)
+            } else {
+                line_index.min(bottom)
+            };
+        }
+        self.snap_to_valid_grapheme();
+        self.scroll_buffer();
+        self.set_render(true);
+
+        previous != (self.offset.row, self.cursor.location().line_index)
+    }
+
+    /// The buffer line shown at `screen_line`, `nth` lines from the top or bottom.
+    fn screen_line(&self, screen_line: ScreenLine, nth: usize) -> usize {
+        let top = self.offset.row;
+        let bottom = self.bottom_line();
+
+        match screen_line {
+            ScreenLine::Top => top.saturating_add(nth).min(bottom),
+            ScreenLine::Middle => top.saturating_add(bottom.saturating_sub(top) / 2),
+            ScreenLine::Bottom => bottom.saturating_sub(nth).max(top),
+        }
+    }
+
+    /// The first non-blank of `line_index`, where linewise motions put the cursor.
+    fn line_start(&self, line_index: usize) -> Location {
+        Location {
+            grapheme_index: self
+                .buffer
+                .get(line_index)
+                .map_or(0, |line| line.first_non_blank()),
+            line_index,
+        }
+    }
+
     // TODO: maybe (x, y) is better?
-    fn render_line(line: u16, text: impl Display) {
-        let result = terminal::print_at(0, line, true, text);
+    fn render_line(backend: &mut dyn Backend, line: u16, text: &str, element: Element) {
+        let result = backend.print_styled_at(0, line, true, text, element);
         debug_assert!(result.is_ok());
     }
 
@@ -185,60 +491,457 @@ impl View {
         self.cursor.location_mut().grapheme_index = 0;
     }
 
-    pub fn move_to_next_word(&mut self) {
-        let idx = self
-            .buffer
-            .get(self.cursor.location().line_index)
-            .map_or(Some(0), |line| {
-                if self.cursor.location().grapheme_index == line.width() {
-                    None
+    pub fn move_to_matching_bracket(&mut self) {
+        if let Some((_, location)) = self.buffer.matching_bracket(
+            self.cursor.location(),
+            self.file_type(),
+            0..self.buffer.len(),
+        ) {
+            self.record_jump(self.cursor.location());
+            self.cursor = Cursor::new(location);
+            self.scroll_buffer();
+        }
+    }
+
+    /// return: (target, how an operator treats the text up to the target)
+    fn motion_target(&mut self, motion: Motion, count: usize) -> Option<(Location, MotionKind)> {
+        let location = self.cursor.location();
+        let repeat_motion = |step: &dyn Fn(Location) -> Location| {
+            (0..count).fold(location, |location, _| step(location))
+        };
+        let (search, repeat) = match motion {
+            Motion::WordStart { forward, big } => {
+                let target = if forward {
+                    repeat_motion(&|location| self.buffer.next_word_start(location, big))
+                } else {
+                    repeat_motion(&|location| self.buffer.previous_word_start(location, big))
+                };
+                return Some((target, MotionKind::Exclusive));
+            }
+            Motion::WordEnd { forward, big } => {
+                let target = if forward {
+                    repeat_motion(&|location| self.buffer.next_word_end(location, big))
+                } else {
+                    repeat_motion(&|location| self.buffer.previous_word_end(location, big))
+                };
+                return Some((target, MotionKind::Inclusive));
+            }
+            Motion::Paragraph { forward } => {
+                let target = if forward {
+                    repeat_motion(&|location| self.buffer.next_paragraph(location))
                 } else {
-                    Some(
-                        line.search_forward(
-                            " ",
-                            self.cursor.location().grapheme_index.saturating_add(1),
-                        )
-                        .map(|n| n.saturating_add(1))
-                        .unwrap_or(line.width()),
+                    repeat_motion(&|location| self.buffer.previous_paragraph(location))
+                };
+                return Some((target, MotionKind::Exclusive));
+            }
+            Motion::Sentence { forward } => {
+                let target = if forward {
+                    repeat_motion(&|location| self.buffer.next_sentence(location))
+                } else {
+                    repeat_motion(&|location| self.buffer.previous_sentence(location))
+                };
+                return Some((target, MotionKind::Exclusive));
+            }
+            Motion::ScreenLine(screen_line) => {
+                let line_index = self.screen_line(screen_line, count.saturating_sub(1));
+                return Some((self.line_start(line_index), MotionKind::Linewise));
+            }
+            Motion::Line(number) => {
+                let line_index = std::cmp::min(number, self.buffer.len()).saturating_sub(1);
+                return Some((self.line_start(line_index), MotionKind::Linewise));
+            }
+            Motion::Mark { name, linewise } => {
+                let mark = self.buffer.marks().get(name)?;
+                let line_index =
+                    std::cmp::min(mark.line_index, self.buffer.len().saturating_sub(1));
+                return Some(if linewise {
+                    (self.line_start(line_index), MotionKind::Linewise)
+                } else {
+                    let grapheme_index = self.buffer.get(line_index).map_or(0, |line| {
+                        std::cmp::min(mark.grapheme_index, line.grapheme_count())
+                    });
+                    (
+                        Location {
+                            grapheme_index,
+                            line_index,
+                        },
+                        MotionKind::Exclusive,
                     )
-                }
-            });
+                });
+            }
+            Motion::FindChar(search) => {
+                self.last_find = Some(search);
+                (search, false)
+            }
+            Motion::RepeatFind => (self.last_find?, true),
+            Motion::RepeatFindReverse => {
+                let search = self.last_find?;
+                (
+                    CharSearch {
+                        forward: !search.forward,
+                        ..search
+                    },
+                    true,
+                )
+            }
+        };
+        let kind = if search.forward {
+            MotionKind::Inclusive
+        } else {
+            MotionKind::Exclusive
+        };
 
-        if let Some(idx) = idx {
-            self.cursor.location_mut().grapheme_index = idx;
+        let line = self.buffer.get(location.line_index)?;
+        let positions = line.char_positions(search.target);
+        let nth = count.saturating_sub(1);
+        // Repeating `t` / `T` must not get stuck right before the character it stopped at.
+        let skip = usize::from(repeat && search.till && count == 1);
+        let grapheme_index = if search.forward {
+            let start = location.grapheme_index.saturating_add(1 + skip);
+            let found = positions.filter(|idx| *idx >= start).nth(nth)?;
+            if search.till { found - 1 } else { found }
         } else {
-            self.move_down(1);
-            self.move_to_start_of_line();
+            let end = location.grapheme_index.checked_sub(skip)?;
+            let found = positions.rev().filter(|idx| *idx < end).nth(nth)?;
+            if search.till { found + 1 } else { found }
+        };
+
+        Some((
+            Location {
+                grapheme_index,
+                ..location
+            },
+            kind,
+        ))
+    }
+
+    /// return: whether the cursor moved
+    pub fn apply_motion(&mut self, motion: Motion, count: usize) -> bool {
+        let from = self.cursor.location();
+        let Some((target, _)) = self.motion_target(motion, count) else {
+            return false;
+        };
+        if motion.is_jump() && target != from {
+            self.record_jump(from);
+        }
+        self.cursor = Cursor::new(target);
+        self.scroll_buffer();
+        true
+    }
+
+    /// `m{name}`: lowercase marks belong to the buffer, uppercase ones to its file.
+    pub fn set_mark(&mut self, name: char) -> anyhow::Result<()> {
+        if !name.is_ascii_alphabetic() && !matches!(name, CONTEXT_MARK | '`') {
+            anyhow::bail!("Invalid mark name: {name}");
+        }
+        if name.is_ascii_uppercase() {
+            self.file_marks.remove(&name);
+        }
+        self.buffer.marks_mut().set(name, self.cursor.location());
+        Ok(())
+    }
+
+    /// Moves to mark `name`, editing its file first for a file mark of another file.
+    pub fn jump_to_mark(&mut self, name: char, linewise: bool) -> anyhow::Result<()> {
+        if self.buffer.marks().get(name).is_none() {
+            let (path, _) = self
+                .file_marks
+                .get(&name)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("Mark not set"))?;
+            self.ensure_saved()?;
+            self.edit(path)?;
+        }
+        self.apply_motion(Motion::Mark { name, linewise }, 1);
+        Ok(())
+    }
+
+    pub fn jump_older(&mut self) -> anyhow::Result<()> {
+        let current = Jump {
+            path: self.buffer.file().map(Path::to_path_buf),
+            location: self.cursor.location(),
+        };
+        let jump = self.jumps.older(current).cloned();
+        self.go_to_jump(jump)
+    }
+
+    pub fn jump_newer(&mut self) -> anyhow::Result<()> {
+        let jump = self.jumps.newer().cloned();
+        self.go_to_jump(jump)
+    }
+
+    fn go_to_jump(&mut self, jump: Option<Jump>) -> anyhow::Result<()> {
+        let Some(Jump { path, location }) = jump else {
+            return Ok(());
+        };
+        if let Some(path) = path
+            && self.buffer.file() != Some(path.as_path())
+        {
+            self.ensure_saved()?;
+            self.open(path)?;
+        }
+
+        self.cursor = Cursor::new(location);
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_buffer();
+        self.set_render(true);
+        Ok(())
+    }
+
+    fn ensure_saved(&self) -> anyhow::Result<()> {
+        if self.buffer.dirty() {
+            anyhow::bail!("No write since last change");
         }
+        Ok(())
+    }
+
+    /// Remembers `from` in the jump list and as the `''` mark.
+    fn record_jump(&mut self, from: Location) {
+        self.buffer.marks_mut().set(CONTEXT_MARK, from);
+        self.jumps.push(Jump {
+            path: self.buffer.file().map(Path::to_path_buf),
+            location: from,
+        });
+    }
+
+    /// return: the text operated on, `None` if the motion failed
+    pub fn apply_operator(
+        &mut self,
+        operator: Operator,
+        motion: Motion,
+        count: usize,
+    ) -> Option<String> {
+        let cursor = self.cursor.location();
+        let (target, kind) = match (operator, motion) {
+            // `cw` changes to the end of the word like `ce`, without skipping to the
+            // next word when on its last grapheme.
+            (Operator::Change, Motion::WordStart { forward: true, big })
+                if !self.buffer.is_blank(cursor) =>
+            {
+                let target = (0..count).fold(cursor, |location, step| {
+                    if step == 0 && self.buffer.is_word_end(location, big) {
+                        location
+                    } else {
+                        self.buffer.next_word_end(location, big)
+                    }
+                });
+                (target, MotionKind::Inclusive)
+            }
+            _ => self.motion_target(motion, count)?,
+        };
+        if kind == MotionKind::Linewise {
+            let first = std::cmp::min(cursor.line_index, target.line_index);
+            let last = std::cmp::max(cursor.line_index, target.line_index);
+            return Some(self.apply_linewise_operator(operator, first, last));
+        }
+
+        let (start, mut end) = if (target.line_index, target.grapheme_index)
+            < (cursor.line_index, cursor.grapheme_index)
+        {
+            (target, cursor)
+        } else {
+            (cursor, target)
+        };
+        if kind == MotionKind::Inclusive {
+            end.grapheme_index = end.grapheme_index.saturating_add(1);
+        } else if end.grapheme_index == 0 && end.line_index > start.line_index {
+            // An exclusive motion ending at the start of a line stops at the end of
+            // the line before, so `dw` on the last word keeps the line break.
+            end.line_index -= 1;
+            end.grapheme_index = self
+                .buffer
+                .get(end.line_index)
+                .map_or(0, |line| line.grapheme_count());
+        }
+
+        Some(self.operate(operator, start, end))
+    }
+
+    /// Applies `operator` to the text from `start` up to `end`, leaving the cursor at `start`.
+    fn operate(&mut self, operator: Operator, start: Location, end: Location) -> String {
+        let text = match operator {
+            Operator::Yank => self.buffer.text_in_range(start, end),
+            Operator::Delete | Operator::Change => self.buffer.delete_range(start, end),
+        };
+        self.cursor = Cursor::new(start);
+        self.snap_to_valid_grapheme();
         self.scroll_buffer();
+        self.set_render(true);
+        text
     }
 
-    pub fn move_to_previous_word(&mut self) {
-        let idx = self
+    fn location_at(&self, col: u16, row: u16) -> Location {
+        let col = (col as usize).saturating_sub(self.gutter_width());
+        let last_line = self.buffer.len().saturating_sub(1);
+        let (line_index, col) = if self.window.bool(OptionId::Wrap) {
+            let mut line_index = self.offset.row;
+            let mut row = row as usize;
+            while line_index < last_line && row >= self.line_rows(line_index) {
+                row -= self.line_rows(line_index);
+                line_index = line_index.saturating_add(1);
+            }
+            (
+                line_index.min(last_line),
+                row.saturating_mul(self.text_width()).saturating_add(col),
+            )
+        } else {
+            (
+                self.offset.row.saturating_add(row as usize).min(last_line),
+                self.offset.col.saturating_add(col),
+            )
+        };
+        let grapheme_index = self
             .buffer
-            .get(self.cursor.location().line_index)
-            .and_then(|line| {
-                if self.cursor.location().grapheme_index == 0 {
-                    None
-                } else {
-                    Some(
-                        line.search_backward(
-                            " ",
-                            self.cursor.location().grapheme_index.saturating_sub(1),
-                        )
-                        .map(|n| n.saturating_add(1))
-                        .unwrap_or_default(),
-                    )
-                }
-            });
+            .get(line_index)
+            .map_or(0, |line| line.grapheme_index_at_width(col, self.tabstop()));
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
 
-        if let Some(idx) = idx {
-            self.cursor.location_mut().grapheme_index = idx;
-        } else if self.cursor.location().line_index > 0 {
-            self.move_up(1);
-            self.move_to_end_of_line();
+    pub fn click(&mut self, col: u16, row: u16) {
+        self.clear_selection();
+        self.cursor = Cursor::new(self.location_at(col, row));
+        self.scroll_buffer();
+    }
+
+    pub fn drag(&mut self, col: u16, row: u16) {
+        if self.selection.is_none() {
+            self.selection = Some(self.cursor.location());
         }
+        self.cursor = Cursor::new(self.location_at(col, row));
+        self.scroll_buffer();
+        self.set_render(true);
+    }
+
+    pub fn select_word(&mut self, col: u16, row: u16) {
+        self.click(col, row);
+        let location = self.cursor.location();
+        let Some(line) = self.buffer.get(location.line_index) else {
+            return;
+        };
+        let class = |idx: usize| line.grapheme_at(idx).map(|g| CharClass::of(g, false));
+        let Some(target) = class(location.grapheme_index) else {
+            return;
+        };
+
+        let mut start = location.grapheme_index;
+        while start > 0 && class(start - 1) == Some(target) {
+            start -= 1;
+        }
+        let mut end = location.grapheme_index;
+        while class(end.saturating_add(1)) == Some(target) {
+            end = end.saturating_add(1);
+        }
+        self.selection = Some(Location {
+            grapheme_index: start,
+            ..location
+        });
+        self.cursor = Cursor::new(Location {
+            grapheme_index: end,
+            ..location
+        });
+        self.set_render(true);
+    }
+
+    pub fn has_selection(&self) -> bool {
+        self.selection.is_some()
+    }
+
+    pub fn clear_selection(&mut self) {
+        if self.selection.take().is_some() {
+            self.set_render(true);
+        }
+    }
+
+    /// (start, end) of the selection, including the graphemes at both ends.
+    fn selection_range(&self) -> Option<(Location, Location)> {
+        let anchor = self.selection?;
+        let cursor = self.cursor.location();
+        let key = |location: Location| (location.line_index, location.grapheme_index);
+        let (start, mut end) = if key(anchor) <= key(cursor) {
+            (anchor, cursor)
+        } else {
+            (cursor, anchor)
+        };
+        end.grapheme_index = end.grapheme_index.saturating_add(1);
+        Some((start, end))
+    }
+
+    /// (lines, graphemes) of the selection, the graphemes are only counted
+    /// within a single line.
+    fn selection_size(&self) -> Option<(usize, usize)> {
+        let (start, end) = self.selection_range()?;
+        let lines = end.line_index.saturating_sub(start.line_index) + 1;
+        let line_end = self
+            .buffer
+            .get(end.line_index)
+            .map_or(0, |line| line.grapheme_count());
+        let graphemes = end
+            .grapheme_index
+            .min(line_end)
+            .saturating_sub(start.grapheme_index);
+        Some((lines, graphemes))
+    }
+
+    /// return: the text operated on, `None` without a selection
+    pub fn apply_operator_to_selection(&mut self, operator: Operator) -> Option<String> {
+        let (start, end) = self.selection_range()?;
+        self.selection = None;
+        Some(self.operate(operator, start, end))
+    }
+
+    pub fn scroll_lines(&mut self, forward: bool, lines: usize) {
+        let height = self.size.height as usize;
+        self.offset.row = if forward {
+            self.offset
+                .row
+                .saturating_add(lines)
+                .min(self.buffer.len().saturating_sub(1))
+        } else {
+            self.offset.row.saturating_sub(lines)
+        };
+
+        let bottom = self.offset.row.saturating_add(height.saturating_sub(1));
+        let line_index = self.cursor.location().line_index;
+        self.cursor.location_mut().line_index = line_index.clamp(self.offset.row, bottom);
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.set_render(true);
+    }
+
+    /// Applies `operator` to the whole lines `first..=last`.
+    fn apply_linewise_operator(&mut self, operator: Operator, first: usize, last: usize) -> String {
+        let start = Location {
+            grapheme_index: 0,
+            line_index: first,
+        };
+        let end = Location {
+            grapheme_index: self
+                .buffer
+                .get(last)
+                .map_or(0, |line| line.grapheme_count()),
+            line_index: last,
+        };
+
+        let (text, location) = match operator {
+            Operator::Yank => (
+                self.buffer.text_in_range(start, end) + "\n",
+                self.line_start(first),
+            ),
+            Operator::Delete => {
+                let text = self.buffer.delete_lines(first..last.saturating_add(1));
+                let line_index = std::cmp::min(first, self.buffer.len().saturating_sub(1));
+                (text, self.line_start(line_index))
+            }
+            Operator::Change => (self.buffer.delete_range(start, end) + "\n", start),
+        };
+        self.cursor = Cursor::new(location);
         self.scroll_buffer();
+        self.set_render(true);
+        text
     }
 
     pub fn move_to_end_of_line(&mut self) {
@@ -262,51 +965,100 @@ impl View {
             std::cmp::min(self.cursor.location().line_index, self.buffer.len());
     }
 
-    fn render_buffer(&self, origin_row: u16) {
-        let (cols, rows) = self.size();
-        let end_y = rows.saturating_add(origin_row);
-        let (left, top) = self.offset.pos();
+    fn render_buffer(&mut self, backend: &mut dyn Backend, origin_row: u16) {
+        let (_, rows) = self.size();
+        let top = self.offset.row;
+        let visible = top..self.bottom_line().saturating_add(1);
 
-        let query = self.search_info.as_ref().map(SearchInfo::query);
-        let selected_match = query.is_some().then_some(self.cursor.location());
-        let mut highlighter =
-            Highlighter::new(query, selected_match, self.get_status().file_info.file_ty);
+        let file_ty = self.file_type();
+        self.buffer
+            .highlight_syntax(visible.end.saturating_sub(1), file_ty);
+        let cursor = self.cursor.location();
+        // Only a pair on screen is highlighted, `%` searches the whole buffer.
+        let bracket_pair = if self.buffer.is_bracket(cursor) {
+            self.buffer
+                .matching_bracket(cursor, file_ty, visible.clone())
+                .filter(|(bracket, _)| *bracket == cursor)
+        } else {
+            None
+        };
 
-        for row in 0..top.saturating_add(rows as usize) {
-            self.buffer.highlight(row, &mut highlighter);
+        let prompt_query = self.search_info.as_ref().and_then(SearchInfo::query);
+        let selected_match = prompt_query.is_some().then_some(self.cursor.location());
+        let query = prompt_query.or(self
+            .highlighted_search()
+            .map(|active_search| &active_search.pattern));
+        let mut highlighter = Highlighter::new(
+            query,
+            selected_match,
+            bracket_pair,
+            self.selection_range(),
+            self.buffer.syntax(),
+        );
+
+        for line_index in visible {
+            self.buffer.highlight(line_index, &mut highlighter);
         }
 
-        for row in origin_row..end_y {
-            let line_index = top
-                .saturating_add(row as usize)
-                .saturating_add(origin_row as usize);
-            let right = left.saturating_add(cols as usize);
-            if let Some(annotation_string) =
-                self.buffer
-                    .get_highlight_substring(line_index, left..right, &highlighter)
-            {
-                let _ = terminal::print_annotated_row(row, annotation_string);
-            } else {
-                Self::render_line(row, "~");
+        let gutter = self.gutter_width();
+        let text_width = self.text_width();
+        let mut line_index = top;
+        let mut row = 0;
+        while row < rows {
+            if line_index >= self.buffer.len() {
+                Self::render_line(
+                    backend,
+                    origin_row.saturating_add(row),
+                    "~",
+                    Element::NonText,
+                );
+                row = row.saturating_add(1);
+                continue;
+            }
+            for part in 0..self.line_rows(line_index) {
+                if row >= rows {
+                    break;
+                }
+                let y = origin_row.saturating_add(row);
+                let number = if part == 0 && gutter > 0 {
+                    format!("{:>1$} ", line_index.saturating_add(1), gutter - 1)
+                } else {
+                    " ".repeat(gutter)
+                };
+                Self::render_line(backend, y, &number, Element::Gutter);
+
+                let left = if self.window.bool(OptionId::Wrap) {
+                    part.saturating_mul(text_width)
+                } else {
+                    self.offset.col
+                };
+                if let Some(annotation_string) = self.buffer.get_highlight_substring(
+                    line_index,
+                    left..left.saturating_add(text_width),
+                    &highlighter,
+                    self.tabstop(),
+                ) {
+                    let _ = backend.print_annotated_at(gutter as u16, y, false, &annotation_string);
+                }
+                row = row.saturating_add(1);
             }
-
-            // Self::render_line(row, text.get_visable_graphemes(left..right));
+            line_index = line_index.saturating_add(1);
         }
     }
 
-    fn render_welcome(&self) {
+    fn render_welcome(&self, backend: &mut dyn Backend) {
         let (_, rows) = self.size();
 
         for row in 0..rows.saturating_sub(1) {
             if row == rows / 3 {
-                self.draw_welcome(row);
+                self.draw_welcome(backend, row);
             } else {
-                Self::render_line(row, "~");
+                Self::render_line(backend, row, "~", Element::NonText);
             }
         }
     }
 
-    fn draw_welcome(&self, row: u16) {
+    fn draw_welcome(&self, backend: &mut dyn Backend, row: u16) {
         let message = format!("{NAME} editor -- version {VERSION}");
         let len = message.len();
 
@@ -317,7 +1069,7 @@ impl View {
         let col = col as usize;
 
         let start = col.saturating_sub(len) / 2;
-        let result = terminal::print_at(start as u16, row, true, message);
+        let result = backend.print_at(start as u16, row, true, &message);
 
         debug_assert!(result.is_ok())
     }
@@ -325,18 +1077,36 @@ impl View {
     fn caret_position(&self) -> Position {
         let cursor = self.cursor.location();
         let row = cursor.line_index;
-        let col = self
-            .buffer
-            .get(row)
-            .map_or(0, |line| line.width_until(cursor.grapheme_index));
+        let col = self.buffer.get(row).map_or(0, |line| {
+            line.width_until(cursor.grapheme_index, self.tabstop())
+        });
         Position { col, row }
     }
 
     pub fn cursor_pos(&self) -> (u16, u16) {
-        self.caret_position().subtract(&self.offset)
+        let caret = self.caret_position();
+        let (col, row) = if self.window.bool(OptionId::Wrap) {
+            let text_width = self.text_width();
+            let rows = (self.offset.row..caret.row)
+                .map(|line_index| self.line_rows(line_index))
+                .sum::<usize>();
+            (
+                (caret.col % text_width) as u16,
+                rows.saturating_add(caret.col / text_width) as u16,
+            )
+        } else {
+            caret.subtract(&self.offset)
+        };
+        (col.saturating_add(self.gutter_width() as u16), row)
     }
 
     pub fn insert_char(&mut self, c: char) {
+        if c == '\t' && self.buffer.options().bool(OptionId::Expandtab) {
+            let Position { col, .. } = self.caret_position();
+            let tabstop = self.tabstop().max(1);
+            self.paste(&" ".repeat(tabstop - col % tabstop));
+            return;
+        }
         let old_len = self
             .buffer
             .get(self.cursor.location().line_index)
@@ -357,6 +1127,14 @@ impl View {
         self.set_render(true);
     }
 
+    /// Inserts pasted text at the cursor as one edit, leaving the cursor after it.
+    pub fn paste(&mut self, text: &str) {
+        let end = self.buffer.insert_text(self.cursor.location(), text);
+        self.cursor = Cursor::new(end);
+        self.scroll_buffer();
+        self.set_render(true);
+    }
+
     pub fn delete(&mut self) {
         self.buffer.delete(&self.cursor);
         self.set_render(true);
@@ -375,6 +1153,42 @@ impl View {
         }
     }
 
+    pub fn replace_char(&mut self, c: char) {
+        let location = self.cursor.location();
+        let original = self
+            .buffer
+            .get(location.line_index)
+            .and_then(|line| line.grapheme_at(location.grapheme_index))
+            .map(str::to_string);
+        if original.is_some() {
+            self.delete();
+        }
+        self.buffer.insert_char(c, &self.cursor);
+        self.move_point(Direction::Right);
+        self.replaced.push((location, original));
+        self.set_render(true);
+    }
+
+    /// Puts back the grapheme typed over left of the cursor. Before where replacing
+    /// started it only moves.
+    pub fn replace_backspace(&mut self) {
+        let location = self.cursor.location();
+        let Some((typed_at, original)) = self.replaced.pop_if(|(typed_at, _)| {
+            typed_at.line_index == location.line_index
+                && typed_at.grapheme_index.saturating_add(1) == location.grapheme_index
+        }) else {
+            self.replaced.clear();
+            self.move_point(Direction::Left);
+            return;
+        };
+        self.move_point(Direction::Left);
+        self.delete();
+        if let Some(original) = original {
+            self.buffer.insert_text(typed_at, &original);
+        }
+        self.set_render(true);
+    }
+
     pub fn open_new_line_below(&mut self) {
         self.move_to_end_of_line();
         self.buffer.insert_newline(&self.cursor);
@@ -400,53 +1214,410 @@ impl View {
         Ok(())
     }
 
+    pub fn check_disk_change(&mut self) -> bool {
+        self.buffer.check_disk_change()
+    }
+
     pub fn save_as(&mut self, path: &str) -> anyhow::Result<()> {
         self.buffer.save_as(path)?;
         self.set_render(true);
         Ok(())
     }
 
+    pub fn commit_change(&mut self) {
+        self.replaced.clear();
+        self.buffer.commit();
+    }
+
+    /// return: false when there is nothing to undo
+    pub fn undo(&mut self) -> bool {
+        let location = self.buffer.undo();
+        self.restore_location(location)
+    }
+
+    /// return: false when there is nothing to redo
+    pub fn redo(&mut self) -> bool {
+        let location = self.buffer.redo();
+        self.restore_location(location)
+    }
+
+    fn restore_location(&mut self, location: Option<Location>) -> bool {
+        if let Some(location) = location {
+            self.cursor = Cursor::new(location);
+            self.snap_to_valid_line();
+            self.snap_to_valid_grapheme();
+            self.scroll_buffer();
+            self.set_render(true);
+        }
+        location.is_some()
+    }
+
+    pub fn substitution(&mut self, substitute: Substitute) -> anyhow::Result<Substitution> {
+        let Substitute {
+            range,
+            pattern,
+            replacement,
+            flags,
+        } = substitute;
+        let pattern = match (pattern.is_empty(), &self.active_search) {
+            (false, _) => pattern,
+            (true, Some(active_search)) => active_search.query.clone(),
+            (true, None) => anyhow::bail!("No previous regular expression"),
+        };
+        let options = flags
+            .ignore_case
+            .map_or(self.search_options(), |ignorecase| SearchOptions {
+                ignorecase,
+                smartcase: false,
+                ..self.search_options()
+            });
+        let pattern = Pattern::new(&pattern, options)
+            .map_err(|_| anyhow::anyhow!("Invalid regular expression"))?;
+        let lines = range.resolve(self.cursor.location().line_index, self.buffer.len())?;
+
+        self.buffer.commit();
+        Ok(Substitution {
+            pattern,
+            replacement,
+            next: (lines.start, 0),
+            after_match: false,
+            lines,
+            global: flags.global,
+            candidate: None,
+            count: 0,
+            changed_lines: 0,
+            last_changed_line: None,
+        })
+    }
+
+    fn find_substitution(&self, substitution: &mut Substitution) -> bool {
+        let (mut line_index, mut byte_idx) = substitution.next;
+        let mut after_match = substitution.after_match;
+        substitution.candidate = None;
+
+        while line_index < substitution.lines.end {
+            if let Some((bytes, grapheme_index)) =
+                self.buffer
+                    .find_match(&substitution.pattern, line_index, byte_idx, after_match)
+            {
+                substitution.candidate = Some((
+                    Location {
+                        grapheme_index,
+                        line_index,
+                    },
+                    bytes,
+                ));
+                return true;
+            }
+            line_index = line_index.saturating_add(1);
+            byte_idx = 0;
+            after_match = false;
+        }
+
+        false
+    }
+
+    fn advance_substitution(&self, substitution: &mut Substitution, line_index: usize, end: usize) {
+        substitution.after_match = substitution.global
+            && substitution
+                .candidate
+                .as_ref()
+                .is_some_and(|(_, bytes)| !bytes.is_empty());
+        substitution.next = if !substitution.global {
+            (line_index.saturating_add(1), 0)
+        } else if substitution
+            .candidate
+            .as_ref()
+            .is_some_and(|(_, bytes)| bytes.is_empty())
+        {
+            // Step over empty matches so they are not found again.
+            let next = self.buffer.get(line_index).and_then(|line| {
+                line.grapheme_indices(true)
+                    .map(|(idx, _)| idx)
+                    .chain(std::iter::once(line.len()))
+                    .find(|idx| *idx > end)
+            });
+            (line_index, next.unwrap_or(usize::MAX))
+        } else {
+            (line_index, end)
+        };
+        substitution.candidate = None;
+    }
+
+    /// return: false when no match is left
+    pub fn next_substitution(&mut self, substitution: &mut Substitution) -> bool {
+        if !self.find_substitution(substitution) {
+            return false;
+        }
+
+        if let Some((location, _)) = substitution.candidate {
+            self.search_info = Some(SearchInfo {
+                previous_pos: self.cursor,
+                previous_offset: self.offset,
+                query: Ok(substitution.pattern.clone()),
+            });
+            self.cursor = Cursor::new(location);
+            self.center_text_location();
+        }
+        true
+    }
+
+    /// Replaces the match found by the last call to `next_substitution`.
+    pub fn substitute(&mut self, substitution: &mut Substitution) {
+        if let Some((location, bytes)) = substitution.candidate.clone() {
+            let len = self.buffer.replace_match(
+                &substitution.pattern,
+                location,
+                bytes.clone(),
+                &substitution.replacement,
+            );
+            substitution.record(location.line_index);
+            self.advance_substitution(
+                substitution,
+                location.line_index,
+                bytes.start.saturating_add(len),
+            );
+            self.set_render(true);
+        }
+    }
+
+    /// Leaves the match found by the last call to `next_substitution` as it is.
+    pub fn skip_substitution(&mut self, substitution: &mut Substitution) {
+        if let Some((location, bytes)) = substitution.candidate.clone() {
+            self.advance_substitution(substitution, location.line_index, bytes.end);
+        }
+    }
+
+    pub fn substitute_all(&mut self, substitution: &mut Substitution) {
+        while self.find_substitution(substitution) {
+            self.substitute(substitution);
+        }
+    }
+
+    pub fn count_substitutions(&mut self, substitution: &mut Substitution) {
+        while self.find_substitution(substitution) {
+            if let Some((location, _)) = substitution.candidate {
+                substitution.record(location.line_index);
+            }
+            self.skip_substitution(substitution);
+        }
+    }
+
+    /// Makes the whole substitution a single undo step and moves to the last changed line.
+    pub fn finish_substitution(&mut self, substitution: &Substitution) {
+        self.buffer.commit();
+        self.search_info = None;
+        if let Some(line_index) = substitution.last_changed_line {
+            self.cursor = Cursor::new(Location {
+                grapheme_index: 0,
+                line_index,
+            });
+        }
+        self.scroll_buffer();
+        self.set_render(true);
+    }
+
+    pub fn option(&self, id: OptionId) -> &Value {
+        match id.info().scope {
+            Scope::Global => self.global.get(id),
+            Scope::Buffer => self.buffer.options().get(id),
+            Scope::Window => self.window.get(id),
+        }
+    }
+
+    /// Sets a buffer or window option for the buffer and window shown, and unless
+    /// `local` also the global value new buffers and windows start with.
+    pub fn set_option(&mut self, id: OptionId, value: Value, local: bool) {
+        match id.info().scope {
+            Scope::Global => self.global.set(id, value),
+            Scope::Buffer => {
+                if !local {
+                    self.global.set(id, value.clone());
+                }
+                self.buffer.options_mut().set(id, value);
+            }
+            Scope::Window => {
+                if !local {
+                    self.global.set(id, value.clone());
+                }
+                self.window.set(id, value);
+            }
+        }
+        self.scroll_buffer();
+        self.set_render(true);
+    }
+
+    fn tabstop(&self) -> usize {
+        self.buffer.options().int(OptionId::Tabstop)
+    }
+
+    fn search_options(&self) -> SearchOptions {
+        SearchOptions {
+            ignorecase: self.global.bool(OptionId::Ignorecase),
+            smartcase: self.global.bool(OptionId::Smartcase),
+            hlsearch: self.global.bool(OptionId::Hlsearch),
+        }
+    }
+
     pub fn enter_search(&mut self) {
         self.search_info = Some(SearchInfo {
             previous_pos: self.cursor,
             previous_offset: self.offset,
-            query: Line::default(),
+            query: Ok(Pattern::from("")),
         });
     }
 
     pub fn search_forward(&mut self, query: &str) {
+        let options = self.search_options();
         if let Some(ref mut search_info) = self.search_info {
-            search_info.query = Line::from(query);
+            search_info.query = Pattern::new(query, options);
         }
         self.search_from(self.cursor.location(), SearchDirection::Forward);
     }
 
     fn search_from(&mut self, from: Location, direction: SearchDirection) {
-        if let Some(ref search_info) = self.search_info {
-            let query = &search_info.query;
-            if query.is_empty() {
-                return;
-            }
+        if let Some(query) = self.search_info.as_ref().and_then(SearchInfo::query) {
+            let query = query.clone();
+            self.jump_to_match(&query, from, direction);
+        }
+    }
 
-            if let Some(location) = match direction {
-                SearchDirection::Forward => self.buffer.search_forward(query, from),
-                SearchDirection::Backward => self.buffer.search_backward(query, from),
-            } {
-                self.cursor = Cursor::new(location);
-                self.center_text_location();
-            }
+    fn jump_to_match(
+        &mut self,
+        pattern: &Pattern,
+        from: Location,
+        direction: SearchDirection,
+    ) -> Option<SearchHit> {
+        let location = match direction {
+            SearchDirection::Forward => self.buffer.search_forward(pattern, from),
+            SearchDirection::Backward => self.buffer.search_backward(pattern, from),
+        }?;
+        let found = (location.line_index, location.grapheme_index);
+        let from = (from.line_index, from.grapheme_index);
+        let hit = match direction {
+            SearchDirection::Forward if found < from => SearchHit::WrappedToTop,
+            SearchDirection::Backward if found >= from => SearchHit::WrappedToBottom,
+            _ => SearchHit::Match,
+        };
+
+        self.cursor = Cursor::new(location);
+        self.center_text_location();
+        Some(hit)
+    }
+
+    /// `smartcase` does not apply.
+    ///
+    /// return: the query searched for
+    pub fn search_word(
+        &mut self,
+        direction: SearchDirection,
+    ) -> anyhow::Result<(String, SearchHit)> {
+        let location = self.cursor.location();
+        let (start, word) = self
+            .buffer
+            .get(location.line_index)
+            .and_then(|line| line.word_at(location.grapheme_index))
+            .ok_or_else(|| anyhow::anyhow!("No string under cursor"))?;
+        let query = format!("{REGEX_PREFIX}\\<{}\\>", regex::escape(word));
+        let options = SearchOptions {
+            smartcase: false,
+            ..self.search_options()
+        };
+        let pattern = Pattern::new(&query, options)?;
+
+        let from = Location {
+            grapheme_index: match direction {
+                SearchDirection::Forward => start.saturating_add(1),
+                SearchDirection::Backward => start,
+            },
+            line_index: location.line_index,
+        };
+        let hit = self.jump_to_match(&pattern, from, direction);
+        if hit.is_some() {
+            self.record_jump(location);
+        }
+        self.active_search = Some(ActiveSearch {
+            query: query.clone(),
+            pattern,
+            direction,
+            highlight: true,
+            count: Cell::default(),
+        });
+        self.set_render(true);
+
+        let hit = hit.ok_or_else(|| anyhow::anyhow!("Pattern not found: {query}"))?;
+        Ok((query, hit))
+    }
+
+    pub fn repeat_search(&mut self, reverse: bool) -> anyhow::Result<SearchHit> {
+        let Some(active_search) = self.active_search.as_mut() else {
+            anyhow::bail!("No previous regular expression");
+        };
+        active_search.highlight = true;
+        let direction = if reverse {
+            active_search.direction.reverse()
+        } else {
+            active_search.direction
+        };
+        let pattern = active_search.pattern.clone();
+        let query = active_search.query.clone();
+        self.set_render(true);
+
+        let location = self.cursor.location();
+        let from = match direction {
+            SearchDirection::Forward => Location {
+                grapheme_index: location.grapheme_index.saturating_add(1),
+                ..location
+            },
+            SearchDirection::Backward => location,
+        };
+        let hit = self
+            .jump_to_match(&pattern, from, direction)
+            .ok_or_else(|| anyhow::anyhow!("Pattern not found: {query}"))?;
+        self.record_jump(location);
+        Ok(hit)
+    }
+
+    pub fn clear_search_highlight(&mut self) {
+        if let Some(active_search) = self.active_search.as_mut() {
+            active_search.highlight = false;
+        }
+        self.set_render(true);
+    }
+
+    fn highlighted_search(&self) -> Option<&ActiveSearch> {
+        self.active_search
+            .as_ref()
+            .filter(|active_search| active_search.highlight && self.global.bool(OptionId::Hlsearch))
+    }
+
+    /// The number of the match at or before the cursor and the number of all matches
+    /// of the highlighted search, counting up to `MAX_SEARCH_COUNT + 1`.
+    fn search_count(&self) -> Option<(usize, usize)> {
+        let active_search = self.highlighted_search()?;
+        let (edits, location) = (self.buffer.edits(), self.cursor.location());
+        if let Some(counted) = active_search.count.get()
+            && (counted.edits, counted.location) == (edits, location)
+        {
+            return Some(counted.count);
         }
+        let count = self.buffer.count_matches(
+            &active_search.pattern,
+            location,
+            MAX_SEARCH_COUNT.saturating_add(1),
+        );
+        active_search.count.set(Some(SearchCount {
+            edits,
+            location,
+            count,
+        }));
+        Some(count)
     }
 
     pub fn search_next(&mut self) {
-        if let Some(ref search_info) = self.search_info {
-            let step_right = std::cmp::max(search_info.query.grapheme_count(), 1);
+        if self.search_info.is_some() {
             let location = Location {
-                grapheme_index: self
-                    .cursor
-                    .location()
-                    .grapheme_index
-                    .saturating_add(step_right),
+                grapheme_index: self.cursor.location().grapheme_index.saturating_add(1),
                 line_index: self.cursor.location().line_index,
             };
 
@@ -467,8 +1638,33 @@ impl View {
         self.search_info = None;
     }
 
-    pub fn exit_search(&mut self) {
-        self.search_info = None
+    pub fn has_invalid_search(&self) -> bool {
+        self.search_info
+            .as_ref()
+            .is_some_and(|search_info| search_info.query.is_err())
+    }
+
+    pub fn exit_search(&mut self, query: String) {
+        let Some(search_info) = self.search_info.take() else {
+            self.set_render(true);
+            return;
+        };
+        let from = search_info.previous_pos.location();
+        if from != self.cursor.location() {
+            self.record_jump(from);
+        }
+        if let Ok(pattern) = search_info.query
+            && !pattern.is_empty()
+        {
+            self.active_search = Some(ActiveSearch {
+                query,
+                pattern,
+                direction: SearchDirection::Forward,
+                highlight: true,
+                count: Cell::default(),
+            });
+        }
+        self.set_render(true);
     }
 }
 
@@ -486,21 +1682,32 @@ impl UiComponent for View {
         self.scroll_buffer();
     }
 
-    fn draw(&mut self, y: u16) -> anyhow::Result<()> {
+    fn draw(&mut self, backend: &mut dyn Backend, y: u16) -> anyhow::Result<()> {
         if self.size.height == 0 {
             anyhow::bail!("terminal size is zero")
         }
 
         if self.buffer.is_empty() {
-            self.render_welcome();
+            self.render_welcome(backend);
         } else {
-            self.render_buffer(y);
+            self.render_buffer(backend, y);
         }
 
         Ok(())
     }
 }
 
+#[cfg(test)]
+use crate::editor::view::line::Line;
+
+#[cfg(test)]
+fn at(line_index: usize, grapheme_index: usize) -> Location {
+    Location {
+        grapheme_index,
+        line_index,
+    }
+}
+
 #[test]
 fn test_search() {
     let mut view = View {
@@ -546,3 +1753,386 @@ fn test_search_with_unicode() {
         }
     );
 }
+
+#[test]
+fn test_regex_search() {
+    let mut view = View {
+        buffer: Buffer::new(vec![
+            Line::from("let a = 1;"),
+            Line::from("let answer = 42;"),
+        ]),
+        ..Default::default()
+    };
+
+    view.enter_search();
+    view.search_forward("\\v\\d{2,}");
+    assert_eq!(view.cursor.location(), at(1, 13));
+
+    view.search_forward("\\v(");
+    assert!(view.has_invalid_search());
+}
+
+#[test]
+fn test_substitute() {
+    let mut view = View {
+        buffer: Buffer::new(vec![
+            Line::from("foo = foo(1);"),
+            Line::from("bar = foo(2);"),
+            Line::from("foo"),
+        ]),
+        ..Default::default()
+    };
+    let substitute = |command: &str| match command.parse() {
+        Ok(crate::editor::ex::ExCommand::Substitute(substitute)) => substitute,
+        _ => panic!("expected a substitution"),
+    };
+
+    let mut substitution = view
+        .substitution(substitute("1,2s/\\v(\\w+)\\((\\d)\\)/\\2.\\1()/g"))
+        .unwrap();
+    view.substitute_all(&mut substitution);
+    view.finish_substitution(&substitution);
+    assert_eq!((substitution.count(), substitution.changed_lines()), (2, 2));
+    assert_eq!(view.buffer[0].to_string(), "foo = 1.foo();");
+    assert_eq!(view.buffer[1].to_string(), "bar = 2.foo();");
+
+    // Confirm: replace the first match, skip the second, then replace the rest.
+    let mut substitution = view.substitution(substitute("%s/foo/baz/gc")).unwrap();
+    assert!(view.next_substitution(&mut substitution));
+    view.substitute(&mut substitution);
+    assert!(view.next_substitution(&mut substitution));
+    assert_eq!(view.cursor.location(), at(0, 8));
+    view.skip_substitution(&mut substitution);
+    assert!(view.next_substitution(&mut substitution));
+    view.substitute_all(&mut substitution);
+    view.finish_substitution(&substitution);
+    assert_eq!(substitution.count(), 3);
+    assert_eq!(view.buffer[0].to_string(), "baz = 1.foo();");
+    assert_eq!(view.buffer[1].to_string(), "bar = 2.baz();");
+    assert_eq!(view.buffer[2].to_string(), "baz");
+
+    // Each substitution is undone as a whole.
+    assert!(view.undo());
+    assert_eq!(view.buffer[0].to_string(), "foo = 1.foo();");
+    assert_eq!(view.buffer[2].to_string(), "foo");
+    assert!(view.undo());
+    assert_eq!(view.buffer[0].to_string(), "foo = foo(1);");
+    assert!(!view.undo());
+    assert!(view.redo());
+    assert_eq!(view.buffer[1].to_string(), "bar = 2.foo();");
+
+    // Empty matches at the end of the line, once per line even with `g`.
+    let mut substitution = view.substitution(substitute("%s/\\v$/x/g")).unwrap();
+    view.substitute_all(&mut substitution);
+    assert_eq!(substitution.count(), 3);
+    assert_eq!(view.buffer[0].to_string(), "foo = 1.foo();x");
+    assert_eq!(view.buffer[2].to_string(), "foox");
+    let mut substitution = view.substitution(substitute("3s/\\vx*/-/g")).unwrap();
+    view.substitute_all(&mut substitution);
+    assert_eq!(view.buffer[2].to_string(), "-f-o-o-");
+}
+
+#[test]
+fn test_search_word() {
+    let mut view = View {
+        buffer: Buffer::new(vec![
+            Line::from("foo foobar foo_bar"),
+            Line::from("(Foo) foo"),
+        ]),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        view.search_word(SearchDirection::Forward).unwrap(),
+        (String::from("\\v\\<foo\\>"), SearchHit::Match)
+    );
+    assert_eq!(view.cursor.location(), at(1, 6));
+
+    view.search_word(SearchDirection::Backward).unwrap();
+    assert_eq!(view.cursor.location(), at(0, 0));
+
+    view.set_option(OptionId::Ignorecase, Value::Bool(true), false);
+    view.set_option(OptionId::Smartcase, Value::Bool(true), false);
+    view.cursor = Cursor::new(at(1, 0));
+    view.search_word(SearchDirection::Forward).unwrap();
+    assert_eq!(view.cursor.location(), at(1, 6));
+}
+
+#[test]
+fn test_repeat_search() {
+    let mut view = View {
+        buffer: Buffer::new(vec![
+            Line::from("one two"),
+            Line::from("two one"),
+            Line::from("one"),
+        ]),
+        ..Default::default()
+    };
+
+    assert!(view.repeat_search(false).is_err());
+    view.enter_search();
+    view.search_forward("one");
+    view.exit_search(String::from("one"));
+    assert_eq!(view.search_count(), Some((1, 3)));
+
+    assert_eq!(view.repeat_search(false).unwrap(), SearchHit::Match);
+    assert_eq!(view.cursor.location(), at(1, 4));
+    assert_eq!(view.search_count(), Some((2, 3)));
+    view.repeat_search(false).unwrap();
+    assert_eq!(view.repeat_search(false).unwrap(), SearchHit::WrappedToTop);
+    assert_eq!(view.cursor.location(), at(0, 0));
+    assert_eq!(
+        view.repeat_search(true).unwrap(),
+        SearchHit::WrappedToBottom
+    );
+    assert_eq!(view.cursor.location(), at(2, 0));
+
+    view.clear_search_highlight();
+    assert_eq!(view.search_count(), None);
+    view.repeat_search(true).unwrap();
+    assert_eq!(view.search_count(), Some((2, 3)));
+    // The count is kept until the buffer changes.
+    view.cursor = Cursor::new(at(2, 3));
+    view.insert_char('o');
+    view.insert_char('n');
+    view.insert_char('e');
+    assert_eq!(view.search_count(), Some((4, 4)));
+}
+
+#[test]
+fn test_find_char() {
+    let mut view = View {
+        buffer: Buffer::new(vec![Line::from("a,b,c 👍🏽 d,e")]),
+        ..Default::default()
+    };
+    let find = |target, forward, till| {
+        Motion::FindChar(CharSearch {
+            target,
+            forward,
+            till,
+        })
+    };
+    let column = |view: &View| view.cursor.location().grapheme_index;
+
+    assert!(view.apply_motion(find(',', true, false), 2));
+    assert_eq!(column(&view), 3);
+    assert!(view.apply_motion(find('👍', true, false), 1));
+    assert_eq!(column(&view), 6);
+    assert!(!view.apply_motion(Motion::RepeatFindReverse, 1));
+    assert_eq!(column(&view), 6);
+    assert!(!view.apply_motion(find('x', true, false), 1));
+
+    view.cursor = Cursor::default();
+    assert!(view.apply_motion(find(',', true, true), 1));
+    assert_eq!(column(&view), 0);
+    assert!(view.apply_motion(Motion::RepeatFind, 1));
+    assert_eq!(column(&view), 2);
+    assert!(!view.apply_motion(Motion::RepeatFindReverse, 1));
+    assert_eq!(column(&view), 2);
+
+    assert_eq!(
+        view.apply_operator(Operator::Delete, find(',', true, false), 2),
+        Some(String::from("b,c 👍🏽 d,"))
+    );
+    assert_eq!(view.buffer.first().unwrap().to_string(), "a,e");
+    assert_eq!(
+        view.apply_operator(Operator::Yank, find('a', false, false), 1),
+        Some(String::from("a,"))
+    );
+}
+
+#[test]
+fn test_word_operators() {
+    let mut view = View {
+        buffer: Buffer::new(vec![Line::from("let x = a;"), Line::from("b")]),
+        ..Default::default()
+    };
+    let word = |forward| Motion::WordStart {
+        forward,
+        big: false,
+    };
+    let line = |view: &View, idx: usize| view.buffer[idx].to_string();
+
+    view.cursor = Cursor::new(at(0, 9));
+    assert_eq!(
+        view.apply_operator(Operator::Delete, word(true), 1),
+        Some(String::from(";"))
+    );
+    assert_eq!(line(&view, 0), "let x = a");
+
+    view.cursor = Cursor::default();
+    assert_eq!(
+        view.apply_operator(Operator::Change, word(true), 1),
+        Some(String::from("let"))
+    );
+    view.cursor = Cursor::new(at(0, 1));
+    assert_eq!(
+        view.apply_operator(Operator::Delete, word(true), 2),
+        Some(String::from("x = "))
+    );
+    assert_eq!(line(&view, 0), " a");
+    assert_eq!(line(&view, 1), "b");
+}
+
+#[test]
+fn test_screen_motions_and_scrolling() {
+    let mut view = View {
+        buffer: Buffer::new((0..30).map(|idx| Line::from(format!("  {idx}"))).collect()),
+        size: Size {
+            width: 20,
+            height: 10,
+        },
+        ..Default::default()
+    };
+    let location = |view: &View| {
+        let location = view.cursor.location();
+        (location.line_index, location.grapheme_index)
+    };
+
+    assert!(view.apply_motion(Motion::Line(usize::MAX), 1));
+    assert_eq!(location(&view), (29, 2));
+    assert_eq!(view.offset.row, 20);
+    assert!(view.apply_motion(Motion::ScreenLine(ScreenLine::Top), 3));
+    assert_eq!(location(&view), (22, 2));
+    assert!(view.apply_motion(Motion::ScreenLine(ScreenLine::Middle), 1));
+    assert_eq!(location(&view), (24, 2));
+
+    assert!(view.scroll(false, true, 1));
+    assert_eq!((view.offset.row, location(&view).0), (15, 19));
+    assert!(view.scroll(false, false, 1));
+    assert_eq!((view.offset.row, location(&view).0), (7, 16));
+    view.reposition(ScreenLine::Bottom);
+    assert_eq!(view.offset.row, 7);
+    view.reposition(ScreenLine::Top);
+    assert_eq!(view.offset.row, 16);
+    assert!(view.scroll(true, true, 5));
+    assert_eq!((view.offset.row, location(&view).0), (20, 29));
+    assert!(!view.scroll(true, true, 1));
+
+    assert_eq!(
+        view.apply_operator(Operator::Delete, Motion::Line(28), 1),
+        Some(String::from("  27\n  28\n  29\n"))
+    );
+    assert_eq!(view.buffer.len(), 27);
+    assert_eq!(location(&view), (26, 2));
+    assert_eq!(
+        view.apply_operator(Operator::Yank, Motion::ScreenLine(ScreenLine::Bottom), 2),
+        Some(String::from("  25\n  26\n"))
+    );
+}
+
+#[test]
+fn test_marks_and_jumps() {
+    let mut view = View {
+        buffer: Buffer::new(
+            (0..20)
+                .map(|idx| Line::from(format!("line {idx}")))
+                .collect(),
+        ),
+        size: Size {
+            width: 20,
+            height: 10,
+        },
+        ..Default::default()
+    };
+    let location = |view: &View| {
+        let location = view.cursor.location();
+        (location.line_index, location.grapheme_index)
+    };
+
+    view.cursor = Cursor::new(at(2, 3));
+    view.set_mark('a').unwrap();
+    assert!(view.set_mark('1').is_err());
+    assert!(view.apply_motion(Motion::Line(10), 1));
+    view.jump_to_mark('a', false).unwrap();
+    assert_eq!(location(&view), (2, 3));
+    view.jump_to_mark('\'', true).unwrap();
+    assert_eq!(location(&view), (9, 0));
+    assert!(view.jump_to_mark('b', true).is_err());
+
+    view.jump_older().unwrap();
+    assert_eq!(location(&view), (2, 3));
+    // The oldest entry stays put.
+    view.jump_older().unwrap();
+    assert_eq!(location(&view), (2, 3));
+    view.jump_newer().unwrap();
+    view.jump_newer().unwrap();
+    assert_eq!(location(&view), (9, 0));
+
+    view.cursor = Cursor::new(at(1, 0));
+    assert_eq!(
+        view.apply_operator(
+            Operator::Delete,
+            Motion::Mark {
+                name: 'a',
+                linewise: true
+            },
+            1
+        ),
+        Some(String::from("line 1\nline 2\n"))
+    );
+    assert!(view.jump_to_mark('a', true).is_err());
+}
+
+#[test]
+fn test_mouse_selection() {
+    let mut view = View {
+        buffer: Buffer::new(
+            (0..30)
+                .map(|idx| Line::from(format!("你好 word{idx}")))
+                .collect(),
+        ),
+        size: Size {
+            width: 20,
+            height: 10,
+        },
+        ..Default::default()
+    };
+    let location = |view: &View| {
+        let location = view.cursor.location();
+        (location.line_index, location.grapheme_index)
+    };
+
+    view.scroll_lines(true, 3);
+    assert_eq!(view.offset.row, 3);
+    assert_eq!(location(&view), (3, 0));
+    view.click(3, 2);
+    assert_eq!(location(&view), (5, 1));
+    view.drag(6, 3);
+    assert_eq!(location(&view), (6, 4));
+    assert_eq!(
+        view.apply_operator_to_selection(Operator::Yank),
+        Some(String::from("好 word5\n你好 wo"))
+    );
+    assert!(!view.has_selection());
+
+    view.select_word(7, 0);
+    assert_eq!(
+        view.apply_operator_to_selection(Operator::Delete),
+        Some(String::from("word3"))
+    );
+    assert_eq!(view.buffer[3].to_string(), "你好 ");
+    assert_eq!(view.apply_operator_to_selection(Operator::Delete), None);
+}
+
+#[test]
+fn test_scrolloff() {
+    let mut view = View {
+        buffer: Buffer::new((0..30).map(|idx| Line::from(format!("{idx}"))).collect()),
+        size: Size {
+            width: 20,
+            height: 10,
+        },
+        ..Default::default()
+    };
+    view.set_option(OptionId::Scrolloff, Value::Int(3), true);
+
+    assert!(view.apply_motion(Motion::Line(7), 1));
+    assert_eq!(view.offset.row, 0);
+    assert!(view.apply_motion(Motion::Line(8), 1));
+    assert_eq!(view.offset.row, 1);
+    assert!(view.apply_motion(Motion::Line(30), 1));
+    assert_eq!(view.offset.row, 20);
+    assert!(view.apply_motion(Motion::Line(22), 1));
+    assert_eq!(view.offset.row, 18);
+}
@@ -1,21 +1,81 @@
 use std::{
+    collections::VecDeque,
     io::Write,
     ops::{Deref, Range},
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
 use anyhow::Context;
+use unicode_segmentation::UnicodeSegmentation;
 
 use crate::editor::{
+    FileType,
     annotated::AnnotatedString,
-    view::{cursor::Location, highlighter::Highlighter, line::Line},
+    options::{OptionId, Options, Value},
+    view::{
+        cursor::Location,
+        highlighter::{Highlighter, SyntaxCache},
+        line::{CharClass, Line},
+        marks::Marks,
+        pattern::Pattern,
+    },
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Bracket {
+    kind: char,
+    open: bool,
+}
+
+impl Bracket {
+    fn from_grapheme(grapheme: &str) -> Option<Self> {
+        let (kind, open) = match grapheme {
+            "(" => ('(', true),
+            ")" => ('(', false),
+            "[" => ('[', true),
+            "]" => ('[', false),
+            "{" => ('{', true),
+            "}" => ('{', false),
+            _ => return None,
+        };
+        Some(Self { kind, open })
+    }
+}
+
+const UNDO_LEVELS: usize = 1000;
+
+/// Lines `start..start + new_len` replaced `old`.
+struct Change {
+    start: usize,
+    old: Vec<Line>,
+    new_len: usize,
+}
+
+/// The changes of an undo step, and where the step started.
+struct Revision {
+    changes: Vec<Change>,
+    location: Location,
+}
+
 #[derive(Default)]
 pub struct Buffer {
     file: Option<PathBuf>,
     dirty: bool,
     lines: Vec<Line>,
+    undo: VecDeque<Revision>,
+    redo: Vec<Revision>,
+    recording: bool,
+    /// The number of lines before the edit being recorded, whose `new_len`
+    /// is only known once it is done.
+    len_before_edit: Option<usize>,
+    marks: Marks,
+    /// When the file was last read or written by us.
+    disk_time: Option<SystemTime>,
+    syntax: SyntaxCache,
+    options: Options,
+    /// Counts the edits, so results computed from the lines can be reused until the next.
+    edits: usize,
 }
 
 impl Buffer {
@@ -26,18 +86,161 @@ impl Buffer {
     pub fn load(&mut self, path: PathBuf) -> anyhow::Result<()> {
         let contents = std::fs::read_to_string(&path).context("read from file")?;
         self.lines = contents.lines().map(Line::from).collect();
+        self.edits = self.edits.wrapping_add(1);
+        self.undo.clear();
+        self.redo.clear();
+        let dos = contents
+            .split_once('\n')
+            .is_some_and(|(line, _)| line.ends_with('\r'));
+        let fileformat = if dos { "dos" } else { "unix" };
+        self.options
+            .set(OptionId::Fileformat, Value::String(fileformat.into()));
+        self.syntax.reset();
+        self.disk_time = modified_time(&path);
+        let readonly =
+            std::fs::metadata(&path).is_ok_and(|metadata| metadata.permissions().readonly());
+        self.options.set(OptionId::Readonly, Value::Bool(readonly));
         self.file = Some(path);
         Ok(())
     }
 
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut Options {
+        &mut self.options
+    }
+
+    /// Whether another program changed the file since it was read or written,
+    /// only reported once per change.
+    pub fn check_disk_change(&mut self) -> bool {
+        let Some(time) = self.file.as_deref().and_then(modified_time) else {
+            return false;
+        };
+        let changed = self.disk_time.is_some_and(|disk_time| disk_time != time);
+        self.disk_time = Some(time);
+        changed
+    }
+
+    /// Keeps `lines`, the lines an edit is about to change, for undo.
+    fn record(&mut self, location: Location, lines: Range<usize>) {
+        self.finish_edit();
+        if !self.recording {
+            if self.undo.len() >= UNDO_LEVELS {
+                self.undo.pop_front();
+            }
+            self.undo.push_back(Revision {
+                changes: Vec::new(),
+                location,
+            });
+            self.redo.clear();
+            self.recording = true;
+        }
+        let lines = lines.start.min(self.lines.len())..lines.end.min(self.lines.len());
+        if let Some(revision) = self.undo.back_mut() {
+            // An edit of lines the step already changed needs no copy of them.
+            let within_last = revision.changes.last().is_some_and(|change| {
+                change.start <= lines.start && lines.end <= change.start + change.new_len
+            });
+            if !within_last {
+                revision.changes.push(Change {
+                    start: lines.start,
+                    old: self.lines[lines.clone()].to_vec(),
+                    new_len: lines.len(),
+                });
+            }
+            self.len_before_edit = Some(self.lines.len());
+        }
+        self.edits = self.edits.wrapping_add(1);
+        self.dirty = true;
+    }
+
+    fn finish_edit(&mut self) {
+        let Some(len_before) = self.len_before_edit.take() else {
+            return;
+        };
+        if let Some(change) = self
+            .undo
+            .back_mut()
+            .and_then(|revision| revision.changes.last_mut())
+        {
+            change.new_len = (change.new_len + self.lines.len()).saturating_sub(len_before);
+        }
+    }
+
+    /// Applies the changes of `revision` backwards.
+    ///
+    /// return: the revision which applies them again
+    fn revert(&mut self, revision: Revision) -> Revision {
+        let changes = revision
+            .changes
+            .into_iter()
+            .rev()
+            .map(|change| Change {
+                start: change.start,
+                new_len: change.old.len(),
+                old: self
+                    .lines
+                    .splice(change.start..change.start + change.new_len, change.old)
+                    .collect(),
+            })
+            .collect();
+        self.syntax.reset();
+        self.edits = self.edits.wrapping_add(1);
+        self.dirty = true;
+        Revision {
+            changes,
+            location: revision.location,
+        }
+    }
+
+    pub fn marks(&self) -> &Marks {
+        &self.marks
+    }
+
+    pub fn marks_mut(&mut self) -> &mut Marks {
+        &mut self.marks
+    }
+
+    pub fn commit(&mut self) {
+        self.finish_edit();
+        self.recording = false;
+    }
+
+    /// return: where the undone change started
+    pub fn undo(&mut self) -> Option<Location> {
+        self.commit();
+        let revision = self.undo.pop_back()?;
+        let location = revision.location;
+        let redo = self.revert(revision);
+        self.redo.push(redo);
+        Some(location)
+    }
+
+    /// return: where the redone change started
+    pub fn redo(&mut self) -> Option<Location> {
+        self.commit();
+        let revision = self.redo.pop()?;
+        let location = revision.location;
+        let undo = self.revert(revision);
+        self.undo.push_back(undo);
+        Some(location)
+    }
+
     pub fn insert_char(&mut self, c: char, cursor: &super::cursor::Cursor) {
         if cursor.location().line_index == self.len() {
-            self.dirty = true;
+            self.record(cursor.location(), self.len()..self.len());
             self.lines.push(Line::from(c));
-        } else if let Some(line) = self.lines.get_mut(cursor.location().line_index) {
-            self.dirty = true;
-            line.insert_char(c, cursor.location().grapheme_index);
+        } else if cursor.location().line_index < self.len() {
+            self.record(
+                cursor.location(),
+                cursor.location().line_index..cursor.location().line_index + 1,
+            );
+            self.lines[cursor.location().line_index]
+                .insert_char(c, cursor.location().grapheme_index);
         }
+        self.syntax.changed(cursor.location().line_index);
     }
 
     pub fn delete(&mut self, cursor: &super::cursor::Cursor) {
@@ -45,54 +248,274 @@ impl Buffer {
             if cursor.location().grapheme_index >= line.grapheme_count()
                 && self.len() > cursor.location().line_index.saturating_add(1)
             {
-                self.dirty = true;
-                let next_line = self
-                    .lines
-                    .remove(cursor.location().line_index.saturating_add(1));
+                let line_index = cursor.location().line_index;
+                let joined_at = line.grapheme_count();
+                self.record(cursor.location(), line_index..line_index + 2);
+                let next_line = self.lines.remove(line_index.saturating_add(1));
 
-                self.lines[cursor.location().line_index].append(next_line);
+                self.lines[line_index].append(next_line);
+                self.syntax
+                    .removed(line_index.saturating_add(1)..line_index.saturating_add(2));
+                self.syntax.changed(line_index);
+                self.marks.adjust(|location| {
+                    Some(match location.line_index {
+                        idx if idx == line_index.saturating_add(1) => Location {
+                            grapheme_index: location.grapheme_index.saturating_add(joined_at),
+                            line_index,
+                        },
+                        idx if idx > line_index => Location {
+                            line_index: idx - 1,
+                            ..location
+                        },
+                        _ => location,
+                    })
+                });
             } else if cursor.location().grapheme_index < line.grapheme_count() {
-                self.dirty = true;
+                self.record(
+                    cursor.location(),
+                    cursor.location().line_index..cursor.location().line_index + 1,
+                );
                 self.lines[cursor.location().line_index].delete(cursor.location().grapheme_index);
+                self.syntax.changed(cursor.location().line_index);
             }
         }
     }
 
     pub fn insert_newline(&mut self, cursor: &super::cursor::Cursor) {
         if cursor.location().line_index == self.len() {
-            self.dirty = true;
+            self.record(cursor.location(), self.len()..self.len());
             self.lines.push(Line::default())
-        } else if let Some(line) = self.lines.get_mut(cursor.location().line_index) {
-            self.dirty = true;
-            let new = line.split(cursor.location().grapheme_index);
-            self.lines
-                .insert(cursor.location().line_index.saturating_add(1), new);
+        } else if cursor.location().line_index < self.len() {
+            self.record(
+                cursor.location(),
+                cursor.location().line_index..cursor.location().line_index + 1,
+            );
+            let Location {
+                grapheme_index: split_at,
+                line_index,
+            } = cursor.location();
+            let new = self.lines[line_index].split(split_at);
+            self.lines.insert(line_index.saturating_add(1), new);
+            self.syntax.changed(line_index);
+            self.syntax.inserted(line_index.saturating_add(1), 1);
+            self.marks.adjust(|location| {
+                Some(match location.line_index {
+                    idx if idx == line_index && location.grapheme_index >= split_at => Location {
+                        grapheme_index: location.grapheme_index - split_at,
+                        line_index: idx.saturating_add(1),
+                    },
+                    idx if idx > line_index => Location {
+                        line_index: idx.saturating_add(1),
+                        ..location
+                    },
+                    _ => location,
+                })
+            });
+        }
+    }
+
+    /// Inserts `text` at `location` as a single edit, each `\n` starts a new line.
+    ///
+    /// return: the location after the inserted text
+    pub fn insert_text(&mut self, location: Location, text: &str) -> Location {
+        if text.is_empty() || location.line_index > self.lines.len() {
+            return location;
+        }
+        self.record(location, location.line_index..location.line_index + 1);
+        if location.line_index == self.lines.len() {
+            self.lines.push(Line::default());
+        }
+
+        let Location {
+            grapheme_index: at,
+            line_index,
+        } = location;
+        let tail = self.lines[line_index].split(at);
+        let mut parts = text.split('\n');
+        self.lines[line_index].append(Line::from(parts.next().unwrap_or_default()));
+        let new_lines = parts.map(Line::from).collect::<Vec<_>>();
+        let added = new_lines.len();
+        let last = line_index.saturating_add(added);
+        self.lines.splice(
+            line_index.saturating_add(1)..line_index.saturating_add(1),
+            new_lines,
+        );
+
+        let end = Location {
+            grapheme_index: self.lines[last].grapheme_count(),
+            line_index: last,
+        };
+        self.lines[last].append(tail);
+        self.syntax.changed(line_index);
+        self.syntax.inserted(line_index.saturating_add(1), added);
+        self.marks.adjust(|mark| {
+            Some(match mark.line_index {
+                idx if idx == line_index && mark.grapheme_index >= at => Location {
+                    grapheme_index: mark.grapheme_index - at + end.grapheme_index,
+                    line_index: last,
+                },
+                idx if idx > line_index => Location {
+                    line_index: idx.saturating_add(added),
+                    ..mark
+                },
+                _ => mark,
+            })
+        });
+        end
+    }
+
+    fn byte_location(&self, location: Location) -> Option<(usize, usize)> {
+        let line = self.lines.get(location.line_index)?;
+        let byte_idx = line
+            .grapheme_index_to_byte_idx(location.grapheme_index)
+            .unwrap_or(line.len());
+        Some((location.line_index, byte_idx))
+    }
+
+    /// The text from `start` up to `end`, lines joined with `\n`.
+    pub fn text_in_range(&self, start: Location, end: Location) -> String {
+        let (Some((start_line, start_byte)), Some((end_line, end_byte))) =
+            (self.byte_location(start), self.byte_location(end))
+        else {
+            return String::new();
+        };
+        if (start_line, start_byte) >= (end_line, end_byte) {
+            return String::new();
+        }
+        if start_line == end_line {
+            return self.lines[start_line][start_byte..end_byte].to_string();
+        }
+
+        let mut text = self.lines[start_line][start_byte..].to_string();
+        for line in &self.lines[start_line.saturating_add(1)..end_line] {
+            text.push('\n');
+            text.push_str(line);
         }
+        text.push('\n');
+        text.push_str(&self.lines[end_line][..end_byte]);
+        text
+    }
+
+    /// return: the deleted text
+    pub fn delete_range(&mut self, start: Location, end: Location) -> String {
+        let text = self.text_in_range(start, end);
+        if text.is_empty() {
+            return text;
+        }
+
+        self.record(start, start.line_index..end.line_index.saturating_add(1));
+        let tail = self.lines[end.line_index].split(end.grapheme_index);
+        let line = &mut self.lines[start.line_index];
+        line.split(start.grapheme_index);
+        line.append(tail);
+        self.lines
+            .drain(start.line_index.saturating_add(1)..=end.line_index);
+        self.syntax.changed(start.line_index);
+        self.syntax
+            .removed(start.line_index.saturating_add(1)..end.line_index.saturating_add(1));
+
+        let removed_lines = end.line_index - start.line_index;
+        let key = |location: Location| (location.line_index, location.grapheme_index);
+        self.marks.adjust(|location| {
+            Some(if key(location) < key(start) {
+                location
+            } else if key(location) < key(end) {
+                start
+            } else if location.line_index == end.line_index {
+                Location {
+                    grapheme_index: location.grapheme_index - end.grapheme_index
+                        + start.grapheme_index,
+                    line_index: start.line_index,
+                }
+            } else {
+                Location {
+                    line_index: location.line_index - removed_lines,
+                    ..location
+                }
+            })
+        });
+        text
+    }
+
+    /// Finds the first match of `pattern` in line `line_idx` starting at or after `byte_idx`.
+    /// With `after_match` an empty match at `byte_idx` belongs to the match
+    /// ending there and is skipped.
+    ///
+    /// return: (bytes, grapheme_index)
+    pub fn find_match(
+        &self,
+        pattern: &Pattern,
+        line_idx: usize,
+        byte_idx: usize,
+        after_match: bool,
+    ) -> Option<(Range<usize>, usize)> {
+        self.lines
+            .get(line_idx)
+            .filter(|line| byte_idx <= line.len())
+            .and_then(|line| {
+                line.find_all(pattern, byte_idx..line.len())
+                    .find(|(bytes, _)| {
+                        !(after_match && bytes.is_empty() && bytes.start == byte_idx)
+                    })
+            })
+    }
+
+    /// `bytes` starts at `location`.
+    ///
+    /// return: the byte length of the inserted text
+    pub fn replace_match(
+        &mut self,
+        pattern: &Pattern,
+        location: Location,
+        bytes: Range<usize>,
+        replacement: &str,
+    ) -> usize {
+        let Some(line) = self.lines.get(location.line_index) else {
+            return 0;
+        };
+        let replace_with = pattern.expand(line, bytes.clone(), replacement);
+
+        self.record(location, location.line_index..location.line_index + 1);
+        self.lines[location.line_index].replace(bytes, &replace_with);
+        self.syntax.changed(location.line_index);
+        replace_with.len()
     }
 
     pub fn save(&mut self) -> anyhow::Result<()> {
+        if self.options.bool(OptionId::Readonly) {
+            anyhow::bail!("'readonly' option is set");
+        }
         if let Some(file) = &self.file {
-            let mut file = std::fs::File::create(file).context("create file")?;
-            for line in self.lines.iter() {
-                writeln!(file, "{line}").context("write to file")?;
-            }
+            self.write_to(file)?;
             self.dirty = false;
+            self.disk_time = self.file.as_deref().and_then(modified_time);
         }
         Ok(())
     }
 
     pub fn save_as(&mut self, path: &str) -> Result<(), anyhow::Error> {
         let path = PathBuf::from(path);
-        let mut file = std::fs::File::create(&path).context("create file")?;
-        for line in self.lines.iter() {
-            writeln!(file, "{line}").context("write to file")?;
-        }
+        self.write_to(&path)?;
+        self.disk_time = modified_time(&path);
         self.file = Some(path);
         self.dirty = false;
         Ok(())
     }
 
-    pub fn search_forward(&self, query: &str, location: Location) -> Option<Location> {
+    /// Writes the lines with the line endings of the `fileformat` option.
+    fn write_to(&self, path: &Path) -> anyhow::Result<()> {
+        let ending = match self.options.get(OptionId::Fileformat).as_str() {
+            "dos" => "\r\n",
+            _ => "\n",
+        };
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path).context("create file")?);
+        for line in self.lines.iter() {
+            write!(file, "{line}{ending}").context("write to file")?;
+        }
+        file.flush().context("write to file")
+    }
+
+    pub fn search_forward(&self, query: &Pattern, location: Location) -> Option<Location> {
         if query.is_empty() {
             return None;
         }
@@ -121,7 +544,7 @@ impl Buffer {
         None
     }
 
-    pub fn search_backward(&self, query: &str, location: Location) -> Option<Location> {
+    pub fn search_backward(&self, query: &Pattern, location: Location) -> Option<Location> {
         if query.is_empty() {
             return None;
         }
@@ -156,10 +579,445 @@ impl Buffer {
         None
     }
 
+    /// Stops counting at `limit`.
+    ///
+    /// return: (matches starting at or before `location`, all matches)
+    pub fn count_matches(
+        &self,
+        pattern: &Pattern,
+        location: Location,
+        limit: usize,
+    ) -> (usize, usize) {
+        let location = (location.line_index, location.grapheme_index);
+        let mut current = 0;
+        let mut total = 0;
+        for (line_index, line) in self.lines.iter().enumerate() {
+            for (_, grapheme_index) in line.find_all(pattern, 0..line.len()) {
+                if total >= limit {
+                    return (current, total);
+                }
+                total += 1;
+                if (line_index, grapheme_index) <= location {
+                    current = total;
+                }
+            }
+        }
+        (current, total)
+    }
+
+    /// The class of the grapheme at `location`, `None` at a line break.
+    fn class_at(&self, location: Location, big: bool) -> Option<CharClass> {
+        self.lines
+            .get(location.line_index)?
+            .grapheme_at(location.grapheme_index)
+            .map(|grapheme| CharClass::of(grapheme, big))
+    }
+
+    fn is_empty_line(&self, line_index: usize) -> bool {
+        self.lines
+            .get(line_index)
+            .is_some_and(|line| line.grapheme_count() == 0)
+    }
+
+    /// The next grapheme, stepping over the line break at the end of each line.
+    fn next_location(&self, location: Location) -> Option<Location> {
+        let line = self.lines.get(location.line_index)?;
+        if location.grapheme_index < line.grapheme_count() {
+            Some(Location {
+                grapheme_index: location.grapheme_index.saturating_add(1),
+                ..location
+            })
+        } else if location.line_index.saturating_add(1) < self.lines.len() {
+            Some(Location {
+                grapheme_index: 0,
+                line_index: location.line_index.saturating_add(1),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The previous grapheme, stepping over the line break at the end of each line.
+    fn previous_location(&self, location: Location) -> Option<Location> {
+        let line = self.lines.get(location.line_index)?;
+        if location.grapheme_index > 0 {
+            Some(Location {
+                grapheme_index: std::cmp::min(location.grapheme_index, line.grapheme_count())
+                    .saturating_sub(1),
+                ..location
+            })
+        } else {
+            let line_index = location.line_index.checked_sub(1)?;
+            Some(Location {
+                grapheme_index: self.lines[line_index].grapheme_count(),
+                line_index,
+            })
+        }
+    }
+
+    fn last_location(&self) -> Location {
+        let line_index = self.lines.len().saturating_sub(1);
+        Location {
+            grapheme_index: self
+                .lines
+                .get(line_index)
+                .map_or(0, |line| line.grapheme_count().saturating_sub(1)),
+            line_index,
+        }
+    }
+
+    /// Whether word motions skip over `location`: blanks and line breaks, but not
+    /// empty lines, which count as a word.
+    fn is_word_gap(&self, location: Location, big: bool) -> bool {
+        match self.class_at(location, big) {
+            Some(CharClass::Blank) => true,
+            None => !self.is_empty_line(location.line_index),
+            Some(_) => false,
+        }
+    }
+
+    pub fn next_word_start(&self, from: Location, big: bool) -> Location {
+        let find = || {
+            let mut location = from;
+            match self.class_at(location, big) {
+                Some(CharClass::Blank) => {}
+                None => location = self.next_location(location)?,
+                class => {
+                    while self.class_at(location, big) == class {
+                        location = self.next_location(location)?;
+                    }
+                }
+            }
+            while self.is_word_gap(location, big) {
+                location = self.next_location(location)?;
+            }
+            Some(location)
+        };
+        find().unwrap_or_else(|| self.last_location())
+    }
+
+    pub fn next_word_end(&self, from: Location, big: bool) -> Location {
+        let find = || {
+            let mut location = self.next_location(from)?;
+            while matches!(self.class_at(location, big), Some(CharClass::Blank) | None) {
+                location = self.next_location(location)?;
+            }
+            let class = self.class_at(location, big);
+            while let Some(next) = self.next_location(location)
+                && self.class_at(next, big) == class
+            {
+                location = next;
+            }
+            Some(location)
+        };
+        find().unwrap_or_else(|| self.last_location())
+    }
+
+    pub fn previous_word_start(&self, from: Location, big: bool) -> Location {
+        let find = || {
+            let mut location = self.previous_location(from)?;
+            while self.is_word_gap(location, big) {
+                location = self.previous_location(location)?;
+            }
+            let class = self.class_at(location, big);
+            if class.is_none() {
+                return Some(location);
+            }
+            while let Some(previous) = self.previous_location(location)
+                && self.class_at(previous, big) == class
+            {
+                location = previous;
+            }
+            Some(location)
+        };
+        find().unwrap_or_default()
+    }
+
+    pub fn previous_word_end(&self, from: Location, big: bool) -> Location {
+        let find = || {
+            let mut location = from;
+            match self.class_at(location, big) {
+                Some(CharClass::Blank) | None => location = self.previous_location(location)?,
+                class => {
+                    while self.class_at(location, big) == class {
+                        location = self.previous_location(location)?;
+                    }
+                }
+            }
+            while self.is_word_gap(location, big) {
+                location = self.previous_location(location)?;
+            }
+            Some(location)
+        };
+        find().unwrap_or_default()
+    }
+
+    pub fn next_paragraph(&self, from: Location) -> Location {
+        let mut line_index = from.line_index;
+        while line_index < self.lines.len() && self.is_empty_line(line_index) {
+            line_index = line_index.saturating_add(1);
+        }
+        while line_index < self.lines.len() && !self.is_empty_line(line_index) {
+            line_index = line_index.saturating_add(1);
+        }
+
+        if line_index < self.lines.len() {
+            Location {
+                grapheme_index: 0,
+                line_index,
+            }
+        } else {
+            self.last_location()
+        }
+    }
+
+    pub fn previous_paragraph(&self, from: Location) -> Location {
+        let mut line_index = from.line_index;
+        while line_index > 0 && self.is_empty_line(line_index) {
+            line_index -= 1;
+        }
+        while line_index > 0 && !self.is_empty_line(line_index) {
+            line_index -= 1;
+        }
+        Location {
+            grapheme_index: 0,
+            line_index,
+        }
+    }
+
+    /// A sentence starts at the first non-blank after a `.`, `!` or `?` which is
+    /// followed by blanks or a line break, optionally with closing `)`, `]`, `"`
+    /// or `'` in between. Paragraph boundaries also start a sentence.
+    fn is_sentence_start(&self, location: Location) -> bool {
+        if self.is_empty_line(location.line_index) {
+            return location.line_index == 0 || !self.is_empty_line(location.line_index - 1);
+        }
+        if matches!(
+            self.class_at(location, false),
+            Some(CharClass::Blank) | None
+        ) {
+            return false;
+        }
+
+        let mut previous = location;
+        let mut gap = false;
+        loop {
+            let Some(location) = self.previous_location(previous) else {
+                return true;
+            };
+            if self.is_empty_line(location.line_index) {
+                return true;
+            }
+            if !matches!(
+                self.class_at(location, false),
+                Some(CharClass::Blank) | None
+            ) {
+                break;
+            }
+            gap = true;
+            previous = location;
+        }
+        if !gap {
+            return false;
+        }
+
+        let mut location = self.previous_location(previous);
+        while let Some(current) = location {
+            match self.grapheme(current) {
+                Some(")" | "]" | "\"" | "'") => location = self.previous_location(current),
+                Some("." | "!" | "?") => return true,
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    pub fn next_sentence(&self, from: Location) -> Location {
+        let mut location = from;
+        while let Some(next) = self.next_location(location) {
+            location = next;
+            if self.is_sentence_start(location) {
+                return location;
+            }
+        }
+        self.last_location()
+    }
+
+    pub fn previous_sentence(&self, from: Location) -> Location {
+        let mut location = from;
+        while let Some(previous) = self.previous_location(location) {
+            location = previous;
+            if self.is_sentence_start(location) {
+                return location;
+            }
+        }
+        location
+    }
+
+    fn grapheme(&self, location: Location) -> Option<&str> {
+        self.lines
+            .get(location.line_index)?
+            .grapheme_at(location.grapheme_index)
+    }
+
+    /// Deletes whole lines, an empty buffer keeps a single empty line.
+    ///
+    /// return: the deleted lines, each ending with `\n`
+    pub fn delete_lines(&mut self, lines: Range<usize>) -> String {
+        let lines = lines.start..std::cmp::min(lines.end, self.lines.len());
+        if lines.is_empty() {
+            return String::new();
+        }
+
+        self.record(
+            Location {
+                grapheme_index: 0,
+                line_index: lines.start,
+            },
+            lines.clone(),
+        );
+        let text = self
+            .lines
+            .drain(lines.clone())
+            .map(|line| format!("{line}\n"))
+            .collect();
+        self.syntax.removed(lines.clone());
+        self.marks.adjust(|location| {
+            if location.line_index < lines.start {
+                Some(location)
+            } else if location.line_index < lines.end {
+                None
+            } else {
+                Some(Location {
+                    line_index: location.line_index - lines.len(),
+                    ..location
+                })
+            }
+        });
+        if self.lines.is_empty() {
+            self.lines.push(Line::default());
+        }
+        text
+    }
+
+    pub fn is_word_end(&self, location: Location, big: bool) -> bool {
+        self.next_location(location)
+            .is_none_or(|next| self.class_at(next, big) != self.class_at(location, big))
+    }
+
+    pub fn is_blank(&self, location: Location) -> bool {
+        matches!(
+            self.class_at(location, false),
+            Some(CharClass::Blank) | None
+        )
+    }
+
+    pub fn is_bracket(&self, location: Location) -> bool {
+        self.lines
+            .get(location.line_index)
+            .and_then(|line| line.grapheme_at(location.grapheme_index))
+            .and_then(Bracket::from_grapheme)
+            .is_some()
+    }
+
+    /// Finds the first bracket at or after `location` on its line and the bracket
+    /// it pairs with, ignoring brackets inside strings, chars and comments.
+    ///
+    /// Only `lines` are searched for the matching bracket.
+    ///
+    /// return: (bracket, matching bracket)
+    pub fn matching_bracket(
+        &mut self,
+        location: Location,
+        file_ty: FileType,
+        lines: Range<usize>,
+    ) -> Option<(Location, Location)> {
+        self.highlight_syntax(location.line_index, file_ty);
+
+        let (start, bracket) = self
+            .code_brackets(location.line_index)
+            .into_iter()
+            .find(|(grapheme_index, _)| *grapheme_index >= location.grapheme_index)?;
+        let from = Location {
+            grapheme_index: start,
+            line_index: location.line_index,
+        };
+
+        let mut depth = 0usize;
+        if bracket.open {
+            for line_index in location.line_index..lines.end.min(self.len()) {
+                self.highlight_syntax(line_index, file_ty);
+                for (grapheme_index, other) in self.code_brackets(line_index) {
+                    if (line_index == location.line_index && grapheme_index <= start)
+                        || other.kind != bracket.kind
+                    {
+                        continue;
+                    }
+                    if other.open {
+                        depth = depth.saturating_add(1);
+                    } else if depth == 0 {
+                        return Some((
+                            from,
+                            Location {
+                                grapheme_index,
+                                line_index,
+                            },
+                        ));
+                    } else {
+                        depth = depth.saturating_sub(1);
+                    }
+                }
+            }
+        } else {
+            for line_index in (lines.start..=location.line_index).rev() {
+                for (grapheme_index, other) in self.code_brackets(line_index).into_iter().rev() {
+                    if (line_index == location.line_index && grapheme_index >= start)
+                        || other.kind != bracket.kind
+                    {
+                        continue;
+                    }
+                    if !other.open {
+                        depth = depth.saturating_add(1);
+                    } else if depth == 0 {
+                        return Some((
+                            from,
+                            Location {
+                                grapheme_index,
+                                line_index,
+                            },
+                        ));
+                    } else {
+                        depth = depth.saturating_sub(1);
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// return: (grapheme_index, bracket) of every bracket outside literals and comments
+    fn code_brackets(&self, line_idx: usize) -> Vec<(usize, Bracket)> {
+        self.lines.get(line_idx).map_or_else(Vec::new, |line| {
+            line.grapheme_indices(true)
+                .enumerate()
+                .filter_map(|(grapheme_index, (byte_idx, grapheme))| {
+                    Bracket::from_grapheme(grapheme)
+                        .filter(|_| !self.syntax.is_literal(line_idx, byte_idx))
+                        .map(|bracket| (grapheme_index, bracket))
+                })
+                .collect()
+        })
+    }
+
     pub fn file(&self) -> Option<&Path> {
         self.file.as_deref()
     }
 
+    pub fn edits(&self) -> usize {
+        self.edits
+    }
+
     pub fn dirty(&self) -> bool {
         self.dirty
     }
@@ -169,12 +1027,25 @@ impl Buffer {
         line_idx: usize,
         range: Range<usize>,
         highlighter: &Highlighter,
+        tab_width: usize,
     ) -> Option<AnnotatedString> {
         self.lines.get(line_idx).map(|line| {
-            line.get_annotated_visiable_string(range, Some(&highlighter.get_annotations(line_idx)))
+            line.get_annotated_visiable_string(
+                range,
+                Some(&highlighter.get_annotations(line_idx)),
+                tab_width,
+            )
         })
     }
 
+    pub fn highlight_syntax(&mut self, through: usize, file_ty: FileType) {
+        self.syntax.update(&self.lines, file_ty, through);
+    }
+
+    pub fn syntax(&self) -> &SyntaxCache {
+        &self.syntax
+    }
+
     pub fn highlight(&self, line_idx: usize, highlighter: &mut Highlighter) {
         if let Some(line) = self.lines.get(line_idx) {
             highlighter.highlight(line_idx, line);
@@ -190,9 +1061,23 @@ impl Deref for Buffer {
     }
 }
 
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::editor::view::{buffer::Buffer, cursor::Location, line::Line};
+    use crate::editor::{
+        FileType,
+        options::{OptionId, Value},
+        view::{
+            buffer::Buffer,
+            cursor::{Cursor, Location},
+            line::Line,
+        },
+    };
 
     impl Buffer {
         pub fn new(lines: Vec<Line>) -> Self {
@@ -203,6 +1088,13 @@ mod tests {
         }
     }
 
+    fn at(line_index: usize, grapheme_index: usize) -> Location {
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+
     #[test]
     fn test_search() {
         let buffer = Buffer {
@@ -211,7 +1103,7 @@ mod tests {
         };
         assert_eq!(
             buffer.search_forward(
-                "new",
+                &"new".into(),
                 Location {
                     grapheme_index: 0,
                     line_index: 0
@@ -225,7 +1117,7 @@ mod tests {
 
         assert_eq!(
             buffer.search_backward(
-                "new",
+                &"new".into(),
                 Location {
                     grapheme_index: 23,
                     line_index: 0
@@ -246,7 +1138,7 @@ mod tests {
         };
         assert_eq!(
             buffer.search_forward(
-                "new",
+                &"new".into(),
                 Location {
                     grapheme_index: 0,
                     line_index: 0
@@ -260,7 +1152,7 @@ mod tests {
 
         assert_eq!(
             buffer.search_backward(
-                "new",
+                &"new".into(),
                 Location {
                     grapheme_index: 0,
                     line_index: 0
@@ -272,4 +1164,315 @@ mod tests {
             })
         )
     }
+
+    #[test]
+    fn matching_bracket() {
+        let mut buffer = Buffer::new(vec![
+            Line::from("fn main() {"),
+            Line::from("    let s = \"}\"; // )"),
+            Line::from("    foo(']', [1, 2]);"),
+            Line::from("}"),
+        ]);
+
+        assert_eq!(
+            buffer.matching_bracket(at(0, 10), FileType::Rust, 0..4),
+            Some((at(0, 10), at(3, 0)))
+        );
+        assert_eq!(
+            buffer.matching_bracket(at(3, 0), FileType::Rust, 0..4),
+            Some((at(3, 0), at(0, 10)))
+        );
+        // Searches forward on the line when the cursor is not on a bracket.
+        assert_eq!(
+            buffer.matching_bracket(at(2, 0), FileType::Rust, 0..4),
+            Some((at(2, 7), at(2, 19)))
+        );
+        assert_eq!(
+            buffer.matching_bracket(at(2, 12), FileType::Rust, 0..4),
+            Some((at(2, 13), at(2, 18)))
+        );
+        // Plain text has no notion of literals, so the quoted brace counts.
+        assert_eq!(
+            buffer.matching_bracket(at(0, 10), FileType::Text, 0..4),
+            Some((at(0, 10), at(1, 13)))
+        );
+        assert_eq!(
+            buffer.matching_bracket(at(1, 0), FileType::Rust, 0..4),
+            None
+        );
+        // The match has to be within `lines`.
+        assert_eq!(
+            buffer.matching_bracket(at(0, 10), FileType::Rust, 0..3),
+            None
+        );
+        assert_eq!(
+            buffer.matching_bracket(at(3, 0), FileType::Rust, 1..4),
+            None
+        );
+
+        assert!(buffer.is_bracket(at(0, 7)));
+        assert!(!buffer.is_bracket(at(0, 0)));
+        assert!(!buffer.is_bracket(at(0, 11)));
+    }
+
+    #[test]
+    fn delete_range() {
+        let mut buffer = Buffer::new(vec![
+            Line::from("fn main() {"),
+            Line::from("    let a = 1;"),
+            Line::from("}"),
+        ]);
+
+        assert_eq!(buffer.text_in_range(at(0, 3), at(0, 7)), "main");
+        assert_eq!(buffer.delete_range(at(1, 8), at(1, 12)), "a = ");
+        assert_eq!(buffer.lines[1].to_string(), "    let 1;");
+
+        assert_eq!(buffer.delete_range(at(0, 9), at(2, 0)), " {\n    let 1;\n");
+        assert_eq!(buffer.lines.len(), 1);
+        assert_eq!(buffer.lines[0].to_string(), "fn main()}");
+
+        assert_eq!(buffer.delete_range(at(0, 4), at(0, 2)), "");
+        buffer.commit();
+        assert_eq!(buffer.undo(), Some(at(1, 8)));
+        assert_eq!(buffer.lines.len(), 3);
+    }
+
+    #[test]
+    fn word_motions() {
+        let buffer = Buffer::new(vec![
+            Line::from("foo.bar(baz,\tqux);"),
+            Line::from(""),
+            Line::from("  let été = 1;"),
+        ]);
+        let walk = |motion: &dyn Fn(Location) -> Location, from: Location, steps: usize| {
+            (0..steps)
+                .scan(from, |location, _| {
+                    *location = motion(*location);
+                    Some((location.line_index, location.grapheme_index))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            walk(&|l| buffer.next_word_start(l, false), at(0, 0), 9),
+            [
+                (0, 3),
+                (0, 4),
+                (0, 7),
+                (0, 8),
+                (0, 11),
+                (0, 13),
+                (0, 16),
+                (1, 0),
+                (2, 2)
+            ]
+        );
+        assert_eq!(
+            walk(&|l| buffer.next_word_start(l, true), at(0, 0), 4),
+            [(0, 13), (1, 0), (2, 2), (2, 6)]
+        );
+        assert_eq!(
+            walk(&|l| buffer.next_word_end(l, false), at(0, 14), 4),
+            [(0, 15), (0, 17), (2, 4), (2, 8)]
+        );
+        assert_eq!(
+            walk(&|l| buffer.previous_word_start(l, false), at(2, 6), 4),
+            [(2, 2), (1, 0), (0, 16), (0, 13)]
+        );
+        assert_eq!(
+            walk(&|l| buffer.previous_word_end(l, false), at(2, 3), 3),
+            [(1, 0), (0, 17), (0, 15)]
+        );
+        assert_eq!(
+            walk(&|l| buffer.previous_word_end(l, true), at(0, 15), 1),
+            [(0, 11)]
+        );
+
+        assert_eq!(buffer.next_word_start(at(2, 13), false), at(2, 13));
+        assert_eq!(buffer.previous_word_start(at(0, 2), false), at(0, 0));
+    }
+
+    #[test]
+    fn paragraph_and_sentence_motions() {
+        let buffer = Buffer::new(vec![
+            Line::from("First one. Second (really!)  Third?"),
+            Line::from("Next line"),
+            Line::from(""),
+            Line::from(""),
+            Line::from("e.g. the end"),
+        ]);
+
+        assert_eq!(buffer.next_paragraph(at(0, 4)), at(2, 0));
+        assert_eq!(buffer.next_paragraph(at(2, 0)), at(4, 11));
+        assert_eq!(buffer.previous_paragraph(at(4, 3)), at(3, 0));
+        assert_eq!(buffer.previous_paragraph(at(3, 0)), at(0, 0));
+
+        let mut sentences = Vec::new();
+        let mut location = at(0, 0);
+        for _ in 0..7 {
+            location = buffer.next_sentence(location);
+            sentences.push((location.line_index, location.grapheme_index));
+        }
+        assert_eq!(
+            sentences,
+            [(0, 11), (0, 29), (1, 0), (2, 0), (4, 0), (4, 5), (4, 11)]
+        );
+
+        assert_eq!(buffer.previous_sentence(at(1, 4)), at(1, 0));
+        assert_eq!(buffer.previous_sentence(at(0, 29)), at(0, 11));
+        assert_eq!(buffer.previous_sentence(at(0, 5)), at(0, 0));
+    }
+
+    #[test]
+    fn marks_follow_their_text() {
+        let mut buffer = Buffer::new(vec![
+            Line::from("one"),
+            Line::from("two words"),
+            Line::from("three"),
+            Line::from("four"),
+        ]);
+        buffer.marks_mut().set('a', at(1, 4));
+        buffer.marks_mut().set('b', at(2, 1));
+        buffer.marks_mut().set('C', at(3, 0));
+
+        buffer.insert_newline(&Cursor::new(at(1, 3)));
+        assert_eq!(buffer.marks().get('a'), Some(at(2, 1)));
+        assert_eq!(buffer.marks().get('C'), Some(at(4, 0)));
+
+        buffer.delete(&Cursor::new(at(1, 3)));
+        assert_eq!(buffer.marks().get('a'), Some(at(1, 4)));
+        assert_eq!(buffer.marks().get('b'), Some(at(2, 1)));
+
+        buffer.delete_range(at(0, 1), at(1, 2));
+        assert_eq!(buffer.marks().get('a'), Some(at(0, 3)));
+        assert_eq!(buffer.lines[0].to_string(), "oo words");
+
+        buffer.delete_lines(1..2);
+        assert_eq!(buffer.marks().get('b'), None);
+        assert_eq!(buffer.marks().get('C'), Some(at(1, 0)));
+        assert_eq!(buffer.lines[1].to_string(), "four");
+    }
+
+    #[test]
+    fn insert_text() {
+        let mut buffer = Buffer::new(vec![Line::from("fn main() {}"), Line::from("// end")]);
+        buffer.marks_mut().set('a', at(0, 11));
+        buffer.marks_mut().set('b', at(1, 0));
+
+        let end = buffer.insert_text(at(0, 11), "\n    let x = 1;\n");
+        assert_eq!(end, at(2, 0));
+        assert_eq!(
+            buffer
+                .lines
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            ["fn main() {", "    let x = 1;", "}", "// end"]
+        );
+        assert_eq!(buffer.marks().get('a'), Some(at(2, 0)));
+        assert_eq!(buffer.marks().get('b'), Some(at(3, 0)));
+
+        assert_eq!(buffer.insert_text(at(3, 2), "!"), at(3, 3));
+        assert_eq!(buffer.lines[3].to_string(), "//! end");
+
+        // Both pastes belong to the same undo step, as they would in one insert.
+        buffer.undo();
+        assert_eq!(buffer.lines[0].to_string(), "fn main() {}");
+        assert_eq!(buffer.lines[1].to_string(), "// end");
+    }
+
+    #[test]
+    fn detect_disk_changes() {
+        let path = std::env::temp_dir().join(format!("oxide-disk-{}.txt", std::process::id()));
+        std::fs::write(&path, "one\n").unwrap();
+        let mut buffer = Buffer::default();
+        buffer.load(path.clone()).unwrap();
+        assert!(!buffer.check_disk_change());
+
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap();
+        assert!(buffer.check_disk_change());
+        assert!(!buffer.check_disk_change());
+
+        buffer.save().unwrap();
+        assert!(!buffer.check_disk_change());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn keep_dos_line_endings() {
+        let path = std::env::temp_dir().join(format!("oxide-dos-{}.txt", std::process::id()));
+        std::fs::write(&path, "one\r\ntwo\r\n").unwrap();
+        let mut buffer = Buffer::default();
+        buffer.load(path.clone()).unwrap();
+        assert_eq!(buffer.lines[0].to_string(), "one");
+        assert_eq!(buffer.options().get(OptionId::Fileformat).as_str(), "dos");
+
+        buffer.save().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\r\ntwo\r\n");
+        buffer
+            .options_mut()
+            .set(OptionId::Fileformat, Value::String("unix".into()));
+        buffer.save().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn undo_groups_edits() {
+        let mut buffer = Buffer::new(vec![Line::from("ab")]);
+        let cursor = |grapheme_index| Cursor::new(at(0, grapheme_index));
+
+        buffer.insert_char('x', &cursor(1));
+        buffer.insert_char('y', &cursor(2));
+        buffer.commit();
+        buffer.insert_newline(&cursor(1));
+        buffer.commit();
+        assert_eq!(buffer.len(), 2);
+
+        assert_eq!(buffer.undo(), Some(at(0, 1)));
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(buffer[0].to_string(), "axyb");
+        assert!(buffer.undo().is_some());
+        assert_eq!(buffer[0].to_string(), "ab");
+        assert!(buffer.undo().is_none());
+
+        assert!(buffer.redo().is_some());
+        assert_eq!(buffer[0].to_string(), "axyb");
+
+        // A new edit discards the redo history.
+        buffer.delete(&cursor(0));
+        assert!(buffer.redo().is_none());
+        assert_eq!(buffer[0].to_string(), "xyb");
+    }
+
+    #[test]
+    fn undo_keeps_only_changed_lines() {
+        let lines = (0..100).map(|idx| idx.to_string()).collect::<Vec<_>>();
+        let mut buffer = Buffer::new(lines.iter().map(|line| Line::from(line.as_str())).collect());
+        let text = |buffer: &Buffer| buffer.lines.iter().map(Line::to_string).collect::<Vec<_>>();
+        let cursor = |line_index, grapheme_index| Cursor::new(at(line_index, grapheme_index));
+
+        buffer.insert_char('x', &cursor(50, 0));
+        buffer.insert_char('y', &cursor(50, 1));
+        buffer.insert_newline(&cursor(50, 1));
+        buffer.delete(&cursor(51, 0));
+        buffer.delete_lines(10..12);
+        buffer.commit();
+        let edited = text(&buffer);
+
+        let changes = &buffer.undo[0].changes;
+        assert_eq!(changes.len(), 2);
+        assert_eq!((changes[0].start, changes[0].new_len), (50, 2));
+        assert_eq!(changes[0].old.len(), 1);
+        assert_eq!((changes[1].old.len(), changes[1].new_len), (2, 0));
+
+        assert!(buffer.undo().is_some());
+        assert_eq!(text(&buffer), lines);
+        assert!(buffer.redo().is_some());
+        assert_eq!(text(&buffer), edited);
+        assert_eq!(buffer[48].to_string(), "x");
+        assert_eq!(buffer[49].to_string(), "50");
+    }
 }
@@ -1,40 +1,44 @@
+pub use crate::editor::view::highlighter::cache::SyntaxCache;
 use crate::editor::{
-    FileType,
     annotated::annotation::Annotation,
     view::{
         cursor::Location,
         highlighter::{
-            rust::RustHighlighter, search::SearchHighlighter, syntax_highlight::SyntaxHighlighter,
+            bracket::BracketHighlighter, search::SearchHighlighter,
+            selection::SelectionHighlighter, syntax_highlight::SyntaxHighlighter,
         },
         line::Line,
+        pattern::Pattern,
     },
 };
 
+mod bracket;
+mod cache;
 mod rust;
 mod search;
+mod selection;
 mod syntax_highlight;
 
 pub struct Highlighter<'a> {
-    syntax: Option<Box<dyn SyntaxHighlighter>>,
+    syntax: &'a SyntaxCache,
     search: SearchHighlighter<'a>,
-}
-
-fn create_syntax_highlighter(file_ty: FileType) -> Option<Box<dyn SyntaxHighlighter>> {
-    match file_ty {
-        FileType::Rust => Some(Box::<RustHighlighter>::default()),
-        FileType::Text => None,
-    }
+    bracket: BracketHighlighter,
+    selection: SelectionHighlighter,
 }
 
 impl<'a> Highlighter<'a> {
     pub fn new(
-        match_word: Option<&'a str>,
+        match_word: Option<&'a Pattern>,
         selected_match: Option<Location>,
-        file_ty: FileType,
+        bracket_pair: Option<(Location, Location)>,
+        selection: Option<(Location, Location)>,
+        syntax: &'a SyntaxCache,
     ) -> Self {
         Self {
-            syntax: create_syntax_highlighter(file_ty),
+            syntax,
             search: SearchHighlighter::new(match_word, selected_match),
+            bracket: BracketHighlighter::new(bracket_pair),
+            selection: SelectionHighlighter::new(selection),
         }
     }
 
@@ -44,22 +48,31 @@ impl<'a> Highlighter<'a> {
         } else {
             vec![]
         };
-        if let Some(ref syntax_highlight) = self.syntax {
-            annotations.extend(
-                syntax_highlight
-                    .get_annotations(line_idx)
-                    .cloned()
-                    .unwrap_or_default(),
-            );
-        }
+        annotations.extend(
+            self.syntax
+                .get_annotations(line_idx)
+                .cloned()
+                .unwrap_or_default(),
+        );
+        annotations.extend(
+            self.bracket
+                .get_annotations(line_idx)
+                .cloned()
+                .unwrap_or_default(),
+        );
+        annotations.extend(
+            self.selection
+                .get_annotations(line_idx)
+                .cloned()
+                .unwrap_or_default(),
+        );
 
         annotations
     }
 
     pub fn highlight(&mut self, idx: usize, line: &Line) {
-        if let Some(ref mut syntax_highlight) = self.syntax {
-            syntax_highlight.highlight(idx, line);
-        }
         self.search.highlight(idx, line);
+        self.bracket.highlight(idx, line);
+        self.selection.highlight(idx, line);
     }
 }
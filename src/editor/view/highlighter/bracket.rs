@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+
+use crate::editor::{
+    annotated::annotation::{Annotation, AnnotationType},
+    view::{cursor::Location, highlighter::syntax_highlight::SyntaxHighlighter, line::Line},
+};
+
+pub struct BracketHighlighter {
+    pair: Option<(Location, Location)>,
+    highlights: HashMap<usize, Vec<Annotation>>,
+}
+
+impl BracketHighlighter {
+    pub fn new(pair: Option<(Location, Location)>) -> Self {
+        Self {
+            pair,
+            highlights: HashMap::new(),
+        }
+    }
+
+    fn highlight_bracket(line: &Line, location: Location, res: &mut Vec<Annotation>) {
+        if let Some(start) = line.grapheme_index_to_byte_idx(location.grapheme_index) {
+            res.push(Annotation {
+                annotation_type: AnnotationType::MatchingBracket,
+                bytes: start..start.saturating_add(1),
+            });
+        }
+    }
+}
+
+impl SyntaxHighlighter for BracketHighlighter {
+    fn highlight(&mut self, idx: usize, line: &Line) {
+        let mut res = Vec::new();
+
+        if let Some((open, close)) = self.pair {
+            for location in [open, close] {
+                if location.line_index == idx {
+                    Self::highlight_bracket(line, location, &mut res);
+                }
+            }
+        }
+
+        self.highlights.insert(idx, res);
+    }
+
+    fn get_annotations(&self, line_idx: usize) -> Option<&Vec<Annotation>> {
+        self.highlights.get(&line_idx)
+    }
+}
@@ -0,0 +1,151 @@
+use std::ops::Range;
+
+use crate::editor::{
+    FileType,
+    annotated::annotation::{Annotation, AnnotationType},
+    view::{
+        highlighter::rust::{self, RustState},
+        line::Line,
+    },
+};
+
+struct CachedLine {
+    /// The state the line was highlighted from.
+    start: RustState,
+    end: RustState,
+    annotations: Vec<Annotation>,
+}
+
+/// Syntax annotations of a buffer's lines, kept between frames.
+///
+/// Every line is highlighted from the state the line before ended in. An edit
+/// only drops the lines it touched, the lines after them are highlighted again
+/// only while they start in a different state than before.
+#[derive(Default)]
+pub struct SyntaxCache {
+    file_ty: FileType,
+    lines: Vec<Option<CachedLine>>,
+    /// The lines before this one are up to date.
+    valid: usize,
+}
+
+impl SyntaxCache {
+    pub fn reset(&mut self) {
+        self.lines.clear();
+        self.valid = 0;
+    }
+
+    pub fn changed(&mut self, line_idx: usize) {
+        if let Some(line) = self.lines.get_mut(line_idx) {
+            *line = None;
+        }
+        self.valid = self.valid.min(line_idx);
+    }
+
+    pub fn inserted(&mut self, at: usize, count: usize) {
+        let at = at.min(self.lines.len());
+        self.lines
+            .splice(at..at, std::iter::repeat_with(|| None).take(count));
+        self.valid = self.valid.min(at);
+    }
+
+    pub fn removed(&mut self, lines: Range<usize>) {
+        let end = lines.end.min(self.lines.len());
+        let start = lines.start.min(end);
+        self.lines.drain(start..end);
+        self.valid = self.valid.min(start);
+    }
+
+    pub fn update(&mut self, lines: &[Line], file_ty: FileType, through: usize) {
+        if file_ty != self.file_ty {
+            self.file_ty = file_ty;
+            self.reset();
+        }
+        if file_ty != FileType::Rust {
+            return;
+        }
+        self.lines.resize_with(lines.len(), || None);
+
+        let end = through.saturating_add(1).min(lines.len());
+        while self.valid < end {
+            let idx = self.valid;
+            let start = idx
+                .checked_sub(1)
+                .and_then(|prev| self.lines[prev].as_ref())
+                .map_or_else(RustState::default, |prev| prev.end);
+            if self.lines[idx]
+                .as_ref()
+                .is_none_or(|cached| cached.start != start)
+            {
+                let (annotations, end) = rust::highlight_line(start, &lines[idx]);
+                self.lines[idx] = Some(CachedLine {
+                    start,
+                    end,
+                    annotations,
+                });
+            }
+            self.valid = idx.saturating_add(1);
+        }
+    }
+
+    /// `line_idx` must be up to date.
+    pub fn get_annotations(&self, line_idx: usize) -> Option<&Vec<Annotation>> {
+        (line_idx < self.valid)
+            .then(|| self.lines.get(line_idx)?.as_ref())
+            .flatten()
+            .map(|cached| &cached.annotations)
+    }
+
+    /// Whether the byte at `byte_idx` lies in a string, char literal or comment.
+    /// `line_idx` must be up to date.
+    pub fn is_literal(&self, line_idx: usize, byte_idx: usize) -> bool {
+        self.get_annotations(line_idx).is_some_and(|annotations| {
+            annotations.iter().any(|annotation| {
+                matches!(
+                    annotation.annotation_type,
+                    AnnotationType::String | AnnotationType::Char | AnnotationType::Comment
+                ) && annotation.bytes.contains(&byte_idx)
+            })
+        })
+    }
+}
+
+#[test]
+fn rehighlight_until_the_state_converges() {
+    let mut lines = ["let a = 1;", "let b = 2;", "let c = 3;", "let d = 4;"]
+        .map(Line::from)
+        .to_vec();
+    let comment = |cache: &SyntaxCache, line_idx| {
+        cache.get_annotations(line_idx).is_some_and(|annotations| {
+            annotations
+                .iter()
+                .any(|annotation| matches!(annotation.annotation_type, AnnotationType::Comment))
+        })
+    };
+    let mut cache = SyntaxCache::default();
+    cache.update(&lines, FileType::Rust, 1);
+    assert!(cache.get_annotations(1).is_some());
+    assert!(cache.get_annotations(2).is_none());
+
+    cache.update(&lines, FileType::Rust, 3);
+    lines[1] = Line::from("let b = 2; /* open");
+    cache.changed(1);
+    cache.update(&lines, FileType::Rust, 3);
+    assert!(!comment(&cache, 0));
+    assert!((1..4).all(|line_idx| comment(&cache, line_idx)));
+
+    lines.insert(2, Line::from("close */"));
+    cache.inserted(2, 1);
+    cache.update(&lines, FileType::Rust, 4);
+    assert!(comment(&cache, 2));
+    assert!(!comment(&cache, 3) && !comment(&cache, 4));
+
+    lines.drain(1..3);
+    cache.removed(1..3);
+    cache.update(&lines, FileType::Rust, 2);
+    assert!((0..3).all(|line_idx| !comment(&cache, line_idx)));
+    assert!(!cache.is_literal(0, 0));
+
+    cache.update(&lines, FileType::Text, 2);
+    assert!(cache.get_annotations(0).is_none());
+}
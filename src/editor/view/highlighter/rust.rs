@@ -2,7 +2,7 @@ use unicode_segmentation::UnicodeSegmentation;
 
 use crate::editor::{
     annotated::annotation::{Annotation, AnnotationType},
-    view::{highlighter::syntax_highlight::SyntaxHighlighter, line::Line},
+    view::line::Line,
 };
 
 const KEYWORDS: [&str; 52] = [
@@ -65,13 +65,24 @@ const TYPES: [&str; 23] = [
     "f64", "bool", "char", "Option", "Result", "String", "str", "Vec", "HashMap", "VecDeque",
 ];
 
-#[derive(Default)]
-pub struct RustHighlighter {
-    highlights: Vec<Vec<Annotation>>,
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RustState {
     multiple_lines_comment: usize,
     multiple_str: bool,
 }
 
+struct RustHighlighter {
+    state: RustState,
+}
+
+/// return: (annotations, the state at the end of the line)
+pub fn highlight_line(state: RustState, line: &Line) -> (Vec<Annotation>, RustState) {
+    let mut highlighter = RustHighlighter { state };
+    let mut annotations = Vec::new();
+    highlighter.highlight(line, &mut annotations);
+    (annotations, highlighter.state)
+}
+
 fn is_numeric_literal(input: &str) -> bool {
     if input.len() < 3 {
         return false;
@@ -149,9 +160,9 @@ fn is_valid_number(input: &str) -> bool {
 
 impl RustHighlighter {
     fn initial_annotation(&mut self, line: &Line) -> Option<Annotation> {
-        if self.multiple_str {
+        if self.state.multiple_str {
             self.annotate_multiple_str(line)
-        } else if self.multiple_lines_comment > 0 {
+        } else if self.state.multiple_lines_comment > 0 {
             self.annotate_multiple_comment(line)
         } else {
             None
@@ -201,27 +212,27 @@ impl RustHighlighter {
     fn annotate_multiple_str(&mut self, input: &str) -> Option<Annotation> {
         let mut chars = input.char_indices();
         while let Some((idx, c)) = chars.next() {
-            if c == '\\' && self.multiple_str {
+            if c == '\\' && self.state.multiple_str {
                 chars.next();
                 continue;
             }
             if c == '"' {
-                if self.multiple_str {
-                    self.multiple_str = false;
+                if self.state.multiple_str {
+                    self.state.multiple_str = false;
                     return Some(Annotation {
                         annotation_type: AnnotationType::String,
                         bytes: 0..idx.saturating_add(1),
                     });
                 }
-                self.multiple_str = true;
+                self.state.multiple_str = true;
             }
 
-            if !self.multiple_str {
+            if !self.state.multiple_str {
                 return None;
             }
         }
 
-        self.multiple_str.then_some(Annotation {
+        self.state.multiple_str.then_some(Annotation {
             annotation_type: AnnotationType::String,
             bytes: 0..input.len(),
         })
@@ -234,16 +245,18 @@ impl RustHighlighter {
             if c == '/'
                 && let Some((_, '*')) = chars.peek()
             {
-                self.multiple_lines_comment = self.multiple_lines_comment.saturating_add(1);
+                self.state.multiple_lines_comment =
+                    self.state.multiple_lines_comment.saturating_add(1);
                 chars.next();
-            } else if self.multiple_lines_comment == 0 {
+            } else if self.state.multiple_lines_comment == 0 {
                 return None;
             } else if c == '*'
                 && let Some((idx, '/')) = chars.peek()
             {
-                self.multiple_lines_comment = self.multiple_lines_comment.saturating_sub(1);
+                self.state.multiple_lines_comment =
+                    self.state.multiple_lines_comment.saturating_sub(1);
 
-                if self.multiple_lines_comment == 0 {
+                if self.state.multiple_lines_comment == 0 {
                     return Some(Annotation {
                         annotation_type: AnnotationType::Comment,
                         bytes: 0..idx.saturating_add(1),
@@ -254,7 +267,7 @@ impl RustHighlighter {
             }
         }
 
-        (self.multiple_lines_comment > 0).then_some(Annotation {
+        (self.state.multiple_lines_comment > 0).then_some(Annotation {
             annotation_type: AnnotationType::Comment,
             bytes: 0..input.len(),
         })
@@ -334,16 +347,3 @@ fn annotate_comment(input: &str) -> Option<Annotation> {
 
     None
 }
-
-impl SyntaxHighlighter for RustHighlighter {
-    fn highlight(&mut self, _idx: usize, line: &crate::editor::view::line::Line) {
-        let mut res = vec![];
-
-        self.highlight(line, &mut res);
-        self.highlights.push(res);
-    }
-
-    fn get_annotations(&self, line_idx: usize) -> Option<&Vec<Annotation>> {
-        self.highlights.get(line_idx)
-    }
-}
@@ -2,17 +2,20 @@ use std::collections::HashMap;
 
 use crate::editor::{
     annotated::annotation::{Annotation, AnnotationType},
-    view::{cursor::Location, highlighter::syntax_highlight::SyntaxHighlighter, line::Line},
+    view::{
+        cursor::Location, highlighter::syntax_highlight::SyntaxHighlighter, line::Line,
+        pattern::Pattern,
+    },
 };
 
 pub struct SearchHighlighter<'a> {
-    match_word: Option<&'a str>,
+    match_word: Option<&'a Pattern>,
     selected_match: Option<Location>,
     highlights: HashMap<usize, Vec<Annotation>>,
 }
 
 impl<'a> SearchHighlighter<'a> {
-    pub fn new(match_word: Option<&'a str>, selected_match: Option<Location>) -> Self {
+    pub fn new(match_word: Option<&'a Pattern>, selected_match: Option<Location>) -> Self {
         Self {
             match_word,
             selected_match,
@@ -22,10 +25,10 @@ impl<'a> SearchHighlighter<'a> {
 
     fn highlight_match(&mut self, line: &Line, res: &mut Vec<Annotation>) {
         if let Some(word) = self.match_word {
-            line.find_all(word, 0..line.len()).for_each(|(start, _)| {
+            line.find_all(word, 0..line.len()).for_each(|(bytes, _)| {
                 res.push(Annotation {
                     annotation_type: AnnotationType::Match,
-                    bytes: start..start.saturating_add(word.len()),
+                    bytes,
                 });
             });
         }
@@ -36,10 +39,13 @@ impl<'a> SearchHighlighter<'a> {
             && let Some(match_word) = self.match_word
             && !match_word.is_empty()
             && let Some(start) = line.grapheme_index_to_byte_idx(selected_match.grapheme_index)
+            && let Some((bytes, _)) = line
+                .find_all(match_word, start..start.saturating_add(1))
+                .find(|(bytes, _)| bytes.start == start)
         {
             res.push(Annotation {
                 annotation_type: AnnotationType::SelectedMatch,
-                bytes: start..start.saturating_add(match_word.len()),
+                bytes,
             });
         }
     }
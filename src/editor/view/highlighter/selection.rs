@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use crate::editor::{
+    annotated::annotation::{Annotation, AnnotationType},
+    view::{cursor::Location, highlighter::syntax_highlight::SyntaxHighlighter, line::Line},
+};
+
+pub struct SelectionHighlighter {
+    /// (start, end), the end is not selected
+    range: Option<(Location, Location)>,
+    highlights: HashMap<usize, Vec<Annotation>>,
+}
+
+impl SelectionHighlighter {
+    pub fn new(range: Option<(Location, Location)>) -> Self {
+        Self {
+            range,
+            highlights: HashMap::new(),
+        }
+    }
+}
+
+impl SyntaxHighlighter for SelectionHighlighter {
+    fn highlight(&mut self, idx: usize, line: &Line) {
+        let mut res = Vec::new();
+
+        if let Some((start, end)) = self.range
+            && (start.line_index..=end.line_index).contains(&idx)
+        {
+            let byte_idx = |grapheme_index| {
+                line.grapheme_index_to_byte_idx(grapheme_index)
+                    .unwrap_or(line.len())
+            };
+            let from = if idx == start.line_index {
+                byte_idx(start.grapheme_index)
+            } else {
+                0
+            };
+            let to = if idx == end.line_index {
+                byte_idx(end.grapheme_index)
+            } else {
+                line.len()
+            };
+            if from < to {
+                res.push(Annotation {
+                    annotation_type: AnnotationType::Selection,
+                    bytes: from..to,
+                });
+            }
+        }
+
+        self.highlights.insert(idx, res);
+    }
+
+    fn get_annotations(&self, line_idx: usize) -> Option<&Vec<Annotation>> {
+        self.highlights.get(&line_idx)
+    }
+}
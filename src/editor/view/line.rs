@@ -1,37 +1,37 @@
 use std::{
     fmt::Display,
-    ops::{Add, Deref, Range, Sub},
+    ops::{Deref, Range},
 };
 
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
-use crate::editor::annotated::{AnnotatedString, annotation::Annotation};
+use crate::editor::{
+    annotated::{AnnotatedString, annotation::Annotation},
+    view::pattern::Pattern,
+};
+
+#[cfg(test)]
+use crate::editor::view::pattern::SearchOptions;
 
 #[derive(Debug, Clone, Copy)]
 enum GraphemeWidth {
     Half,
     Full,
+    /// Up to the next multiple of the tab width.
+    Tab,
 }
 
-impl Add<usize> for GraphemeWidth {
-    type Output = usize;
-
-    fn add(self, rhs: usize) -> Self::Output {
+impl GraphemeWidth {
+    /// The columns taken when drawn from column `col`.
+    fn at(self, col: usize, tab_width: usize) -> usize {
         match self {
-            GraphemeWidth::Half => rhs.saturating_add(1),
-            GraphemeWidth::Full => rhs.saturating_add(2),
-        }
-    }
-}
-
-impl Sub<GraphemeWidth> for usize {
-    type Output = usize;
-
-    fn sub(self, rhs: GraphemeWidth) -> Self::Output {
-        match rhs {
-            GraphemeWidth::Half => self.saturating_sub(1),
-            GraphemeWidth::Full => self.saturating_sub(2),
+            GraphemeWidth::Half => 1,
+            GraphemeWidth::Full => 2,
+            GraphemeWidth::Tab => {
+                let tab_width = tab_width.max(1);
+                tab_width - col % tab_width
+            }
         }
     }
 }
@@ -44,6 +44,16 @@ struct TextFragment {
     start_byte_idx: usize,
 }
 
+impl TextFragment {
+    /// What is drawn of the fragment when only `columns` of it are visible.
+    fn cut_off(&self, columns: usize) -> String {
+        match self.rendered_width {
+            GraphemeWidth::Tab => " ".repeat(columns),
+            _ => String::from("⋯"),
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct Line {
     fragments: Vec<TextFragment>,
@@ -57,14 +67,16 @@ impl Display for Line {
 }
 
 impl Line {
-    pub fn get_visable_graphemes(&self, range: Range<usize>) -> String {
-        self.get_annotated_visiable_string(range, None).to_string()
+    pub fn get_visable_graphemes(&self, range: Range<usize>, tab_width: usize) -> String {
+        self.get_annotated_visiable_string(range, None, tab_width)
+            .to_string()
     }
 
     pub fn get_annotated_visiable_string(
         &self,
         range: Range<usize>,
         annotations: Option<&Vec<Annotation>>,
+        tab_width: usize,
     ) -> AnnotatedString {
         let mut res = AnnotatedString::from(&self.string[..]);
 
@@ -74,17 +86,20 @@ impl Line {
             }
         }
 
-        let mut fragment_start = self.width();
-        for fragnment in self.fragments.iter().rev() {
-            let fragment_end = fragment_start;
-            fragment_start = fragment_start - fragnment.rendered_width;
+        let columns = self.columns(tab_width).collect::<Vec<_>>();
+        for (fragnment, columns) in self.fragments.iter().zip(columns).rev() {
+            let Range {
+                start: fragment_start,
+                end: fragment_end,
+            } = columns;
 
             if fragment_start > range.end {
                 continue;
             }
 
             if fragment_start < range.end && fragment_end > range.end {
-                res.replace(fragnment.start_byte_idx..self.string.len(), "⋯");
+                let cut = fragnment.cut_off(range.end - fragment_start);
+                res.replace(fragnment.start_byte_idx..self.string.len(), &cut);
                 continue;
             } else if fragment_start == range.end {
                 res.truncate_right_at(fragnment.start_byte_idx);
@@ -99,11 +114,12 @@ impl Line {
                 );
                 break;
             } else if fragment_start < range.start && fragment_end > range.start {
+                let cut = fragnment.cut_off(fragment_end - range.start);
                 res.replace(
                     0..fragnment
                         .start_byte_idx
                         .saturating_add(fragnment.grapheme.len()),
-                    "⋯",
+                    &cut,
                 );
                 break;
             }
@@ -114,7 +130,11 @@ impl Line {
             {
                 let start_byte_idx = fragnment.start_byte_idx;
                 let end_byte_idx = start_byte_idx.saturating_add(fragnment.grapheme.len());
-                res.replace(start_byte_idx..end_byte_idx, &replacement.to_string());
+                let width = fragment_end - fragment_start;
+                res.replace(
+                    start_byte_idx..end_byte_idx,
+                    &replacement.to_string().repeat(width),
+                );
             }
         }
 
@@ -125,19 +145,31 @@ impl Line {
         self.fragments.len()
     }
 
-    pub fn width_until(&self, grapheme_index: usize) -> usize {
-        self.fragments
-            .iter()
+    fn columns(&self, tab_width: usize) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.fragments.iter().scan(0_usize, move |col, fragment| {
+            let start = *col;
+            *col = start.saturating_add(fragment.rendered_width.at(start, tab_width));
+            Some(start..*col)
+        })
+    }
+
+    pub fn width_until(&self, grapheme_index: usize, tab_width: usize) -> usize {
+        self.columns(tab_width)
             .take(grapheme_index)
-            .map(|fragment| match fragment.rendered_width {
-                GraphemeWidth::Half => 1,
-                GraphemeWidth::Full => 2,
-            })
-            .sum()
+            .last()
+            .map_or(0, |columns| columns.end)
     }
 
-    pub fn width(&self) -> usize {
-        self.width_until(self.grapheme_count())
+    /// The grapheme drawn at screen column `col`, or the end of the line past it.
+    /// Every column of a wide grapheme or a tab belongs to it.
+    pub fn grapheme_index_at_width(&self, col: usize, tab_width: usize) -> usize {
+        self.columns(tab_width)
+            .position(|columns| columns.end > col)
+            .unwrap_or(self.fragments.len())
+    }
+
+    pub fn width(&self, tab_width: usize) -> usize {
+        self.width_until(self.grapheme_count(), tab_width)
     }
 
     pub fn clear(&mut self) {
@@ -154,10 +186,6 @@ impl Line {
         self.rebuild_fragments();
     }
 
-    pub fn append_char(&mut self, c: char) {
-        self.insert_char(c, self.grapheme_count());
-    }
-
     pub fn delete(&mut self, grapheme_index: usize) {
         if let Some(fragment) = self.fragments.get(grapheme_index) {
             let start = fragment.start_byte_idx;
@@ -168,8 +196,9 @@ impl Line {
         }
     }
 
-    pub fn delete_last(&mut self) {
-        self.delete(self.grapheme_count().saturating_sub(1));
+    pub fn replace(&mut self, bytes: Range<usize>, replace_with: &str) {
+        self.string.replace_range(bytes, replace_with);
+        self.rebuild_fragments();
     }
 
     pub fn append(&mut self, next_line: Line) {
@@ -203,70 +232,141 @@ impl Line {
             .map(|fragment| fragment.start_byte_idx)
     }
 
-    pub fn search_forward(&self, query: &str, grapheme_index: usize) -> Option<usize> {
+    pub fn search_forward(&self, pattern: &Pattern, grapheme_index: usize) -> Option<usize> {
         let byte_index = if grapheme_index == self.grapheme_count() {
-            None
+            Some(self.string.len())
         } else {
             self.grapheme_index_to_byte_idx(grapheme_index)
         }?;
 
-        self.find_all(query, byte_index..self.string.len())
+        self.find_all(pattern, byte_index..self.string.len())
             .next()
             .map(|(_, grapheme_index)| grapheme_index)
     }
 
-    pub fn search_backward(&self, query: &str, grapheme_index: usize) -> Option<usize> {
+    pub fn search_backward(&self, pattern: &Pattern, grapheme_index: usize) -> Option<usize> {
         let byte_index = if grapheme_index == self.grapheme_count() {
             Some(self.string.len())
         } else {
             self.grapheme_index_to_byte_idx(grapheme_index)
         }?;
 
-        self.find_all(query, 0..byte_index)
+        self.find_all(pattern, 0..byte_index)
             .last()
             .map(|(_, grapheme_index)| grapheme_index)
     }
 
-    /// return: (bytes_index, grapheme_index)
-    pub fn find_all(
-        &self,
-        query: &str,
+    /// Matches which do not start and end on grapheme boundaries are skipped.
+    ///
+    /// return: (bytes, grapheme_index)
+    pub fn find_all<'a>(
+        &'a self,
+        pattern: &'a Pattern,
         range: Range<usize>,
-    ) -> impl Iterator<Item = (usize, usize)> {
+    ) -> impl Iterator<Item = (Range<usize>, usize)> {
         debug_assert!(range.start <= self.string.len());
-        let substr = &self.string[range.start..std::cmp::min(range.end, self.string.len())];
 
-        let potential_matches = substr
-            .match_indices(query)
-            .map(move |(idx, _)| idx.saturating_add(range.start));
+        pattern
+            .find_iter(&self.string, range)
+            .filter_map(move |bytes| {
+                if !self.is_grapheme_boundary(bytes.end) {
+                    return None;
+                }
+                if bytes.start == self.string.len() {
+                    return Some((bytes, self.grapheme_count()));
+                }
+                self.byte_idx_to_grapheme_index(bytes.start)
+                    .filter(|grapheme_idx| {
+                        self.grapheme_index_to_byte_idx(*grapheme_idx) == Some(bytes.start)
+                    })
+                    .map(|grapheme_idx| (bytes, grapheme_idx))
+            })
+    }
 
-        self.match_grapheme_clusters(potential_matches, query)
+    pub fn grapheme_at(&self, grapheme_index: usize) -> Option<&str> {
+        self.fragments
+            .get(grapheme_index)
+            .map(|fragment| fragment.grapheme.as_str())
     }
 
-    fn match_grapheme_clusters(
-        &self,
-        matches: impl Iterator<Item = usize>,
-        query: &str,
-    ) -> impl Iterator<Item = (usize, usize)> {
-        let grapheme_count = query.graphemes(true).count();
-
-        matches.filter_map(move |idx| {
-            self.byte_idx_to_grapheme_index(idx)
-                .and_then(|grapheme_idx| {
-                    self.fragments
-                        .get(grapheme_idx..grapheme_idx.saturating_add(grapheme_count))
-                        .and_then(|fragments| {
-                            let substr = fragments
-                                .iter()
-                                .map(|fragment| fragment.grapheme.as_str())
-                                .collect::<String>();
-                            (substr == query).then_some((idx, grapheme_idx))
-                        })
-                })
-        })
+    /// Indices of the graphemes starting with `c`, so an emoji with a skin tone
+    /// modifier is found by the emoji alone.
+    pub fn char_positions(&self, c: char) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        self.fragments
+            .iter()
+            .enumerate()
+            .filter(move |(_, fragment)| fragment.grapheme.starts_with(c))
+            .map(|(idx, _)| idx)
+    }
+
+    /// The keyword under the cursor, or the first one after it on the line.
+    ///
+    /// return: (grapheme_index, word)
+    pub fn word_at(&self, grapheme_index: usize) -> Option<(usize, &str)> {
+        let first = self
+            .fragments
+            .iter()
+            .skip(grapheme_index)
+            .position(|fragment| is_keyword(&fragment.grapheme))?
+            .saturating_add(grapheme_index);
+
+        let start = self.fragments[..first]
+            .iter()
+            .rposition(|fragment| !is_keyword(&fragment.grapheme))
+            .map_or(0, |idx| idx.saturating_add(1));
+        let end = self.fragments[first..]
+            .iter()
+            .position(|fragment| !is_keyword(&fragment.grapheme))
+            .map_or(self.fragments.len(), |idx| idx.saturating_add(first));
+
+        let start_byte = self.fragments[start].start_byte_idx;
+        let end_byte = self
+            .grapheme_index_to_byte_idx(end)
+            .unwrap_or(self.string.len());
+        Some((start, &self.string[start_byte..end_byte]))
+    }
+
+    pub fn first_non_blank(&self) -> usize {
+        self.fragments
+            .iter()
+            .position(|fragment| CharClass::of(&fragment.grapheme, false) != CharClass::Blank)
+            .unwrap_or(self.fragments.len())
+    }
+
+    fn is_grapheme_boundary(&self, byte_index: usize) -> bool {
+        byte_index == self.string.len()
+            || self
+                .fragments
+                .binary_search_by_key(&byte_index, |fragment| fragment.start_byte_idx)
+                .is_ok()
     }
 }
 
+/// How word motions group graphemes: a word is a run of graphemes of the same class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharClass {
+    Blank,
+    Punctuation,
+    /// Letters, digits and `_` in any script.
+    Keyword,
+}
+
+impl CharClass {
+    /// With `big` (WORD motions) every non-blank grapheme is a keyword.
+    pub fn of(grapheme: &str, big: bool) -> Self {
+        match grapheme.chars().next() {
+            None => Self::Blank,
+            Some(c) if c.is_whitespace() => Self::Blank,
+            Some(c) if big || c.is_alphanumeric() || c == '_' => Self::Keyword,
+            Some(_) => Self::Punctuation,
+        }
+    }
+}
+
+fn is_keyword(grapheme: &str) -> bool {
+    CharClass::of(grapheme, false) == CharClass::Keyword
+}
+
 fn replace_charactor(grapheme: &str) -> Option<char> {
     let width = grapheme.width();
     match grapheme {
@@ -300,7 +400,14 @@ fn str_to_fragments(value: &str) -> Vec<TextFragment> {
                     };
                     (rendered_width, None)
                 },
-                |replacement| (GraphemeWidth::Half, Some(replacement)),
+                |replacement| {
+                    let rendered_width = if grapheme == "\t" {
+                        GraphemeWidth::Tab
+                    } else {
+                        GraphemeWidth::Half
+                    };
+                    (rendered_width, Some(replacement))
+                },
             );
 
             TextFragment {
@@ -346,26 +453,26 @@ impl Deref for Line {
 fn test_line_range() {
     let line = Line::from("Hello, world!");
     assert_eq!(line.grapheme_count(), 13);
-    assert_eq!(&line.get_visable_graphemes(0..5), "Hello");
-    assert_eq!(&line.get_visable_graphemes(7..13), "world!");
-    assert_eq!(&line.get_visable_graphemes(13..18), "");
+    assert_eq!(&line.get_visable_graphemes(0..5, 8), "Hello");
+    assert_eq!(&line.get_visable_graphemes(7..13, 8), "world!");
+    assert_eq!(&line.get_visable_graphemes(13..18, 8), "");
 }
 
 #[test]
 fn test_unicode() {
     let line = Line::from("𝒻𝒶𝓃𝒸𝓎!");
     assert_eq!(line.grapheme_count(), 6);
-    assert_eq!(&line.get_visable_graphemes(0..6), "𝒻𝒶𝓃𝒸𝓎!");
-    assert_eq!(&line.get_visable_graphemes(7..100), "");
+    assert_eq!(&line.get_visable_graphemes(0..6, 8), "𝒻𝒶𝓃𝒸𝓎!");
+    assert_eq!(&line.get_visable_graphemes(7..100, 8), "");
 }
 
 #[test]
 fn test_width_charactor() {
     let line = Line::from("Ａ");
     assert_eq!(line.grapheme_count(), 1);
-    assert_eq!(&line.get_visable_graphemes(0..1), "⋯");
-    assert_eq!(&line.get_visable_graphemes(0..2), "Ａ");
-    assert_eq!(&line.get_visable_graphemes(2..3), "");
+    assert_eq!(&line.get_visable_graphemes(0..1, 8), "⋯");
+    assert_eq!(&line.get_visable_graphemes(0..2, 8), "Ａ");
+    assert_eq!(&line.get_visable_graphemes(2..3, 8), "");
 }
 
 #[test]
@@ -380,16 +487,76 @@ fn esc_and_bell() {
 #[test]
 fn test_search() {
     let line = Line::from("hello world");
-    assert_eq!(line.search_forward("hello", 0), Some(0));
-    assert_eq!(line.search_forward("world", 0), Some(6));
+    assert_eq!(line.search_forward(&"hello".into(), 0), Some(0));
+    assert_eq!(line.search_forward(&"world".into(), 0), Some(6));
 
     let line = Line::from("你好 世界 ");
 
-    assert_eq!(line.search_forward("你好", 0), Some(0));
-    assert_eq!(line.search_forward("世界", 0), Some(3));
+    assert_eq!(line.search_forward(&"你好".into(), 0), Some(0));
+    assert_eq!(line.search_forward(&"世界".into(), 0), Some(3));
 
-    assert_eq!(line.search_backward("你好", 5), Some(0));
-    assert_eq!(line.search_backward("世界", 5), Some(3));
+    assert_eq!(line.search_backward(&"你好".into(), 5), Some(0));
+    assert_eq!(line.search_backward(&"世界".into(), 5), Some(3));
+}
+
+#[test]
+fn test_regex_search() {
+    let line = Line::from("你好 世界 foo_bar42");
+
+    let pattern = Pattern::new("\\v[a-z_]+\\d+", SearchOptions::default()).unwrap();
+    assert_eq!(line.search_forward(&pattern, 0), Some(6));
+    assert_eq!(
+        line.find_all(&pattern, 0..line.len()).next(),
+        Some((14..23, 6))
+    );
+
+    let pattern = Pattern::new("\\v(世|你)\\S", SearchOptions::default()).unwrap();
+    assert_eq!(line.search_forward(&pattern, 1), Some(3));
+    assert_eq!(line.search_backward(&pattern, 5), Some(3));
+
+    // Matches splitting a grapheme cluster are rejected.
+    let line = Line::from("e\u{301}e");
+    let pattern = Pattern::new("\\ve", SearchOptions::default()).unwrap();
+    assert_eq!(line.find_all(&pattern, 0..line.len()).count(), 1);
+
+    let pattern = Pattern::new("\\v$", SearchOptions::default()).unwrap();
+    assert_eq!(line.search_forward(&pattern, 1), Some(2));
+    let pattern = Pattern::new("\\v^$", SearchOptions::default()).unwrap();
+    assert_eq!(line.search_forward(&pattern, 0), None);
+    assert_eq!(Line::default().search_forward(&pattern, 0), Some(0));
+}
+
+#[test]
+fn word_at() {
+    let line = Line::from("let größe = foo_bar(1);");
+    assert_eq!(line.word_at(0), Some((0, "let")));
+    assert_eq!(line.word_at(3), Some((4, "größe")));
+    assert_eq!(line.word_at(7), Some((4, "größe")));
+    assert_eq!(line.word_at(10), Some((12, "foo_bar")));
+    assert_eq!(line.word_at(21), None);
+}
+
+#[test]
+fn grapheme_at_screen_column() {
+    let line = Line::from("a你好b");
+    let at = |col| line.grapheme_index_at_width(col, 8);
+    assert_eq!(
+        [at(0), at(1), at(2), at(3), at(4), at(5), at(9)],
+        [0, 1, 1, 2, 2, 3, 4]
+    );
+}
+
+#[test]
+fn expand_tabs_to_tab_stops() {
+    let line = Line::from("a\tb\t\tc");
+    assert_eq!(line.width_until(2, 4), 4);
+    assert_eq!(line.width(4), 13);
+    assert_eq!(line.width(8), 25);
+    assert_eq!(line.grapheme_index_at_width(3, 4), 1);
+    assert_eq!(line.grapheme_index_at_width(9, 4), 4);
+    assert_eq!(&line.get_visable_graphemes(0..13, 4), "a   b       c");
+    assert_eq!(&line.get_visable_graphemes(4..13, 4), "b       c");
+    assert_eq!(&line.get_visable_graphemes(2..13, 4), "  b       c");
 }
 
 #[test]
@@ -405,7 +572,7 @@ fn annotation() {
             bytes: 5..6,
         },
     ];
-    let annotation = line.get_annotated_visiable_string(0..7, Some(&annotation));
+    let annotation = line.get_annotated_visiable_string(0..7, Some(&annotation), 8);
     let mut iter = annotation.into_iter();
 
     assert!(iter.next().is_some()); // C
@@ -0,0 +1,116 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::editor::view::cursor::Location;
+
+const JUMP_LIMIT: usize = 100;
+
+/// The position before the latest jump, used by `''` and ``` `` ```.
+pub const CONTEXT_MARK: char = '\'';
+
+/// Named positions in a buffer, kept on the same text while lines are inserted
+/// and deleted.
+#[derive(Debug, Default)]
+pub struct Marks {
+    marks: HashMap<char, Location>,
+}
+
+impl Marks {
+    pub fn get(&self, name: char) -> Option<Location> {
+        self.marks.get(&key(name)).copied()
+    }
+
+    pub fn set(&mut self, name: char, location: Location) {
+        self.marks.insert(key(name), location);
+    }
+
+    pub fn file_marks(&self) -> impl Iterator<Item = (char, Location)> + '_ {
+        self.marks
+            .iter()
+            .filter(|(name, _)| name.is_ascii_uppercase())
+            .map(|(name, location)| (*name, *location))
+    }
+
+    /// Moves every mark to where `adjust` puts it, marks it returns `None` for are deleted.
+    pub fn adjust(&mut self, adjust: impl Fn(Location) -> Option<Location>) {
+        self.marks.retain(|_, location| {
+            adjust(*location).is_some_and(|adjusted| {
+                *location = adjusted;
+                true
+            })
+        });
+    }
+}
+
+/// `` ` `` names the same mark as `'`.
+fn key(name: char) -> char {
+    if name == '`' { CONTEXT_MARK } else { name }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Jump {
+    pub path: Option<PathBuf>,
+    pub location: Location,
+}
+
+#[derive(Debug, Default)]
+pub struct JumpList {
+    jumps: Vec<Jump>,
+    /// The entry `Ctrl-o` / `Ctrl-i` moved to, `jumps.len()` after a new jump.
+    index: usize,
+}
+
+impl JumpList {
+    /// Remembers `jump`, an older entry on the same line is dropped.
+    pub fn push(&mut self, jump: Jump) {
+        self.jumps.retain(|other| {
+            other.path != jump.path || other.location.line_index != jump.location.line_index
+        });
+        if self.jumps.len() >= JUMP_LIMIT {
+            self.jumps.remove(0);
+        }
+        self.jumps.push(jump);
+        self.index = self.jumps.len();
+    }
+
+    /// `Ctrl-o`: the entry before the current one. Leaving the newest position
+    /// remembers `current` so `Ctrl-i` can return to it.
+    pub fn older(&mut self, current: Jump) -> Option<&Jump> {
+        if self.index >= self.jumps.len() {
+            self.push(current);
+            self.index = self.jumps.len().saturating_sub(1);
+        }
+        self.index = self.index.checked_sub(1)?;
+        self.jumps.get(self.index)
+    }
+
+    pub fn newer(&mut self) -> Option<&Jump> {
+        if self.index.saturating_add(1) >= self.jumps.len() {
+            return None;
+        }
+        self.index = self.index.saturating_add(1);
+        self.jumps.get(self.index)
+    }
+}
+
+#[test]
+fn walk_the_jump_list() {
+    let jump = |line_index| Jump {
+        path: None,
+        location: Location {
+            grapheme_index: 0,
+            line_index,
+        },
+    };
+    let mut jumps = JumpList::default();
+    jumps.push(jump(1));
+    jumps.push(jump(5));
+    jumps.push(jump(1));
+
+    assert_eq!(jumps.newer(), None);
+    assert_eq!(jumps.older(jump(9)), Some(&jump(1)));
+    assert_eq!(jumps.older(jump(1)), Some(&jump(5)));
+    assert_eq!(jumps.older(jump(5)), None);
+    assert_eq!(jumps.newer(), Some(&jump(1)));
+    assert_eq!(jumps.newer(), Some(&jump(9)));
+    assert_eq!(jumps.newer(), None);
+}
@@ -0,0 +1,379 @@
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+/// Queries starting with this prefix are treated as regular expressions.
+pub const REGEX_PREFIX: &str = "\\v";
+const IGNORE_CASE_FLAG: &str = "\\c";
+const MATCH_CASE_FLAG: &str = "\\C";
+/// Full case folds of more than one character, which the simple folding of the regex
+/// crate misses. Longer folds come first.
+const MULTI_CHAR_FOLDS: &[(&str, &[char])] = &[
+    ("ffi", &['ﬃ']),
+    ("ffl", &['ﬄ']),
+    ("ff", &['ﬀ']),
+    ("fi", &['ﬁ']),
+    ("fl", &['ﬂ']),
+    ("ss", &['ß', 'ẞ']),
+    ("st", &['ﬅ', 'ﬆ']),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub ignorecase: bool,
+    /// Only takes effect with `ignorecase`: a query containing uppercase letters is case sensitive.
+    pub smartcase: bool,
+    pub hlsearch: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            ignorecase: false,
+            smartcase: false,
+            hlsearch: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Literal(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    pub fn new(query: &str, options: SearchOptions) -> Result<Self, regex::Error> {
+        let (query, flag) = strip_case_flags(query);
+        let ignore_case = match flag {
+            Some(MATCH_CASE_FLAG) => false,
+            Some(_) => true,
+            None => options.ignorecase && !(options.smartcase && has_uppercase(&query)),
+        };
+
+        match query.strip_prefix(REGEX_PREFIX) {
+            Some(regex) if ignore_case => RegexBuilder::new(&fold_regex(regex))
+                .multi_line(true)
+                .case_insensitive(true)
+                .build()
+                .map(Self::Regex),
+            Some(regex) => RegexBuilder::new(regex)
+                .multi_line(true)
+                .build()
+                .map(Self::Regex),
+            None if ignore_case && !query.is_empty() => RegexBuilder::new(&fold_literal(&query))
+                .case_insensitive(true)
+                .build()
+                .map(Self::Regex),
+            None => Ok(Self::Literal(query)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Pattern::Literal(literal) => literal.is_empty(),
+            Pattern::Regex(regex) => regex.as_str().is_empty(),
+        }
+    }
+
+    /// Iterate over the byte ranges of all matches in `haystack` which start inside `range`.
+    ///
+    /// The whole haystack is always searched so anchors and word boundaries see
+    /// the text around `range`. A range reaching the end of the haystack also
+    /// yields an empty match there, like `$`.
+    pub fn find_iter<'a>(
+        &'a self,
+        haystack: &'a str,
+        range: Range<usize>,
+    ) -> impl Iterator<Item = Range<usize>> + 'a {
+        let end = if range.end >= haystack.len() {
+            haystack.len().saturating_add(1)
+        } else {
+            range.end
+        };
+        let mut at = range.start;
+        let mut last_end = None;
+
+        std::iter::from_fn(move || {
+            loop {
+                if at >= end {
+                    return None;
+                }
+
+                let bytes = match self {
+                    Pattern::Literal(literal) => haystack[at..]
+                        .find(literal.as_str())
+                        .map(|idx| at.saturating_add(idx)..at.saturating_add(idx + literal.len())),
+                    Pattern::Regex(regex) => regex.find_at(haystack, at).map(|m| m.range()),
+                }?;
+                if bytes.start >= end {
+                    return None;
+                }
+
+                at = if bytes.is_empty() {
+                    haystack[bytes.end..]
+                        .chars()
+                        .next()
+                        .map_or(end, |c| bytes.end.saturating_add(c.len_utf8()))
+                } else {
+                    bytes.end
+                };
+                // An empty match right after a match is part of it, as in `\v\d*`.
+                let follows_match = bytes.is_empty() && last_end == Some(bytes.start);
+                last_end = Some(bytes.end);
+                if !follows_match {
+                    return Some(bytes);
+                }
+            }
+        })
+    }
+
+    /// Builds the replacement for the match at `bytes`: `&` and `\0` insert the whole
+    /// match, `\1`..`\9` a capture group and `\t` a tab; other escaped characters
+    /// are inserted as they are.
+    pub fn expand(&self, haystack: &str, bytes: Range<usize>, replacement: &str) -> String {
+        let captures = match self {
+            Pattern::Regex(regex) => regex
+                .captures_at(haystack, bytes.start)
+                .filter(|captures| captures.get(0).is_some_and(|m| m.range() == bytes)),
+            Pattern::Literal(_) => None,
+        };
+        let group = |idx: usize| match captures {
+            Some(ref captures) => captures.get(idx).map_or("", |m| m.as_str()),
+            None if idx == 0 => &haystack[bytes.clone()],
+            None => "",
+        };
+
+        let mut res = String::new();
+        let mut chars = replacement.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '&' => res.push_str(group(0)),
+                '\\' => match chars.next() {
+                    Some(digit @ '0'..='9') => {
+                        res.push_str(group(digit.to_digit(10).unwrap_or_default() as usize))
+                    }
+                    Some('t') => res.push('\t'),
+                    Some(other) => res.push(other),
+                    None => res.push(c),
+                },
+                _ => res.push(c),
+            }
+        }
+        res
+    }
+}
+
+/// Removes the unescaped `\c` and `\C` flags from `query`.
+///
+/// return: the query and the flag taking effect, `\C` winning over `\c`
+fn strip_case_flags(query: &str) -> (String, Option<&'static str>) {
+    let mut res = String::with_capacity(query.len());
+    let mut flag = None;
+    let mut chars = query.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('C') => flag = Some(MATCH_CASE_FLAG),
+            Some('c') => flag = flag.or(Some(IGNORE_CASE_FLAG)),
+            Some(escaped) => {
+                res.push(c);
+                res.push(escaped);
+            }
+            None => res.push(c),
+        }
+    }
+    (res, flag)
+}
+
+fn fold_alternatives(folded: &str, chars: &[char]) -> String {
+    let mut res = format!("(?:{folded}");
+    for c in chars {
+        res.push('|');
+        res.push(*c);
+    }
+    res.push(')');
+    res
+}
+
+/// A regex matching `literal` with full case folding: `ß` also matches `ss` and
+/// `ss` also matches `ß`.
+fn fold_literal(literal: &str) -> String {
+    let mut res = String::new();
+    let mut rest = literal;
+    while let Some(c) = rest.chars().next() {
+        let fold = MULTI_CHAR_FOLDS.iter().find(|(folded, chars)| {
+            chars.contains(&c)
+                || rest
+                    .get(..folded.len())
+                    .is_some_and(|start| start.eq_ignore_ascii_case(folded))
+        });
+        let len = match fold {
+            Some((folded, chars)) => {
+                res.push_str(&fold_alternatives(folded, chars));
+                if chars.contains(&c) {
+                    c.len_utf8()
+                } else {
+                    folded.len()
+                }
+            }
+            None => {
+                res.push_str(&regex::escape(&rest[..c.len_utf8()]));
+                c.len_utf8()
+            }
+        };
+        rest = &rest[len..];
+    }
+    res
+}
+
+/// Lets the characters of `regex` with a multi-character fold also match the
+/// folded text. Escaped characters and character classes are left as they are.
+fn fold_regex(regex: &str) -> String {
+    let mut res = String::with_capacity(regex.len());
+    let mut in_class = false;
+    let mut chars = regex.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                res.push(c);
+                res.extend(chars.next());
+            }
+            '[' => {
+                in_class = true;
+                res.push(c);
+            }
+            ']' => {
+                in_class = false;
+                res.push(c);
+            }
+            _ => match MULTI_CHAR_FOLDS
+                .iter()
+                .find(|(_, chars)| chars.contains(&c))
+            {
+                Some((folded, chars)) if !in_class => {
+                    res.push_str(&fold_alternatives(folded, chars))
+                }
+                _ => res.push(c),
+            },
+        }
+    }
+    res
+}
+
+/// Escaped characters such as `\S` or `\W` are not counted.
+fn has_uppercase(query: &str) -> bool {
+    let mut chars = query.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
+impl From<&str> for Pattern {
+    fn from(value: &str) -> Self {
+        Self::Literal(value.to_string())
+    }
+}
+
+#[test]
+fn regex_anchors() {
+    let pattern = Pattern::new("\\v^fn\\s+(\\w+)", SearchOptions::default()).unwrap();
+    assert_eq!(
+        pattern.find_iter("fn main() {}", 0..12).collect::<Vec<_>>(),
+        vec![0..7]
+    );
+    // `^` still refers to the start of the line when searching from the middle.
+    assert_eq!(pattern.find_iter("fn main() {}", 1..12).count(), 0);
+
+    let pattern = Pattern::new("\\v[0-9]+$", SearchOptions::default()).unwrap();
+    assert_eq!(
+        pattern.find_iter("a1 b22 c333", 0..11).collect::<Vec<_>>(),
+        vec![8..11]
+    );
+
+    let find = |query: &str, haystack: &str, range| {
+        Pattern::new(query, SearchOptions::default())
+            .unwrap()
+            .find_iter(haystack, range)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(find("\\v$", "abc", 0..3), vec![3..3]);
+    assert!(find("\\v$", "abc", 0..2).is_empty());
+    assert_eq!(find("\\v^", "", 0..0), vec![0..0]);
+    assert_eq!(find("\\v$", "", 0..0), vec![0..0]);
+    assert_eq!(find("\\v^$", "", 0..0), vec![0..0]);
+    assert!(find("\\v^$", "a", 0..1).is_empty());
+    // The empty match right after `1` is not returned again.
+    assert_eq!(find("\\v\\d*", "a1", 0..2), vec![0..0, 1..2]);
+}
+
+#[test]
+fn case_folding() {
+    let ignorecase = SearchOptions {
+        ignorecase: true,
+        ..Default::default()
+    };
+    let smartcase = SearchOptions {
+        ignorecase: true,
+        smartcase: true,
+        ..Default::default()
+    };
+    let count = |query: &str, options: SearchOptions, haystack: &str| {
+        Pattern::new(query, options)
+            .unwrap()
+            .find_iter(haystack, 0..haystack.len())
+            .count()
+    };
+
+    // Full case folding: `ß` matches `SS` and the other way around.
+    assert_eq!(count("Straße", ignorecase, "STRASSE Straße"), 2);
+    assert_eq!(count("strasse", ignorecase, "Straße STRAẞE"), 2);
+    assert_eq!(count("\\vstra(ß)e", ignorecase, "STRASSE"), 1);
+    assert_eq!(count("\\v[ß]", ignorecase, "SS ß"), 1);
+    assert_eq!(count("ﬁle", ignorecase, "FILE ﬁle"), 2);
+    assert_eq!(
+        count("Straße", SearchOptions::default(), "STRASSE Strasse"),
+        0
+    );
+    assert_eq!(count("ПРИВЕТ", ignorecase, "привет Привет"), 2);
+    assert_eq!(count("Привет", smartcase, "привет Привет"), 1);
+    assert_eq!(count("привет", smartcase, "привет Привет"), 2);
+    assert_eq!(count("\\cΣΑΣ", SearchOptions::default(), "σας"), 1);
+    assert_eq!(count("a.b\\C", ignorecase, "A.B a.b"), 1);
+    assert_eq!(count("\\v\\S+", smartcase, "Ab ab"), 2);
+    // An escaped backslash followed by `c` is not a flag.
+    assert_eq!(count("\\v\\\\c", SearchOptions::default(), "a\\c \\C"), 1);
+    assert_eq!(
+        count("\\v\\\\c\\c", SearchOptions::default(), "a\\c \\C"),
+        2
+    );
+}
+
+#[test]
+fn expand_replacement() {
+    let haystack = "let answer = 42;";
+    let pattern = Pattern::new("\\v(\\w+) = (\\d+)", SearchOptions::default()).unwrap();
+    let bytes = pattern
+        .find_iter(haystack, 0..haystack.len())
+        .next()
+        .unwrap();
+    assert_eq!(
+        pattern.expand(haystack, bytes.clone(), "\\2 =: \\1 (&) \\& \\3"),
+        "42 =: answer (answer = 42) & "
+    );
+
+    let pattern = Pattern::from("answer");
+    let bytes = pattern
+        .find_iter(haystack, 0..haystack.len())
+        .next()
+        .unwrap();
+    assert_eq!(pattern.expand(haystack, bytes, "<&>\\1"), "<answer>");
+}
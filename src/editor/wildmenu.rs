@@ -0,0 +1,174 @@
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    editor::{
+        annotated::{AnnotatedString, annotation::AnnotationType},
+        completion::Completion,
+        ui::UiComponent,
+    },
+    terminal::Backend,
+};
+
+const SEPARATOR: &str = "  ";
+
+#[derive(Default)]
+pub struct WildMenu {
+    completion: Option<Completion>,
+    /// The text the candidates replace, restored after cycling past the last one.
+    original: String,
+    selected: Option<usize>,
+    width: u16,
+    render: bool,
+}
+
+impl WildMenu {
+    pub fn is_active(&self) -> bool {
+        self.completion.is_some()
+    }
+
+    /// Only shown when there is more than one candidate to choose from.
+    pub fn is_visible(&self) -> bool {
+        self.completion
+            .as_ref()
+            .is_some_and(|completion| completion.candidates.len() > 1)
+    }
+
+    /// Byte index in the command bar where the candidates are inserted.
+    pub fn start(&self) -> usize {
+        self.completion
+            .as_ref()
+            .map_or(0, |completion| completion.start)
+    }
+
+    pub fn show(&mut self, completion: Completion, original: String) {
+        self.completion = Some(completion);
+        self.original = original;
+        self.selected = None;
+        self.set_render(true);
+    }
+
+    /// return: whether the menu was visible
+    pub fn hide(&mut self) -> bool {
+        let visible = self.is_visible();
+        self.completion = None;
+        self.selected = None;
+        visible
+    }
+
+    /// Wraps around through the original text.
+    ///
+    /// return: the text to insert
+    pub fn select(&mut self, forward: bool) -> &str {
+        self.set_render(true);
+        let Some(completion) = &self.completion else {
+            return &self.original;
+        };
+        let len = completion.candidates.len();
+        self.selected = match (self.selected, forward) {
+            (None, true) => Some(0),
+            (None, false) => len.checked_sub(1),
+            (Some(idx), true) => Some(idx.saturating_add(1)).filter(|idx| *idx < len),
+            (Some(idx), false) => idx.checked_sub(1),
+        };
+
+        self.selected
+            .and_then(|idx| completion.candidates.get(idx))
+            .unwrap_or(&self.original)
+    }
+
+    /// The candidates fitting into the menu's width, starting at the page of the
+    /// selected one.
+    fn visible_candidates<'a>(&self, candidates: &'a [String]) -> (usize, &'a [String]) {
+        let width = self.width as usize;
+        let mut first = 0;
+        let mut used = 0_usize;
+        for (idx, candidate) in candidates.iter().enumerate() {
+            let candidate_width = candidate.width().saturating_add(SEPARATOR.len());
+            if used > 0 && used.saturating_add(candidate.width()) > width {
+                if self.selected.is_none_or(|selected| selected < idx) {
+                    return (first, &candidates[first..idx]);
+                }
+                first = idx;
+                used = 0;
+            }
+            used = used.saturating_add(candidate_width);
+        }
+        (first, &candidates[first..])
+    }
+}
+
+impl UiComponent for WildMenu {
+    fn set_render(&mut self, render: bool) {
+        self.render = render;
+    }
+
+    fn needs_render(&self) -> bool {
+        self.render && self.is_visible()
+    }
+
+    fn set_size(&mut self, width: u16, _height: u16) {
+        self.width = width;
+    }
+
+    fn draw(&mut self, backend: &mut dyn Backend, y: u16) -> anyhow::Result<()> {
+        let Some(completion) = &self.completion else {
+            return Ok(());
+        };
+        let (first, candidates) = self.visible_candidates(&completion.candidates);
+
+        let mut menu = String::new();
+        let mut selected = None;
+        for (idx, candidate) in candidates.iter().enumerate() {
+            if idx > 0 {
+                menu.push_str(SEPARATOR);
+            }
+            if self.selected == Some(first.saturating_add(idx)) {
+                selected = Some(menu.len()..menu.len().saturating_add(candidate.len()));
+            }
+            menu.push_str(candidate);
+        }
+
+        let mut menu = AnnotatedString::from(menu);
+        if let Some(bytes) = selected {
+            menu.add_annotation(AnnotationType::WildMenuSelection, bytes);
+        }
+        backend.print_annotated_at(0, y, true, &menu)
+    }
+}
+
+#[test]
+fn cycle_through_candidates() {
+    let mut menu = WildMenu {
+        width: 20,
+        ..Default::default()
+    };
+    menu.show(
+        Completion {
+            start: 0,
+            candidates: vec![String::from("saveas"), String::from("set")],
+        },
+        String::from("s"),
+    );
+
+    assert!(menu.is_visible());
+    assert_eq!(menu.select(true), "saveas");
+    assert_eq!(menu.select(true), "set");
+    assert_eq!(menu.select(true), "s");
+    assert_eq!(menu.select(false), "set");
+
+    assert!(menu.hide());
+    assert!(!menu.is_active());
+}
+
+#[test]
+fn page_to_the_selected_candidate() {
+    let candidates = ["alpha", "beta", "gamma", "delta"].map(String::from);
+    let mut menu = WildMenu {
+        width: 14,
+        ..Default::default()
+    };
+
+    assert_eq!(menu.visible_candidates(&candidates), (0, &candidates[..2]));
+    menu.selected = Some(2);
+    assert_eq!(menu.visible_candidates(&candidates), (2, &candidates[2..]));
+}
@@ -10,6 +10,9 @@ mod terminal;
 #[derive(Parser)]
 pub struct Cli {
     path: Option<PathBuf>,
+    /// Read the options from this file instead of `$XDG_CONFIG_HOME/oxide/config.toml`
+    #[arg(long)]
+    config: Option<PathBuf>,
 }
 
 fn main() {
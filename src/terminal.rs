@@ -4,18 +4,23 @@ use std::{
 };
 
 use anyhow::Context;
-use crossterm::{
-    cursor, queue,
-    style::{self, Print},
-    terminal,
+use crossterm::{cursor, event, queue, terminal};
+
+#[cfg(test)]
+pub use crate::terminal::test_backend::TestBackend;
+pub use crate::terminal::{
+    backend::{Backend, CrosstermBackend},
+    color::parse_color,
+    screen::Style,
+    theme::{Element, Theme},
 };
 
-use crate::{
-    editor::annotated::{AnnotatedString, annotation::AnnotationType},
-    terminal::attribute::Attribute,
-};
-
-mod attribute;
+mod backend;
+mod color;
+mod screen;
+#[cfg(test)]
+mod test_backend;
+mod theme;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Position {
@@ -29,47 +34,63 @@ impl Position {
         let y = self.row.saturating_sub(offset.row) as u16;
         (x, y)
     }
+}
 
-    pub fn pos(&self) -> (usize, usize) {
-        (self.col, self.row)
-    }
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaretShape {
+    #[default]
+    Block,
+    Bar,
+    Underline,
 }
 
 pub fn init() -> anyhow::Result<()> {
     terminal::enable_raw_mode().context("enable raw mode in terminal")?;
 
     enter_alternate()?;
+    enable_bracketed_paste()?;
     disable_line_wrap()?;
     set_cursor_style(cursor::SetCursorStyle::SteadyBlock)?;
 
-    clear_screen()?;
-    execute()
+    stdout().flush().context("flush stdout")
 }
 
 pub fn terminate() -> anyhow::Result<()> {
+    set_mouse_capture(false)?;
+    disable_bracketed_paste()?;
     set_cursor_style(cursor::SetCursorStyle::DefaultUserShape)?;
     exit_alternate()?;
     enable_line_wrap()?;
-    show_caret()?;
+    queue!(stdout(), cursor::Show).context("show the cursor")?;
 
-    execute()?;
+    stdout().flush().context("flush stdout")?;
     terminal::disable_raw_mode().context("disable raw mode in terminal")
 }
 
-fn enter_alternate() -> anyhow::Result<()> {
-    queue!(stdout(), terminal::EnterAlternateScreen).context("enter alternate screen")
+/// Receive mouse events instead of letting the terminal select text.
+pub fn set_mouse_capture(enable: bool) -> anyhow::Result<()> {
+    if enable {
+        queue!(stdout(), event::EnableMouseCapture).context("enable mouse capture")
+    } else {
+        queue!(stdout(), event::DisableMouseCapture).context("disable mouse capture")
+    }
 }
 
-fn exit_alternate() -> anyhow::Result<()> {
-    queue!(stdout(), terminal::LeaveAlternateScreen).context("exit alternate screen")
+/// Pasted text arrives as a single event instead of one key event per character.
+fn enable_bracketed_paste() -> anyhow::Result<()> {
+    queue!(stdout(), event::EnableBracketedPaste).context("enable bracketed paste")
 }
 
-pub fn clear_screen() -> anyhow::Result<()> {
-    queue!(stdout(), terminal::Clear(terminal::ClearType::All)).context("clear screen")
+fn disable_bracketed_paste() -> anyhow::Result<()> {
+    queue!(stdout(), event::DisableBracketedPaste).context("disable bracketed paste")
+}
+
+fn enter_alternate() -> anyhow::Result<()> {
+    queue!(stdout(), terminal::EnterAlternateScreen).context("enter alternate screen")
 }
 
-pub fn clear_line() -> anyhow::Result<()> {
-    queue!(stdout(), terminal::Clear(terminal::ClearType::CurrentLine)).context("clear line")
+fn exit_alternate() -> anyhow::Result<()> {
+    queue!(stdout(), terminal::LeaveAlternateScreen).context("exit alternate screen")
 }
 
 fn enable_line_wrap() -> anyhow::Result<()> {
@@ -89,95 +110,6 @@ pub fn size() -> anyhow::Result<(u16, u16)> {
     terminal::size().context("get the terminal's size")
 }
 
-pub fn move_caret(col: u16, row: u16) -> anyhow::Result<()> {
-    queue!(stdout(), cursor::MoveTo(col, row))
-        .with_context(|| format!("move cursor into ({col}, {row})"))
-}
-
-pub fn hide_caret() -> anyhow::Result<()> {
-    queue!(stdout(), cursor::Hide).context("hide the cursor")
-}
-
-pub fn show_caret() -> anyhow::Result<()> {
-    queue!(stdout(), cursor::Show).context("show the cursor")
-}
-
-pub fn print(text: impl Display) -> anyhow::Result<()> {
-    queue!(stdout(), Print(&text)).with_context(|| format!("print `{text}` into terminal"))
-}
-
-pub fn print_inverted(text: impl Display) -> anyhow::Result<()> {
-    print(format!(
-        "{}{}{}",
-        style::Attribute::Reverse,
-        text,
-        style::Attribute::Reset
-    ))
-}
-
-pub fn print_at(col: u16, row: u16, clear: bool, text: impl Display) -> anyhow::Result<()> {
-    move_caret(col, row)?;
-    if clear {
-        clear_line()?;
-    }
-
-    print(text)
-}
-
-pub fn print_inverted_at(
-    col: u16,
-    row: u16,
-    clear: bool,
-    text: impl Display,
-) -> anyhow::Result<()> {
-    move_caret(col, row)?;
-    if clear {
-        clear_line()?;
-    }
-
-    print_inverted(text)
-}
-
-pub fn print_annotated_row(row: u16, annotated_string: AnnotatedString) -> anyhow::Result<()> {
-    move_caret(0, row)?;
-    clear_line()?;
-
-    annotated_string
-        .into_iter()
-        .try_for_each(|part| -> anyhow::Result<()> {
-            if let Some(annotation) = part.annotation {
-                set_attribute(annotation)?;
-            }
-
-            print(part.inner)?;
-            reset_color()?;
-
-            Ok(())
-        })?;
-
-    Ok(())
-}
-
-fn set_attribute(annotation: AnnotationType) -> anyhow::Result<()> {
-    let attribute: Attribute = annotation.into();
-    if let Some(foreground) = attribute.foreground {
-        queue!(stdout(), style::SetForegroundColor(foreground)).context("set foreground color")?;
-    }
-    if let Some(background) = attribute.background {
-        queue!(stdout(), style::SetBackgroundColor(background)).context("set background color")?;
-    }
-
-    Ok(())
-}
-
-fn reset_color() -> anyhow::Result<()> {
-    queue!(stdout(), style::ResetColor).context("reset color")
-}
-
-pub fn execute() -> anyhow::Result<()> {
-    stdout().flush().context("flush stdout")
-}
-
 pub fn set_title(title: impl Display) -> anyhow::Result<()> {
     queue!(stdout(), terminal::SetTitle(title)).context("set title")?;
     Ok(())
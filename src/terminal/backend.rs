@@ -0,0 +1,174 @@
+use std::io::{Write, stdout};
+
+use anyhow::Context;
+use crossterm::cursor::SetCursorStyle;
+
+use crate::{
+    editor::annotated::AnnotatedString,
+    terminal::{
+        self, CaretShape,
+        color::ColorDepth,
+        screen::{Screen, Style},
+        theme::{Element, Theme},
+    },
+};
+
+pub trait Backend {
+    /// The size of the drawing area (cols, rows)
+    fn size(&self) -> anyhow::Result<(u16, u16)>;
+
+    /// Starts the next frame over blank at the new size.
+    fn resize(&mut self, width: u16, height: u16);
+
+    fn clear_row(&mut self, row: u16);
+
+    /// return: the column after the text
+    fn print(&mut self, col: u16, row: u16, text: &str, style: Style) -> u16;
+
+    fn move_caret(&mut self, col: u16, row: u16);
+
+    fn set_caret_visible(&mut self, visible: bool);
+
+    /// Takes effect with the next flush.
+    fn set_caret_shape(&mut self, shape: CaretShape) -> anyhow::Result<()>;
+
+    fn set_title(&mut self, title: &str) -> anyhow::Result<()>;
+
+    fn set_mouse_capture(&mut self, enable: bool) -> anyhow::Result<()>;
+
+    fn theme(&self) -> &Theme;
+
+    fn set_theme(&mut self, theme: Theme);
+
+    fn flush(&mut self) -> anyhow::Result<()>;
+
+    fn terminate(&mut self) -> anyhow::Result<()>;
+
+    fn print_at(&mut self, col: u16, row: u16, clear: bool, text: &str) -> anyhow::Result<()> {
+        if clear {
+            self.clear_row(row);
+        }
+        self.print(col, row, text, Style::default());
+        Ok(())
+    }
+
+    fn print_styled_at(
+        &mut self,
+        col: u16,
+        row: u16,
+        clear: bool,
+        text: &str,
+        element: Element,
+    ) -> anyhow::Result<()> {
+        if clear {
+            self.clear_row(row);
+        }
+        let style = self.theme().style(element);
+        self.print(col, row, text, style);
+        Ok(())
+    }
+
+    fn print_annotated_at(
+        &mut self,
+        col: u16,
+        row: u16,
+        clear: bool,
+        annotated_string: &AnnotatedString,
+    ) -> anyhow::Result<()> {
+        if clear {
+            self.clear_row(row);
+        }
+        annotated_string.into_iter().fold(col, |col, part| {
+            let style = part
+                .annotation
+                .map_or_else(Style::default, |annotation| self.theme().style(annotation));
+            self.print(col, row, part.inner, style)
+        });
+        Ok(())
+    }
+}
+
+pub struct CrosstermBackend {
+    screen: Screen,
+    theme: Theme,
+    /// The shape last sent to the terminal, `init` starts with a block.
+    caret_shape: CaretShape,
+}
+
+impl CrosstermBackend {
+    pub fn new() -> anyhow::Result<Self> {
+        terminal::init()?;
+        let (width, height) = terminal::size()?;
+        let mut screen = Screen::new(width, height);
+        screen.set_color_depth(ColorDepth::detect());
+        Ok(Self {
+            screen,
+            theme: Theme::default(),
+            caret_shape: CaretShape::Block,
+        })
+    }
+}
+
+impl Backend for CrosstermBackend {
+    fn size(&self) -> anyhow::Result<(u16, u16)> {
+        terminal::size()
+    }
+
+    fn resize(&mut self, width: u16, height: u16) {
+        self.screen.resize(width, height);
+    }
+
+    fn clear_row(&mut self, row: u16) {
+        self.screen.clear_row(row);
+    }
+
+    fn print(&mut self, col: u16, row: u16, text: &str, style: Style) -> u16 {
+        self.screen.print(col, row, text, style)
+    }
+
+    fn move_caret(&mut self, col: u16, row: u16) {
+        self.screen.move_caret(col, row);
+    }
+
+    fn set_caret_visible(&mut self, visible: bool) {
+        self.screen.set_caret_visible(visible);
+    }
+
+    fn set_caret_shape(&mut self, shape: CaretShape) -> anyhow::Result<()> {
+        if shape != self.caret_shape {
+            terminal::set_cursor_style(match shape {
+                CaretShape::Block => SetCursorStyle::SteadyBlock,
+                CaretShape::Bar => SetCursorStyle::SteadyBar,
+                CaretShape::Underline => SetCursorStyle::SteadyUnderScore,
+            })?;
+            self.caret_shape = shape;
+        }
+        Ok(())
+    }
+
+    fn set_title(&mut self, title: &str) -> anyhow::Result<()> {
+        terminal::set_title(title)
+    }
+
+    fn set_mouse_capture(&mut self, enable: bool) -> anyhow::Result<()> {
+        terminal::set_mouse_capture(enable)?;
+        stdout().flush().context("flush stdout")
+    }
+
+    fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.screen.invalidate();
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.screen.flush(&mut stdout()).context("flush the screen")
+    }
+
+    fn terminate(&mut self) -> anyhow::Result<()> {
+        terminal::terminate()
+    }
+}
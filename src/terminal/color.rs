@@ -0,0 +1,205 @@
+use crossterm::style::Color;
+
+/// The 16 ANSI colors in the order of their indices, with xterm's values.
+const ANSI_16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (205, 0, 0)),
+    (Color::DarkGreen, (0, 205, 0)),
+    (Color::DarkYellow, (205, 205, 0)),
+    (Color::DarkBlue, (0, 0, 238)),
+    (Color::DarkMagenta, (205, 0, 205)),
+    (Color::DarkCyan, (0, 205, 205)),
+    (Color::Grey, (229, 229, 229)),
+    (Color::DarkGrey, (127, 127, 127)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (92, 92, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// The levels of each channel in the 6x6x6 color cube of the 256 color palette.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    #[default]
+    TrueColor,
+    Ansi256,
+    /// The Linux console and terminals which claim nothing better.
+    Ansi16,
+}
+
+impl ColorDepth {
+    /// What `$COLORTERM` and `$TERM` promise.
+    pub fn detect() -> Self {
+        let var = |name| std::env::var(name).unwrap_or_default();
+        Self::from_env(&var("COLORTERM"), &var("TERM"))
+    }
+
+    fn from_env(colorterm: &str, term: &str) -> Self {
+        if matches!(colorterm, "truecolor" | "24bit") || term.ends_with("-direct") {
+            Self::TrueColor
+        } else if term.contains("256color") {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+
+    pub fn downsample(self, color: Color) -> Color {
+        match (self, color) {
+            (Self::TrueColor, color) => color,
+            (Self::Ansi256, Color::Rgb { r, g, b }) => Color::AnsiValue(ansi_256(r, g, b)),
+            (Self::Ansi16, Color::Rgb { r, g, b }) => ansi_16(r, g, b),
+            (Self::Ansi16, Color::AnsiValue(n)) => match ANSI_16.get(n as usize) {
+                Some((color, _)) => *color,
+                None => {
+                    let (r, g, b) = ansi_256_rgb(n);
+                    ansi_16(r, g, b)
+                }
+            },
+            (_, color) => color,
+        }
+    }
+}
+
+/// Parses `#rrggbb`, a palette index from 0 to 255 or a color name like `red`
+/// or `dark_blue`.
+pub fn parse_color(s: &str) -> anyhow::Result<Color> {
+    if let Some(hex) = s.strip_prefix('#')
+        && hex.len() == 6
+        && let Ok(rgb) = u32::from_str_radix(hex, 16)
+    {
+        let [_, r, g, b] = rgb.to_be_bytes();
+        return Ok(Color::Rgb { r, g, b });
+    }
+    if let Ok(n) = s.parse() {
+        return Ok(Color::AnsiValue(n));
+    }
+    Ok(match s.to_ascii_lowercase().replace('-', "_").as_str() {
+        "black" => Color::Black,
+        "dark_red" => Color::DarkRed,
+        "dark_green" => Color::DarkGreen,
+        "dark_yellow" => Color::DarkYellow,
+        "dark_blue" => Color::DarkBlue,
+        "dark_magenta" => Color::DarkMagenta,
+        "dark_cyan" => Color::DarkCyan,
+        "grey" | "gray" => Color::Grey,
+        "dark_grey" | "dark_gray" => Color::DarkGrey,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        _ => anyhow::bail!("Invalid color: {s}, expected #rrggbb, 0 to 255 or a color name"),
+    })
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    [(r1, r2), (g1, g2), (b1, b2)]
+        .into_iter()
+        .map(|(a, b)| u32::from(a.abs_diff(b)).pow(2))
+        .sum()
+}
+
+fn ansi_16(r: u8, g: u8, b: u8) -> Color {
+    ANSI_16
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb, (r, g, b)))
+        .map_or(Color::Reset, |(color, _)| *color)
+}
+
+/// The closest of the color cube and the gray ramp of the 256 color palette.
+fn ansi_256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |value: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|idx| CUBE_LEVELS[*idx].abs_diff(value))
+            .unwrap_or_default() as u8
+    };
+    let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+    let average = ((u16::from(r) + u16::from(g) + u16::from(b)) / 3) as u8;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23);
+    if distance(ansi_256_rgb(gray), (r, g, b)) < distance(ansi_256_rgb(cube), (r, g, b)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+fn ansi_256_rgb(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..16 => ANSI_16[n as usize].1,
+        16..232 => {
+            let idx = n - 16;
+            (
+                CUBE_LEVELS[(idx / 36) as usize],
+                CUBE_LEVELS[(idx / 6 % 6) as usize],
+                CUBE_LEVELS[(idx % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + 10 * (n - 232);
+            (level, level, level)
+        }
+    }
+}
+
+#[test]
+fn detect_color_depth() {
+    assert_eq!(
+        ColorDepth::from_env("truecolor", "xterm-256color"),
+        ColorDepth::TrueColor
+    );
+    assert_eq!(
+        ColorDepth::from_env("", "tmux-256color"),
+        ColorDepth::Ansi256
+    );
+    assert_eq!(ColorDepth::from_env("", "linux"), ColorDepth::Ansi16);
+    assert_eq!(ColorDepth::from_env("", ""), ColorDepth::Ansi16);
+}
+
+#[test]
+fn downsample_colors() {
+    let rgb = |r, g, b| Color::Rgb { r, g, b };
+    assert_eq!(ColorDepth::TrueColor.downsample(rgb(1, 2, 3)), rgb(1, 2, 3));
+    assert_eq!(
+        ColorDepth::Ansi256.downsample(rgb(255, 0, 0)),
+        Color::AnsiValue(196)
+    );
+    assert_eq!(
+        ColorDepth::Ansi256.downsample(rgb(128, 128, 128)),
+        Color::AnsiValue(244)
+    );
+    assert_eq!(ColorDepth::Ansi16.downsample(rgb(250, 10, 10)), Color::Red);
+    assert_eq!(
+        ColorDepth::Ansi16.downsample(Color::AnsiValue(4)),
+        Color::DarkBlue
+    );
+    assert_eq!(
+        ColorDepth::Ansi16.downsample(Color::AnsiValue(231)),
+        Color::White
+    );
+    assert_eq!(ColorDepth::Ansi16.downsample(Color::Yellow), Color::Yellow);
+}
+
+#[test]
+fn parse_colors() {
+    assert_eq!(
+        parse_color("#ff6347").unwrap(),
+        Color::Rgb {
+            r: 255,
+            g: 99,
+            b: 71
+        }
+    );
+    assert_eq!(parse_color("208").unwrap(), Color::AnsiValue(208));
+    assert_eq!(parse_color("Dark-Blue").unwrap(), Color::DarkBlue);
+    assert!(parse_color("#ff63").is_err());
+    assert!(parse_color("256").is_err());
+    assert!(parse_color("teal").is_err());
+}
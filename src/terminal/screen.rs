@@ -0,0 +1,365 @@
+use std::io::{self, Write};
+
+use crossterm::{
+    cursor, queue,
+    style::{self, Color, Print},
+    terminal,
+};
+
+use crate::terminal::color::ColorDepth;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// How the text of a cell is drawn, `None` colors are the terminal's defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub reverse: bool,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl Style {
+    pub const REVERSE: Self = Self {
+        foreground: None,
+        background: None,
+        reverse: true,
+        bold: false,
+        italic: false,
+        underline: false,
+    };
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cell {
+    /// Empty for the right half of a wide grapheme, which the cell before draws.
+    symbol: String,
+    style: Style,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            symbol: String::from(" "),
+            style: Style::default(),
+        }
+    }
+}
+
+impl Cell {
+    fn is_continuation(&self) -> bool {
+        self.symbol.is_empty()
+    }
+}
+
+/// A back buffer of the terminal's cells. Components draw a frame into it and
+/// `flush` sends only the cells which differ from the frame shown before.
+#[derive(Debug, Default)]
+pub struct Screen {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    /// What the terminal shows, `None` when unknown and everything has to be drawn.
+    shown: Option<Vec<Cell>>,
+    caret: (u16, u16),
+    caret_visible: bool,
+    shown_caret: Option<((u16, u16), bool)>,
+    color_depth: ColorDepth,
+}
+
+impl Screen {
+    pub fn new(width: u16, height: u16) -> Self {
+        let mut screen = Self::default();
+        screen.resize(width, height);
+        screen
+    }
+
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.width = width;
+        self.height = height;
+        self.cells = vec![Cell::default(); width as usize * height as usize];
+        self.invalidate();
+    }
+
+    /// Forgets what the terminal shows, the next flush clears and redraws it.
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+        self.shown_caret = None;
+    }
+
+    pub fn set_color_depth(&mut self, depth: ColorDepth) {
+        self.color_depth = depth;
+        self.invalidate();
+    }
+
+    pub fn clear_row(&mut self, row: u16) {
+        for col in 0..self.width {
+            self.set(col, row, Cell::default());
+        }
+    }
+
+    /// Draws `text` from column `col` of `row`, cut off at the right edge.
+    ///
+    /// return: the column after the text
+    pub fn print(&mut self, col: u16, row: u16, text: &str, style: Style) -> u16 {
+        let mut col = col;
+        for grapheme in text.graphemes(true) {
+            let width = grapheme.width() as u16;
+            if width == 0 {
+                continue;
+            }
+            if col.saturating_add(width) > self.width {
+                break;
+            }
+            self.set(
+                col,
+                row,
+                Cell {
+                    symbol: grapheme.to_string(),
+                    style,
+                },
+            );
+            for continuation in 1..width {
+                self.set(
+                    col + continuation,
+                    row,
+                    Cell {
+                        symbol: String::new(),
+                        style,
+                    },
+                );
+            }
+            col = col.saturating_add(width);
+        }
+        col
+    }
+
+    pub fn move_caret(&mut self, col: u16, row: u16) {
+        self.caret = (col, row);
+    }
+
+    pub fn set_caret_visible(&mut self, visible: bool) {
+        self.caret_visible = visible;
+    }
+
+    fn index(&self, col: u16, row: u16) -> Option<usize> {
+        (col < self.width && row < self.height)
+            .then(|| row as usize * self.width as usize + col as usize)
+    }
+
+    /// Replaces a cell, blanking the rest of a wide grapheme it cuts through.
+    fn set(&mut self, col: u16, row: u16, cell: Cell) {
+        let Some(idx) = self.index(col, row) else {
+            return;
+        };
+        if self.cells[idx].is_continuation() && !cell.is_continuation() && col > 0 {
+            self.cells[idx - 1] = Cell::default();
+        }
+        if let Some(next) = self.index(col.saturating_add(1), row)
+            && self.cells[next].is_continuation()
+            && !self.cells[idx].is_continuation()
+        {
+            self.cells[next] = Cell::default();
+        }
+        self.cells[idx] = cell;
+    }
+
+    /// Sends the changes since the last flush to `out`, moving the caret only to
+    /// cells which are not next to the one drawn before.
+    pub fn flush(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let mut position = None;
+        let mut style = Style::default();
+        let mut drawn = self.shown.is_none();
+
+        if drawn {
+            queue!(out, cursor::Hide, terminal::Clear(terminal::ClearType::All))?;
+        }
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let Some(idx) = self.index(col, row) else {
+                    continue;
+                };
+                let cell = &self.cells[idx];
+                if cell.is_continuation() {
+                    continue;
+                }
+                let unchanged = match &self.shown {
+                    Some(shown) => {
+                        let width = cell.symbol.width().max(1);
+                        (idx..idx.saturating_add(width))
+                            .all(|idx| shown.get(idx) == self.cells.get(idx))
+                    }
+                    // A cleared screen already shows blank cells.
+                    None => *cell == Cell::default(),
+                };
+                if unchanged {
+                    continue;
+                }
+
+                if !drawn {
+                    queue!(out, cursor::Hide)?;
+                    drawn = true;
+                }
+                if position != Some((col, row)) {
+                    queue!(out, cursor::MoveTo(col, row))?;
+                }
+                apply_style(out, style, cell.style, self.color_depth)?;
+                style = cell.style;
+                queue!(out, Print(&cell.symbol))?;
+                position = Some((col.saturating_add(cell.symbol.width() as u16), row));
+            }
+        }
+        apply_style(out, style, Style::default(), self.color_depth)?;
+
+        let caret = (self.caret, self.caret_visible);
+        if drawn || self.shown_caret != Some(caret) {
+            let (col, row) = self.caret;
+            queue!(out, cursor::MoveTo(col, row))?;
+            if self.caret_visible {
+                queue!(out, cursor::Show)?;
+            } else {
+                queue!(out, cursor::Hide)?;
+            }
+        }
+        self.shown = Some(self.cells.clone());
+        self.shown_caret = Some(caret);
+        out.flush()
+    }
+}
+
+/// Read back by the test backend.
+#[cfg(test)]
+impl Screen {
+    /// (cols, rows)
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    pub fn row_text(&self, row: u16) -> String {
+        (0..self.width)
+            .filter_map(|col| self.index(col, row))
+            .map(|idx| self.cells[idx].symbol.as_str())
+            .collect()
+    }
+
+    pub fn style_at(&self, col: u16, row: u16) -> Option<Style> {
+        self.index(col, row).map(|idx| self.cells[idx].style)
+    }
+
+    pub fn caret(&self) -> Option<(u16, u16)> {
+        self.caret_visible.then_some(self.caret)
+    }
+}
+
+fn apply_style(out: &mut impl Write, from: Style, to: Style, depth: ColorDepth) -> io::Result<()> {
+    let attributes = [
+        (
+            from.reverse,
+            to.reverse,
+            style::Attribute::Reverse,
+            style::Attribute::NoReverse,
+        ),
+        (
+            from.bold,
+            to.bold,
+            style::Attribute::Bold,
+            style::Attribute::NormalIntensity,
+        ),
+        (
+            from.italic,
+            to.italic,
+            style::Attribute::Italic,
+            style::Attribute::NoItalic,
+        ),
+        (
+            from.underline,
+            to.underline,
+            style::Attribute::Underlined,
+            style::Attribute::NoUnderline,
+        ),
+    ];
+    for (from, to, on, off) in attributes {
+        if from != to {
+            queue!(out, style::SetAttribute(if to { on } else { off }))?;
+        }
+    }
+    let color = |color: Option<Color>| color.map_or(Color::Reset, |color| depth.downsample(color));
+    if from.foreground != to.foreground {
+        queue!(out, style::SetForegroundColor(color(to.foreground)))?;
+    }
+    if from.background != to.background {
+        queue!(out, style::SetBackgroundColor(color(to.background)))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn flush_only_changed_cells() {
+    let mut screen = Screen::new(10, 2);
+    screen.print(0, 0, "hello", Style::default());
+    screen.set_caret_visible(true);
+    let mut out = Vec::new();
+    screen.flush(&mut out).unwrap();
+    let first = String::from_utf8(out).unwrap();
+    assert!(first.starts_with("\x1b[?25l\x1b[2J"));
+    assert!(first.contains("\x1b[1;1Hhello"));
+
+    // The same frame again sends nothing.
+    screen.print(0, 0, "hello", Style::default());
+    let mut out = Vec::new();
+    screen.flush(&mut out).unwrap();
+    assert!(out.is_empty());
+
+    screen.print(0, 0, "help", Style::default());
+    screen.print(
+        3,
+        1,
+        "你",
+        Style {
+            reverse: true,
+            ..Default::default()
+        },
+    );
+    let mut out = Vec::new();
+    screen.flush(&mut out).unwrap();
+    let diff = String::from_utf8(out).unwrap();
+    assert!(diff.contains("\x1b[1;4Hp"));
+    assert!(diff.contains("\x1b[2;4H\x1b[7m你\x1b[27m"));
+    assert!(!diff.contains("hel"));
+    assert!(diff.ends_with("\x1b[1;1H\x1b[?25h"));
+}
+
+#[test]
+fn downsample_colors_for_the_terminal() {
+    let mut screen = Screen::new(4, 1);
+    screen.set_color_depth(ColorDepth::Ansi16);
+    screen.print(
+        0,
+        0,
+        "x",
+        Style {
+            foreground: Some(Color::Rgb { r: 250, g: 0, b: 0 }),
+            bold: true,
+            ..Default::default()
+        },
+    );
+    let mut out = Vec::new();
+    screen.flush(&mut out).unwrap();
+    let frame = String::from_utf8(out).unwrap();
+    assert!(frame.contains("\x1b[1m\x1b[38;5;9mx\x1b[22m\x1b[39m"));
+}
+
+#[test]
+fn overwrite_half_of_a_wide_grapheme() {
+    let mut screen = Screen::new(4, 1);
+    screen.print(0, 0, "你好", Style::default());
+    screen.print(1, 0, "a", Style::default());
+    let symbols = screen
+        .cells
+        .iter()
+        .map(|cell| cell.symbol.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(symbols, [" ", "a", "好", ""]);
+}
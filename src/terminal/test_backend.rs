@@ -0,0 +1,131 @@
+use crate::terminal::{
+    CaretShape,
+    backend::Backend,
+    screen::{Screen, Style},
+    theme::{Element, Theme},
+};
+
+#[derive(Debug)]
+pub struct TestBackend {
+    screen: Screen,
+    title: String,
+    mouse_capture: bool,
+    caret_shape: CaretShape,
+    theme: Theme,
+}
+
+impl TestBackend {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            screen: Screen::new(width, height),
+            title: String::new(),
+            mouse_capture: false,
+            caret_shape: CaretShape::default(),
+            theme: Theme::default(),
+        }
+    }
+
+    /// The text shown on `row`, trailing blanks included.
+    pub fn row_text(&self, row: u16) -> String {
+        self.screen.row_text(row)
+    }
+
+    pub fn style_at(&self, col: u16, row: u16) -> Option<Style> {
+        self.screen.style_at(col, row)
+    }
+
+    /// Where the caret is, `None` while it is hidden.
+    pub fn caret(&self) -> Option<(u16, u16)> {
+        self.screen.caret()
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn mouse_capture(&self) -> bool {
+        self.mouse_capture
+    }
+
+    pub fn caret_shape(&self) -> CaretShape {
+        self.caret_shape
+    }
+}
+
+impl Backend for TestBackend {
+    fn size(&self) -> anyhow::Result<(u16, u16)> {
+        Ok(self.screen.size())
+    }
+
+    fn resize(&mut self, width: u16, height: u16) {
+        self.screen.resize(width, height);
+    }
+
+    fn clear_row(&mut self, row: u16) {
+        self.screen.clear_row(row);
+    }
+
+    fn print(&mut self, col: u16, row: u16, text: &str, style: Style) -> u16 {
+        self.screen.print(col, row, text, style)
+    }
+
+    fn move_caret(&mut self, col: u16, row: u16) {
+        self.screen.move_caret(col, row);
+    }
+
+    fn set_caret_visible(&mut self, visible: bool) {
+        self.screen.set_caret_visible(visible);
+    }
+
+    fn set_caret_shape(&mut self, shape: CaretShape) -> anyhow::Result<()> {
+        self.caret_shape = shape;
+        Ok(())
+    }
+
+    fn set_title(&mut self, title: &str) -> anyhow::Result<()> {
+        self.title = title.to_string();
+        Ok(())
+    }
+
+    fn set_mouse_capture(&mut self, enable: bool) -> anyhow::Result<()> {
+        self.mouse_capture = enable;
+        Ok(())
+    }
+
+    fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn terminate(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn draw_into_memory() {
+    let mut backend = TestBackend::new(8, 2);
+    backend.print_at(0, 0, true, "old text").unwrap();
+    backend.print_at(0, 0, true, "new").unwrap();
+    backend
+        .print_styled_at(2, 1, false, "ab", Element::StatusBar)
+        .unwrap();
+    backend.move_caret(3, 1);
+    backend.set_caret_visible(true);
+
+    assert_eq!(backend.row_text(0), "new     ");
+    assert_eq!(backend.row_text(1), "  ab    ");
+    assert!(!backend.style_at(1, 1).unwrap().reverse);
+    assert!(backend.style_at(2, 1).unwrap().reverse);
+    assert_eq!(backend.caret(), Some((3, 1)));
+
+    backend.set_caret_visible(false);
+    assert_eq!(backend.caret(), None);
+}
@@ -0,0 +1,206 @@
+use crossterm::style::Color;
+
+use crate::{editor::annotated::annotation::AnnotationType, terminal::screen::Style};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Element {
+    Match,
+    SelectedMatch,
+    Number,
+    Comment,
+    Keyword,
+    Type,
+    Char,
+    Lifetime,
+    String,
+    MatchingBracket,
+    WildMenuSelection,
+    Selection,
+    StatusBar,
+    /// The line numbers left of the text.
+    Gutter,
+    /// The `~` of the rows after the last line.
+    NonText,
+}
+
+impl Element {
+    /// Every element, in the order of `NAMES`.
+    pub const ALL: &[Self] = &[
+        Self::Match,
+        Self::SelectedMatch,
+        Self::Number,
+        Self::Comment,
+        Self::Keyword,
+        Self::Type,
+        Self::Char,
+        Self::Lifetime,
+        Self::String,
+        Self::MatchingBracket,
+        Self::WildMenuSelection,
+        Self::Selection,
+        Self::StatusBar,
+        Self::Gutter,
+        Self::NonText,
+    ];
+
+    pub const NAMES: &[&str] = &[
+        "match",
+        "selected_match",
+        "number",
+        "comment",
+        "keyword",
+        "type",
+        "char",
+        "lifetime",
+        "string",
+        "matching_bracket",
+        "wild_menu_selection",
+        "selection",
+        "status_bar",
+        "gutter",
+        "non_text",
+    ];
+
+    pub fn find(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .position(|other| *other == name)
+            .map(|idx| Self::ALL[idx])
+    }
+}
+
+impl From<AnnotationType> for Element {
+    fn from(annotation: AnnotationType) -> Self {
+        match annotation {
+            AnnotationType::Match => Self::Match,
+            AnnotationType::SelectedMatch => Self::SelectedMatch,
+            AnnotationType::Number => Self::Number,
+            AnnotationType::Comment => Self::Comment,
+            AnnotationType::Keyword => Self::Keyword,
+            AnnotationType::Type => Self::Type,
+            AnnotationType::Char => Self::Char,
+            AnnotationType::Lifetime => Self::Lifetime,
+            AnnotationType::String => Self::String,
+            AnnotationType::MatchingBracket => Self::MatchingBracket,
+            AnnotationType::WildMenuSelection => Self::WildMenuSelection,
+            AnnotationType::Selection => Self::Selection,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    name: String,
+    /// Indexed by `Element`.
+    styles: Vec<Style>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let rgb = |r, g, b| Some(Color::Rgb { r, g, b });
+        let colored = |foreground, background| Style {
+            foreground,
+            background,
+            ..Default::default()
+        };
+        let mut theme = Self {
+            name: String::from("default"),
+            styles: vec![Style::default(); Element::ALL.len()],
+        };
+        for (element, style) in [
+            (
+                Element::Match,
+                colored(Some(Color::White), rgb(211, 211, 211)),
+            ),
+            (
+                Element::SelectedMatch,
+                colored(Some(Color::White), rgb(255, 255, 153)),
+            ),
+            (Element::Number, colored(rgb(255, 99, 71), None)),
+            (Element::Comment, colored(rgb(34, 139, 34), None)),
+            (Element::Keyword, colored(rgb(100, 149, 237), None)),
+            (Element::Type, colored(rgb(175, 225, 175), None)),
+            (Element::Char, colored(rgb(255, 191, 0), None)),
+            (Element::Lifetime, colored(rgb(102, 205, 170), None)),
+            (Element::String, colored(rgb(255, 179, 102), None)),
+            (
+                Element::MatchingBracket,
+                colored(Some(Color::White), rgb(70, 130, 180)),
+            ),
+            (
+                Element::WildMenuSelection,
+                colored(Some(Color::Black), Some(Color::Yellow)),
+            ),
+            (
+                Element::Selection,
+                colored(Some(Color::White), rgb(72, 61, 139)),
+            ),
+            (Element::StatusBar, Style::REVERSE),
+        ] {
+            theme.set_style(element, style);
+        }
+        theme
+    }
+}
+
+impl Theme {
+    pub const BUILTIN: &[&str] = &["default", "mono"];
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::default()),
+            // Without colors, matches and selections are drawn in reverse video.
+            "mono" => {
+                let mut theme = Self {
+                    name: name.to_string(),
+                    styles: vec![Style::default(); Element::ALL.len()],
+                };
+                for element in [
+                    Element::Match,
+                    Element::SelectedMatch,
+                    Element::MatchingBracket,
+                    Element::WildMenuSelection,
+                    Element::Selection,
+                    Element::StatusBar,
+                ] {
+                    theme.set_style(element, Style::REVERSE);
+                }
+                Some(theme)
+            }
+            _ => None,
+        }
+    }
+
+    /// The default theme under another name, for theme files to change.
+    pub fn named(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn style(&self, element: impl Into<Element>) -> Style {
+        self.styles[element.into() as usize]
+    }
+
+    pub fn set_style(&mut self, element: Element, style: Style) {
+        self.styles[element as usize] = style;
+    }
+}
+
+#[test]
+fn elements_are_in_name_order() {
+    assert_eq!(Element::ALL.len(), Element::NAMES.len());
+    assert!(
+        Element::ALL
+            .iter()
+            .enumerate()
+            .all(|(idx, element)| *element as usize == idx
+                && Element::find(Element::NAMES[idx]) == Some(*element))
+    );
+    assert_eq!(Element::find("status"), None);
+}